@@ -1,8 +1,12 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use serde::Serialize;
 use std::env;
 
+use crate::plugin::LifecyclePlugin;
+use crate::util::openlineage::OpenLineageClient;
+
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 #[derive(Serialize, Clone)]
@@ -17,6 +21,12 @@ pub struct BoxContext {
 
     #[serde(rename = "environmentVariables", skip_serializing)]
     pub environment_variables: HashMap<String, String>,
+
+    #[serde(skip_serializing)]
+    pub openlineage: Option<OpenLineageClient>,
+
+    #[serde(skip_serializing)]
+    pub lifecycle_plugins: Vec<Arc<dyn LifecyclePlugin>>,
 }
 
 impl BoxContext {
@@ -37,6 +47,21 @@ impl BoxContext {
             version: VERSION.to_owned(),
             commandline_arguments,
             environment_variables,
+            openlineage: None,
+            lifecycle_plugins: vec![],
         }
     }
+
+    pub fn with_openlineage(mut self, openlineage: OpenLineageClient) -> Self {
+        self.openlineage = Some(openlineage);
+        self
+    }
+
+    pub fn with_lifecycle_plugins(
+        mut self,
+        lifecycle_plugins: Vec<Arc<dyn LifecyclePlugin>>,
+    ) -> Self {
+        self.lifecycle_plugins = lifecycle_plugins;
+        self
+    }
 }
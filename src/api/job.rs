@@ -0,0 +1,163 @@
+use std::sync::Arc;
+
+use datafusion::prelude::*;
+use serde_json::Value;
+
+use crate::api::*;
+use crate::plugin::{self, LifecyclePlugin};
+use crate::util::*;
+
+/// Builds a job in code or from a job file and executes it, returning the result and events
+/// rather than printing them
+///
+/// ```no_run
+/// # async fn run() -> box_lib::util::Result<()> {
+/// use box_lib::api::{new_execution_context, BoxContext, Job};
+/// use datafusion::prelude::ExecutionConfig;
+/// use serde_json::json;
+///
+/// let mut ctx = new_execution_context(ExecutionConfig::new());
+/// let job_result = Job::new(BoxContext::new(None, None))
+///     .with_stage_config(json!({
+///         "type": "SQLTransform",
+///         "sql": "SELECT 1 AS one",
+///         "outputView": "one"
+///     }))?
+///     .execute(&mut ctx)
+///     .await?;
+/// assert!(job_result.result.is_some());
+/// # Ok(())
+/// # }
+/// ```
+pub struct Job {
+    box_ctx: BoxContext,
+    stages: Vec<Box<dyn PipelineStage>>,
+    show_entry_exit: bool,
+}
+
+impl Job {
+    pub fn new(box_ctx: BoxContext) -> Self {
+        Self {
+            box_ctx,
+            stages: vec![],
+            show_entry_exit: true,
+        }
+    }
+
+    /// Parse the stages and lifecycle plugins of a job file. Hocon style parameters are converted
+    /// and all placeholders must be resolvable.
+    pub fn from_config(box_ctx: BoxContext, config: &str) -> Result<Self> {
+        let config = variables::replace_hocon_parameters(config);
+        let lifecycle_plugins = parse_lifecycle_plugins(box_ctx.clone(), &config, false, false)?;
+        let box_ctx = box_ctx.with_lifecycle_plugins(lifecycle_plugins);
+        let stages = parse_config(box_ctx.clone(), &config, false, false)?;
+        Ok(Self {
+            box_ctx,
+            stages,
+            show_entry_exit: true,
+        })
+    }
+
+    /// Append a stage implemented in code
+    pub fn with_stage(mut self, stage: Box<dyn PipelineStage>) -> Self {
+        self.stages.push(stage);
+        self
+    }
+
+    /// Append a stage from its json configuration as it would appear in a job file
+    pub fn with_stage_config(mut self, config: Value) -> Result<Self> {
+        let object = match config {
+            Value::Object(object) => object,
+            v => return Err(BoxError::new(format!("Expected object. Got '{:?}'.", v))),
+        };
+        let stage =
+            plugin::registry().create_stage(&object, &params(&self.box_ctx), false, false)?;
+        self.stages.push(stage);
+        Ok(self)
+    }
+
+    pub fn with_lifecycle_plugin(mut self, lifecycle_plugin: Arc<dyn LifecyclePlugin>) -> Self {
+        self.box_ctx.lifecycle_plugins.push(lifecycle_plugin);
+        self
+    }
+
+    /// Whether to emit the job level enter and exit events
+    pub fn with_show_entry_exit(mut self, show_entry_exit: bool) -> Self {
+        self.show_entry_exit = show_entry_exit;
+        self
+    }
+
+    pub async fn execute(
+        self,
+        ctx: &mut ExecutionContext,
+    ) -> std::result::Result<JobResult, JobError> {
+        self.execute_with(ctx, |_| {}).await
+    }
+
+    /// Execute passing each event to `on_event` as it happens
+    pub async fn execute_with<F>(
+        self,
+        ctx: &mut ExecutionContext,
+        on_event: F,
+    ) -> std::result::Result<JobResult, JobError>
+    where
+        F: FnMut(&Event) + Send,
+    {
+        execute_with(
+            self.box_ctx,
+            ctx,
+            self.stages,
+            self.show_entry_exit,
+            on_event,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_execute_error_events() -> Result<()> {
+        let mut ctx = new_execution_context(ExecutionConfig::new());
+        let err = Job::new(BoxContext::new(None, None))
+            .with_show_entry_exit(false)
+            .with_stage_config(json!({
+                "type": "SQLTransform",
+                "sql": "SELECT 1 AS one",
+                "outputView": "one"
+            }))?
+            .with_stage_config(json!({
+                "type": "SQLTransform",
+                "sql": "SELECT * FROM missing",
+                "outputView": "two"
+            }))?
+            .execute(&mut ctx)
+            .await
+            .err()
+            .unwrap();
+
+        let events = err
+            .events
+            .iter()
+            .map(|event| (event.event.as_str(), event.success))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            vec![
+                ("enter", None),
+                ("exit", None),
+                ("enter", None),
+                ("exit", Some(false))
+            ]
+        );
+
+        // the stage error wraps the DataFusion error
+        let source = std::error::Error::source(&err.error)
+            .and_then(|source| source.downcast_ref::<BoxError>());
+        assert!(matches!(source, Some(BoxError::DataFusionError(_))));
+        Ok(())
+    }
+}
@@ -1,20 +1,22 @@
 mod box_context;
+mod job;
 
 pub use box_context::BoxContext;
+pub use job::Job;
 
+use std::collections::HashMap;
+use std::fmt;
 use std::sync::Arc;
 use std::time::Instant;
-use std::{collections::HashMap, str::FromStr};
 
 use datafusion::prelude::*;
 
 use async_trait::async_trait;
 use serde::Serialize;
-use serde_json::value::to_value;
 use serde_json::Value;
 
-use crate::extract::{DelimitedExtract, ParquetExtract};
-use crate::transform::SQLTransform;
+use crate::plugin;
+use crate::util::openlineage::OpenLineageRun;
 use crate::util::*;
 
 #[async_trait]
@@ -28,7 +30,7 @@ pub trait PipelineStage: Send + Sync {
     ) -> Result<Option<Arc<dyn DataFrame>>>;
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Default, Clone, Debug)]
 pub struct Event {
     pub event: String,
 
@@ -46,6 +48,13 @@ pub struct Event {
 
     #[serde(rename = "configuration", skip_serializing_if = "Option::is_none")]
     pub box_ctx: Option<Value>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lineage: Option<Value>,
+
+    /// The number of rows of the stage's result when known without executing it again
+    #[serde(rename = "rowCount", skip_serializing_if = "Option::is_none")]
+    pub row_count: Option<usize>,
 }
 
 pub fn parse_config(
@@ -54,142 +63,316 @@ pub fn parse_config(
     allow_missing_placeholders: bool,
     allow_missing_parameters: bool,
 ) -> Result<Vec<Box<dyn PipelineStage>>> {
-    // prepare params
+    let params = params(&box_ctx);
+    let registry = plugin::registry();
+    job_section(config, "stages")?
+        .iter()
+        .map(|v| match v {
+            Value::Object(object) => registry.create_stage(
+                object,
+                &params,
+                allow_missing_placeholders,
+                allow_missing_parameters,
+            ),
+            v => Err(BoxError::new(format!("Expected object. Got '{:?}'.", v))),
+        })
+        .collect::<Result<Vec<_>>>()
+}
+
+/// The parameters the stages of a job refer to which have no value
+pub fn missing_parameters(box_ctx: &BoxContext, config: &str) -> Result<Vec<String>> {
+    let params = params(box_ctx);
+    let registry = plugin::registry();
+    let mut missing = job_section(config, "stages")?
+        .iter()
+        .filter_map(Value::as_object)
+        .flat_map(|object| registry.missing_parameters(object, &params))
+        .collect::<Vec<_>>();
+    missing.sort();
+    missing.dedup();
+    Ok(missing)
+}
+
+/// Parse the lifecycle plugins of a job given in the `{"plugins": {"lifecycle": [...]}}` form
+pub fn parse_lifecycle_plugins(
+    box_ctx: BoxContext,
+    config: &str,
+    allow_missing_placeholders: bool,
+    allow_missing_parameters: bool,
+) -> Result<Vec<Arc<dyn plugin::LifecyclePlugin>>> {
+    let params = params(&box_ctx);
+    let registry = plugin::registry();
+    job_section(config, "lifecycle")?
+        .iter()
+        .map(|v| match v {
+            Value::Object(object) => registry.create_lifecycle_plugin(
+                object,
+                &params,
+                allow_missing_placeholders,
+                allow_missing_parameters,
+            ),
+            v => Err(BoxError::new(format!("Expected object. Got '{:?}'.", v))),
+        })
+        .collect::<Result<Vec<_>>>()
+}
+
+/// The job parameters: the environment variables overridden by the command line arguments
+pub(crate) fn params(box_ctx: &BoxContext) -> HashMap<String, String> {
     let mut params = box_ctx.environment_variables.clone();
-    params.extend(box_ctx.commandline_arguments.unwrap_or_else(HashMap::new));
+    params.extend(box_ctx.commandline_arguments.clone().unwrap_or_default());
+    params
+}
 
+/// A job is either an array of stages or an object of `stages` and `plugins`
+fn job_section(config: &str, section: &str) -> Result<Vec<Value>> {
     // Parse the string of data into serde_json::Value.
     let v = serde_json::from_str(config)?;
+    let v = match (v, section) {
+        (Value::Array(stages), "stages") => return Ok(stages),
+        (Value::Array(_), _) => return Ok(vec![]),
+        (Value::Object(mut object), "stages") => object.remove("stages"),
+        (Value::Object(mut object), _) => match object.remove("plugins") {
+            Some(Value::Object(mut plugins)) => plugins.remove(section),
+            Some(v) => {
+                return Err(BoxError::new(format!(
+                    "Expected 'plugins' to be an object. Got '{:?}'.",
+                    v
+                )))
+            }
+            None => None,
+        },
+        (v, _) => return Err(BoxError::new(format!("Expected array. Got '{:?}'.", v))),
+    };
     match v {
-        Value::Array(stage) => stage
-            .iter()
-            .map(|v| match v {
-                Value::Object(object) => match v["type"].as_str() {
-                    Some("DelimitedExtract") => {
-                        let json = variables::substitute_variables(Value::to_string(&to_value(object)?), &params, allow_missing_placeholders, allow_missing_parameters)?;
-                        DelimitedExtract::try_new(json)
-                        .map(|s| Box::new(s) as Box<dyn PipelineStage>)
-                    }
-                    Some("ParquetExtract") => {
-                        let json = variables::substitute_variables(Value::to_string(&to_value(object)?), &params, allow_missing_placeholders, allow_missing_parameters)?;
-                        ParquetExtract::try_new(json)
-                        .map(|s| Box::new(s) as Box<dyn PipelineStage>)
-                    }
-                    Some("SQLTransform") => {
-                        // don't try to replace variables within the sql value
-                        let obj= object.iter().map(|(key, value)| {
-                            if key != "sql" {
-                                let value = variables::substitute_variables(Value::to_string(value), &params, allow_missing_placeholders, allow_missing_parameters)?;
-                                let value = Value::from_str(&value)?;
-                                Ok((key.clone(), value))
-                            } else {
-                                Ok((key.clone(), value.clone()))
-                            }
-                        })
-                        .collect::<Result<serde_json::Map<_,_>>>()?;
-
-                        let json = Value::to_string(&to_value(obj)?);
-                        SQLTransform::try_new(json)
-                        .map(|s| Box::new(s) as Box<dyn PipelineStage>)
-                    },
-                    Some(t) => Err(BoxError::new(format!("Expected field 'type' to be one of ['DelimitedExtract', 'ParquetExtract', 'SQLTransform']. Got '{}'.", t))),
-                    None => Err(BoxError::new("Missing required field 'type'.".to_string())),
-                },
-                v =>Err(BoxError::new(format!("Expected object. Got '{:?}'.", v))),
-            })
-
-            .collect::<Result<Vec<_>>>(),
-        _ => Err(BoxError::new(format!("Expected array. Got '{:?}'.", v))),
+        Some(Value::Array(values)) => Ok(values),
+        None if section != "stages" => Ok(vec![]),
+        None => Err(BoxError::new(
+            "Missing required field 'stages'.".to_string(),
+        )),
+        Some(v) => Err(BoxError::new(format!(
+            "Expected '{}' to be an array. Got '{:?}'.",
+            section, v
+        ))),
+    }
+}
+
+/// Create an execution context with the builtin and plugin functions registered
+pub fn new_execution_context(config: ExecutionConfig) -> ExecutionContext {
+    let mut ctx = ExecutionContext::with_config(config);
+    plugin::registry().register_functions(&mut ctx);
+    ctx
+}
+
+/// The result of the final stage and the events emitted while executing a job
+pub struct JobResult {
+    pub result: Option<Arc<dyn DataFrame>>,
+    pub events: Vec<Event>,
+}
+
+/// The error which stopped a job and the events emitted up to and including the failure
+#[derive(Debug)]
+pub struct JobError {
+    pub error: BoxError,
+    pub events: Vec<Event>,
+}
+
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.error)
     }
 }
 
+impl std::error::Error for JobError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl From<JobError> for BoxError {
+    fn from(e: JobError) -> Self {
+        e.error
+    }
+}
+
+/// Execute the stages printing each event as a JSON line
 pub async fn execute(
     box_ctx: BoxContext,
     execution_ctx: &mut ExecutionContext,
     stages: Vec<Box<dyn PipelineStage>>,
     show_entry_exit: bool,
 ) -> Result<Option<Arc<dyn DataFrame>>> {
+    execute_with(box_ctx, execution_ctx, stages, show_entry_exit, |event| {
+        println!("{}", serde_json::to_string(event).unwrap())
+    })
+    .await
+    .map(|job_result| job_result.result)
+    .map_err(BoxError::from)
+}
+
+/// Execute the stages passing each event to `on_event` as it happens. The events are also
+/// returned with the result or the error.
+pub async fn execute_with<F>(
+    box_ctx: BoxContext,
+    execution_ctx: &mut ExecutionContext,
+    stages: Vec<Box<dyn PipelineStage>>,
+    show_entry_exit: bool,
+    mut on_event: F,
+) -> std::result::Result<JobResult, JobError>
+where
+    F: FnMut(&Event) + Send,
+{
+    let mut events = vec![];
+    let mut emit = |event: Event| {
+        on_event(&event);
+        events.push(event);
+    };
     let mut result: Option<Arc<dyn DataFrame>> = None;
+    let mut lineage: Vec<Value> = vec![];
+    let mut openlineage_run = box_ctx.openlineage.clone().map(OpenLineageRun::new);
     let job_start = Instant::now();
 
+    if let Some(openlineage_run) = &mut openlineage_run {
+        let values = stages
+            .iter()
+            .map(|stage| stage.to_value())
+            .collect::<Vec<_>>();
+        openlineage_run.start(&values).await;
+    }
+
     if show_entry_exit {
-        println!(
-            "{}",
-            serde_json::to_string(&Event {
-                event: "enter".to_string(),
-                success: None,
-                error: None,
-                duration: None,
-                stage: None,
-                box_ctx: Some(serde_json::to_value(box_ctx.clone()).unwrap()),
-            })
-            .unwrap()
-        );
+        emit(Event {
+            event: "enter".to_string(),
+            success: None,
+            error: None,
+            duration: None,
+            stage: None,
+            box_ctx: Some(serde_json::to_value(box_ctx.clone()).unwrap()),
+            lineage: None,
+            row_count: None,
+        });
     }
 
     for mut stage in stages {
         let stage_start = Instant::now();
 
-        println!(
-            "{}",
-            serde_json::to_string(&Event {
-                event: "enter".to_string(),
-                success: None,
-                error: None,
-                duration: None,
-                stage: Some(stage.to_value()),
-                box_ctx: None,
-            })
-            .unwrap()
-        );
-
-        result = stage
-            .execute(box_ctx.clone(), execution_ctx)
-            .await
-            .map_err(|err| {
-                println!(
-                    "{}",
-                    serde_json::to_string(&Event {
-                        event: "exit".to_string(),
-                        duration: Some(job_start.elapsed().as_millis() as usize),
-                        stage: Some(stage.to_value()),
-                        box_ctx: None,
-                        success: Some(false),
-                        error: Some(err.to_string()),
-                    })
-                    .unwrap()
-                );
-                err
-            })?;
-
-        println!(
-            "{}",
-            serde_json::to_string(&Event {
-                event: "exit".to_string(),
-                success: None,
-                error: None,
-                duration: Some(stage_start.elapsed().as_millis() as usize),
-                stage: Some(stage.to_value()),
-                box_ctx: None,
-            })
-            .unwrap()
-        );
+        emit(Event {
+            event: "enter".to_string(),
+            success: None,
+            error: None,
+            duration: None,
+            stage: Some(stage.to_value()),
+            box_ctx: None,
+            lineage: None,
+            row_count: None,
+        });
+
+        let outcome = async {
+            let value = stage.to_value();
+            for lifecycle_plugin in &box_ctx.lifecycle_plugins {
+                lifecycle_plugin.before_stage(&box_ctx, &value).await?;
+            }
+            let result = stage.execute(box_ctx.clone(), execution_ctx).await?;
+            let value = stage.to_value();
+            for lifecycle_plugin in &box_ctx.lifecycle_plugins {
+                lifecycle_plugin
+                    .after_stage(&box_ctx, &value, result.clone())
+                    .await?;
+            }
+            Ok::<_, BoxError>(result)
+        }
+        .await;
+
+        result = match outcome {
+            Ok(result) => result,
+            Err(err) => {
+                emit(Event {
+                    event: "exit".to_string(),
+                    duration: Some(job_start.elapsed().as_millis() as usize),
+                    stage: Some(stage.to_value()),
+                    box_ctx: None,
+                    success: Some(false),
+                    error: Some(err.to_string()),
+                    lineage: None,
+                    row_count: None,
+                });
+                for lifecycle_plugin in &box_ctx.lifecycle_plugins {
+                    if let Err(plugin_err) = lifecycle_plugin
+                        .on_error(&box_ctx, &stage.to_value(), &err)
+                        .await
+                    {
+                        eprintln!("Lifecycle plugin on_error failed: {}", plugin_err);
+                    }
+                }
+                job_end(&box_ctx, false).await;
+                if let Some(openlineage_run) = &openlineage_run {
+                    openlineage_run.fail(&err.to_string()).await;
+                }
+                return Err(JobError {
+                    error: BoxError::ExecutionError {
+                        stage: stage.to_value(),
+                        error: Box::new(err),
+                    },
+                    events,
+                });
+            }
+        };
+
+        let value = stage.to_value();
+        if let Some(columns) = value.get("columnLineage") {
+            lineage.push(serde_json::json!({
+                "outputView": value["outputView"],
+                "columns": columns,
+            }));
+        }
+        if let Some(openlineage_run) = &mut openlineage_run {
+            openlineage_run.record_stage(&value, execution_ctx);
+        }
+
+        let row_count = match &result {
+            Some(df) => statistics::exact_row_count(execution_ctx, df.clone())
+                .await
+                .ok()
+                .flatten(),
+            None => None,
+        };
+
+        emit(Event {
+            event: "exit".to_string(),
+            success: None,
+            error: None,
+            duration: Some(stage_start.elapsed().as_millis() as usize),
+            stage: Some(value),
+            box_ctx: None,
+            lineage: None,
+            row_count,
+        });
     }
 
     if show_entry_exit {
-        println!(
-            "{}",
-            serde_json::to_string(&Event {
-                event: "exit".to_string(),
-                duration: Some(job_start.elapsed().as_millis() as usize),
-                stage: None,
-                box_ctx: Some(serde_json::to_value(box_ctx.clone()).unwrap()),
-                success: Some(true),
-                error: None
-            })
-            .unwrap()
-        );
+        emit(Event {
+            event: "exit".to_string(),
+            duration: Some(job_start.elapsed().as_millis() as usize),
+            stage: None,
+            box_ctx: Some(serde_json::to_value(box_ctx.clone()).unwrap()),
+            success: Some(true),
+            error: None,
+            lineage: Some(Value::Array(lineage)),
+            row_count: None,
+        });
+    }
+
+    job_end(&box_ctx, true).await;
+    if let Some(openlineage_run) = &openlineage_run {
+        openlineage_run.complete().await;
     }
 
-    Ok(result)
+    Ok(JobResult { result, events })
+}
+
+async fn job_end(box_ctx: &BoxContext, success: bool) {
+    for lifecycle_plugin in &box_ctx.lifecycle_plugins {
+        if let Err(err) = lifecycle_plugin.job_end(box_ctx, success).await {
+            eprintln!("Lifecycle plugin job_end failed: {}", err);
+        }
+    }
 }
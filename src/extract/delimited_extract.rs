@@ -39,6 +39,9 @@ pub struct DelimitedExtract {
     #[serde(default = "default_false")]
     persist: bool,
 
+    #[serde(default = "default_false")]
+    profile: bool,
+
     delimiter: String,
 
     #[serde(rename = "numPartitions", skip_serializing_if = "Option::is_none")]
@@ -138,8 +141,12 @@ impl PipelineStage for DelimitedExtract {
 
         ctx.register_table(self.output_view.as_str(), table_provider)?;
 
-        ctx.table(self.output_view.as_str())
-            .map(Some)
-            .map_err(BoxError::from)
+        let df = ctx.table(self.output_view.as_str())?;
+
+        if self.profile {
+            self.statistics = statistics::profile(self.statistics.take(), df.clone()).await?;
+        }
+
+        Ok(Some(df))
     }
 }
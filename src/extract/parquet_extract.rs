@@ -36,6 +36,9 @@ pub struct ParquetExtract {
     #[serde(default = "default_false")]
     persist: bool,
 
+    #[serde(default = "default_false")]
+    profile: bool,
+
     #[serde(rename = "numPartitions", skip_serializing_if = "Option::is_none")]
     num_partitions: Option<usize>,
 
@@ -132,8 +135,12 @@ impl PipelineStage for ParquetExtract {
 
         ctx.register_table(self.output_view.as_str(), table_provider)?;
 
-        ctx.table(self.output_view.as_str())
-            .map(Some)
-            .map_err(BoxError::from)
+        let df = ctx.table(self.output_view.as_str())?;
+
+        if self.profile {
+            self.statistics = statistics::profile(self.statistics.take(), df.clone()).await?;
+        }
+
+        Ok(Some(df))
     }
 }
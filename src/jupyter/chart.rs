@@ -0,0 +1,182 @@
+//! Vega-Lite charts of a cell's result
+
+use datafusion::arrow::datatypes::{DataType, Schema};
+use serde_json::{json, Map, Value};
+
+use crate::util::*;
+
+pub const VEGA_LITE_MIME_TYPE: &str = "application/vnd.vegalite.v4+json";
+
+#[derive(Debug, PartialEq)]
+pub enum Mark {
+    Bar,
+    Line,
+    Scatter,
+    Histogram,
+}
+
+impl Mark {
+    pub fn parse(mark: &str) -> Result<Mark> {
+        match mark {
+            "bar" => Ok(Mark::Bar),
+            "line" => Ok(Mark::Line),
+            "scatter" => Ok(Mark::Scatter),
+            "histogram" => Ok(Mark::Histogram),
+            _ => Err(BoxError::new(format!(
+                "Unknown chart '{}'. Expected one of ['bar', 'line', 'scatter', 'histogram'].",
+                mark
+            ))),
+        }
+    }
+}
+
+/// A chart mapping columns to the x and y axes and optionally to color. A histogram bins `x` and
+/// counts the rows of each bin so has no `y`.
+#[derive(Debug, PartialEq)]
+pub struct Chart {
+    pub mark: Mark,
+    pub x: String,
+    pub y: Option<String>,
+    pub color: Option<String>,
+    pub title: Option<String>,
+}
+
+impl Chart {
+    /// The Vega-Lite specification of the chart of the rows, json objects of column name to value
+    pub fn spec(&self, schema: &Schema, rows: Vec<Value>) -> Result<Value> {
+        let mut encoding = Map::new();
+        match self.mark {
+            Mark::Histogram => {
+                encoding.insert(
+                    "x".to_string(),
+                    json!({"field": self.x, "bin": true, "type": "quantitative"}),
+                );
+                encoding.insert(
+                    "y".to_string(),
+                    json!({"aggregate": "count", "type": "quantitative"}),
+                );
+            }
+            _ => {
+                encoding.insert("x".to_string(), channel(schema, &self.x)?);
+                if let Some(y) = &self.y {
+                    encoding.insert("y".to_string(), channel(schema, y)?);
+                }
+            }
+        }
+        if let Some(color) = &self.color {
+            encoding.insert("color".to_string(), channel(schema, color)?);
+        }
+
+        let mark = match self.mark {
+            Mark::Bar | Mark::Histogram => "bar",
+            Mark::Line => "line",
+            Mark::Scatter => "point",
+        };
+        let mut spec = json!({
+            "$schema": "https://vega.github.io/schema/vega-lite/v4.json",
+            "data": {"values": rows},
+            "mark": {"type": mark, "tooltip": true},
+            "encoding": encoding,
+        });
+        if let Some(title) = &self.title {
+            spec["title"] = json!(title);
+        }
+        Ok(spec)
+    }
+}
+
+/// Encode a column choosing the Vega-Lite type from its data type
+fn channel(schema: &Schema, column: &str) -> Result<Value> {
+    let field = schema.field_with_name(column).map_err(|_| {
+        BoxError::new(format!(
+            "Unable to chart column '{}'. Expected one of [{}].",
+            column,
+            schema
+                .fields()
+                .iter()
+                .map(|field| format!("'{}'", field.name()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))
+    })?;
+    let encoding_type = match field.data_type() {
+        DataType::Int8
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::Int64
+        | DataType::UInt8
+        | DataType::UInt16
+        | DataType::UInt32
+        | DataType::UInt64
+        | DataType::Float16
+        | DataType::Float32
+        | DataType::Float64
+        | DataType::Decimal(_, _) => "quantitative",
+        DataType::Date32 | DataType::Date64 | DataType::Timestamp(_, _) => "temporal",
+        _ => "nominal",
+    };
+    Ok(json!({"field": column, "type": encoding_type}))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::datatypes::Field;
+
+    #[test]
+    fn test_spec() -> Result<()> {
+        let schema = Schema::new(vec![
+            Field::new("day", DataType::Date32, false),
+            Field::new("vendor", DataType::Utf8, true),
+            Field::new("fare", DataType::Float64, true),
+        ]);
+        let rows = vec![json!({"day": "2021-01-01", "vendor": "a", "fare": 1.5})];
+
+        let chart = Chart {
+            mark: Mark::Line,
+            x: "day".to_string(),
+            y: Some("fare".to_string()),
+            color: Some("vendor".to_string()),
+            title: Some("Fares".to_string()),
+        };
+        assert_eq!(
+            chart.spec(&schema, rows.clone())?,
+            json!({
+                "$schema": "https://vega.github.io/schema/vega-lite/v4.json",
+                "data": {"values": rows},
+                "mark": {"type": "line", "tooltip": true},
+                "encoding": {
+                    "x": {"field": "day", "type": "temporal"},
+                    "y": {"field": "fare", "type": "quantitative"},
+                    "color": {"field": "vendor", "type": "nominal"}
+                },
+                "title": "Fares"
+            })
+        );
+
+        let histogram = Chart {
+            mark: Mark::Histogram,
+            x: "fare".to_string(),
+            y: None,
+            color: None,
+            title: None,
+        };
+        assert_eq!(
+            histogram.spec(&schema, vec![])?["encoding"],
+            json!({
+                "x": {"field": "fare", "bin": true, "type": "quantitative"},
+                "y": {"aggregate": "count", "type": "quantitative"}
+            })
+        );
+
+        let unknown = Chart {
+            mark: Mark::Bar,
+            x: "distance".to_string(),
+            y: Some("fare".to_string()),
+            color: None,
+            title: None,
+        };
+        assert!(unknown.spec(&schema, vec![]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,458 @@
+//! Tab completion of cells, which are stage configurations. Completion is only offered within a
+//! json string as that is where the cursor is when typing a key, a stage `type`, a view or `sql`.
+
+use std::collections::{BTreeMap, HashMap};
+
+use datafusion::prelude::*;
+use regex::Regex;
+
+use crate::plugin;
+
+// the catalog and schema views are registered in unless the context is configured otherwise
+const DEFAULT_CATALOG: &str = "datafusion";
+const DEFAULT_SCHEMA: &str = "public";
+
+lazy_static! {
+    static ref STAGE_TYPE: Regex = Regex::new(r#""type"\s*:\s*"([^"]*)""#).unwrap();
+}
+
+// keywords which may follow a view rather than an alias
+const ALIAS_TERMINATORS: [&str; 19] = [
+    "CROSS",
+    "EXCEPT",
+    "FULL",
+    "GROUP",
+    "HAVING",
+    "INNER",
+    "INTERSECT",
+    "JOIN",
+    "LEFT",
+    "LIMIT",
+    "NATURAL",
+    "ON",
+    "ORDER",
+    "OUTER",
+    "RIGHT",
+    "UNION",
+    "USING",
+    "WHERE",
+    "WINDOW",
+];
+
+/// The matches replacing the text between `cursor_start` and `cursor_end`, which are offsets in
+/// unicode code points as required by the Jupyter protocol
+#[derive(Debug, PartialEq)]
+pub struct Completions {
+    pub matches: Vec<String>,
+    pub cursor_start: usize,
+    pub cursor_end: usize,
+}
+
+/// The names available for completion
+#[derive(Default)]
+pub struct CompletionSource {
+    pub stage_types: Vec<String>,
+    pub stage_fields: HashMap<String, Vec<String>>,
+
+    /// The registered views and their column names
+    pub views: BTreeMap<String, Vec<String>>,
+}
+
+impl CompletionSource {
+    /// Collect the registered stages and the views registered in `ctx`
+    pub fn new(ctx: &ExecutionContext) -> Self {
+        let registry = plugin::registry();
+        let stage_types = registry
+            .stage_types()
+            .into_iter()
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        let stage_fields = stage_types
+            .iter()
+            .map(|stage_type| {
+                let fields = registry
+                    .stage_info(stage_type)
+                    .map(|info| {
+                        info.fields
+                            .iter()
+                            .map(|field| field.name.to_string())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                (stage_type.to_owned(), fields)
+            })
+            .collect();
+
+        let mut views = BTreeMap::new();
+        if let Some(schema) = ctx
+            .catalog(DEFAULT_CATALOG)
+            .and_then(|catalog| catalog.schema(DEFAULT_SCHEMA))
+        {
+            for view in schema.table_names() {
+                let columns = schema
+                    .table(&view)
+                    .map(|table| {
+                        table
+                            .schema()
+                            .fields()
+                            .iter()
+                            .map(|field| field.name().to_owned())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                views.insert(view, columns);
+            }
+        }
+
+        Self {
+            stage_types,
+            stage_fields,
+            views,
+        }
+    }
+}
+
+/// Where within the json the string containing the cursor is
+#[derive(Debug, PartialEq)]
+enum Position {
+    Key,
+    /// The value of a key, or an element of an array which is the value of a key
+    Value(Option<String>),
+}
+
+enum Container {
+    Object {
+        expect_key: bool,
+        key: Option<String>,
+    },
+    Array,
+}
+
+/// Complete the word before `cursor_pos`
+pub fn complete(code: &str, cursor_pos: usize, source: &CompletionSource) -> Completions {
+    let chars = code.chars().collect::<Vec<_>>();
+    let cursor = cursor_pos.min(chars.len());
+    let none = Completions {
+        matches: vec![],
+        cursor_start: cursor,
+        cursor_end: cursor,
+    };
+
+    let (position, start) = match string_at_cursor(&chars, cursor) {
+        Some(string) => string,
+        None => return none,
+    };
+    let prefix = chars[start..cursor].iter().collect::<String>();
+
+    let (candidates, cursor_start) = match position {
+        Position::Key => {
+            // prefer the type of the stage being typed but it may be given after the cursor
+            let before = chars[..cursor].iter().collect::<String>();
+            let after = chars[cursor..].iter().collect::<String>();
+            let stage_type = STAGE_TYPE
+                .captures_iter(&before)
+                .last()
+                .or_else(|| STAGE_TYPE.captures(&after))
+                .map(|captures| captures[1].to_string());
+            let fields = match stage_type.and_then(|t| source.stage_fields.get(&t)) {
+                Some(fields) => fields.clone(),
+                None => vec!["type".to_string()],
+            };
+            (fields, start)
+        }
+        Position::Value(Some(key)) if key == "type" => (source.stage_types.clone(), start),
+        Position::Value(Some(key)) if key == "sql" => {
+            let end = string_end(&chars, start);
+            return complete_sql(&chars[start..end], cursor - start, source, start);
+        }
+        Position::Value(Some(key)) if key.ends_with("View") || key.ends_with("Views") => {
+            (source.views.keys().cloned().collect(), start)
+        }
+        Position::Value(_) => return none,
+    };
+
+    Completions {
+        matches: filter(candidates, &prefix),
+        cursor_start,
+        cursor_end: cursor,
+    }
+}
+
+/// Complete views after `FROM` or `JOIN`, qualified columns after a view or alias and otherwise
+/// the columns of the views the query references
+fn complete_sql(
+    sql: &[char],
+    cursor: usize,
+    source: &CompletionSource,
+    offset: usize,
+) -> Completions {
+    let mut word_start = cursor;
+    while word_start > 0 && is_identifier(sql[word_start - 1]) {
+        word_start -= 1;
+    }
+    let word = sql[word_start..cursor].iter().collect::<String>();
+    let before = sql[..word_start].iter().collect::<String>();
+    let keyword = before
+        .trim_end()
+        .rsplit(|c: char| !is_identifier(c))
+        .next()
+        .unwrap_or_default()
+        .to_uppercase();
+    let sql = sql.iter().collect::<String>();
+
+    let candidates = if keyword == "FROM" || keyword == "JOIN" {
+        source.views.keys().cloned().collect()
+    } else if let Some((qualifier, _)) = word.rsplit_once('.') {
+        let view = if source.views.contains_key(qualifier) {
+            Some(qualifier.to_string())
+        } else {
+            table_references(&sql)
+                .into_iter()
+                .find(|(_, alias)| alias.as_deref() == Some(qualifier))
+                .map(|(view, _)| view)
+        };
+        view.and_then(|view| source.views.get(&view))
+            .map(|columns| {
+                columns
+                    .iter()
+                    .map(|column| format!("{}.{}", qualifier, column))
+                    .collect()
+            })
+            .unwrap_or_default()
+    } else {
+        let referenced = table_references(&sql)
+            .into_iter()
+            .filter_map(|(view, _)| source.views.get(&view))
+            .collect::<Vec<_>>();
+        let columns = if referenced.is_empty() {
+            source.views.values().collect()
+        } else {
+            referenced
+        };
+        columns
+            .into_iter()
+            .flatten()
+            .chain(source.views.keys())
+            .cloned()
+            .collect()
+    };
+
+    Completions {
+        matches: filter(candidates, &word),
+        cursor_start: offset + word_start,
+        cursor_end: offset + cursor,
+    }
+}
+
+/// The views following `FROM` or `JOIN` and their aliases. This is a scan of the words rather
+/// than a parse as the sql is usually incomplete while being typed.
+fn table_references(sql: &str) -> Vec<(String, Option<String>)> {
+    let words = sql
+        .split(|c: char| !is_identifier(c))
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>();
+    let mut references = vec![];
+    let mut i = 0;
+    while i < words.len() {
+        let keyword = words[i].to_uppercase();
+        if (keyword == "FROM" || keyword == "JOIN") && i + 1 < words.len() {
+            let view = words[i + 1].to_string();
+            let alias = match words.get(i + 2) {
+                Some(word) if word.eq_ignore_ascii_case("AS") => words.get(i + 3),
+                Some(word) if !ALIAS_TERMINATORS.contains(&word.to_uppercase().as_str()) => {
+                    Some(word)
+                }
+                _ => None,
+            };
+            references.push((view, alias.map(|alias| alias.to_string())));
+        }
+        i += 1;
+    }
+    references
+}
+
+/// Find the string containing the cursor, returning its position and the index after its
+/// opening quote
+fn string_at_cursor(chars: &[char], cursor: usize) -> Option<(Position, usize)> {
+    let mut stack = vec![];
+    let mut i = 0;
+    while i < cursor {
+        match chars[i] {
+            '"' => {
+                let start = i + 1;
+                let end = string_end(chars, start);
+                if cursor <= end {
+                    let position = match stack.last() {
+                        Some(Container::Object {
+                            expect_key: true, ..
+                        }) => Position::Key,
+                        Some(Container::Object { key, .. }) => Position::Value(key.clone()),
+                        Some(Container::Array) => match stack.iter().rev().nth(1) {
+                            Some(Container::Object { key, .. }) => Position::Value(key.clone()),
+                            _ => Position::Value(None),
+                        },
+                        None => Position::Value(None),
+                    };
+                    return Some((position, start));
+                }
+                if let Some(Container::Object {
+                    expect_key: true,
+                    key,
+                }) = stack.last_mut()
+                {
+                    *key = Some(chars[start..end].iter().collect());
+                }
+                i = end;
+            }
+            '{' => stack.push(Container::Object {
+                expect_key: true,
+                key: None,
+            }),
+            '[' => stack.push(Container::Array),
+            '}' | ']' => {
+                stack.pop();
+            }
+            ':' => {
+                if let Some(Container::Object { expect_key, .. }) = stack.last_mut() {
+                    *expect_key = false;
+                }
+            }
+            ',' => {
+                if let Some(Container::Object { expect_key, key }) = stack.last_mut() {
+                    *expect_key = true;
+                    *key = None;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// The index of the closing quote of a string starting at `start` or the end of the code
+fn string_end(chars: &[char], start: usize) -> usize {
+    let mut i = start;
+    while i < chars.len() && chars[i] != '"' {
+        if chars[i] == '\\' {
+            i += 1;
+        }
+        i += 1;
+    }
+    i.min(chars.len())
+}
+
+pub(crate) fn is_identifier(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+fn filter(candidates: Vec<String>, prefix: &str) -> Vec<String> {
+    let prefix = prefix.to_lowercase();
+    let mut matches = candidates
+        .into_iter()
+        .filter(|candidate| candidate.to_lowercase().starts_with(&prefix))
+        .collect::<Vec<_>>();
+    matches.sort_by_key(|candidate| candidate.to_lowercase());
+    matches.dedup();
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source() -> CompletionSource {
+        let mut source = CompletionSource {
+            stage_types: vec!["SQLTransform".to_string(), "SQLValidate".to_string()],
+            ..Default::default()
+        };
+        source.stage_fields.insert(
+            "SQLTransform".to_string(),
+            vec!["type", "sql", "sqlParams", "outputView"]
+                .into_iter()
+                .map(str::to_owned)
+                .collect(),
+        );
+        source.views.insert(
+            "trips".to_string(),
+            vec!["trip_id".to_string(), "fare".to_string()],
+        );
+        source.views.insert(
+            "zones".to_string(),
+            vec!["zone_id".to_string(), "borough".to_string()],
+        );
+        source
+    }
+
+    fn complete_at(code: &str) -> Completions {
+        let cursor = code.chars().position(|c| c == '|').unwrap();
+        complete(&code.replacen('|', "", 1), cursor, &source())
+    }
+
+    fn matches(code: &str) -> Vec<String> {
+        complete_at(code).matches
+    }
+
+    #[test]
+    fn test_complete_type() {
+        assert_eq!(
+            complete_at(r#"{"type": "SQLT|"#),
+            Completions {
+                matches: vec!["SQLTransform".to_string()],
+                cursor_start: 10,
+                cursor_end: 14,
+            }
+        );
+        assert_eq!(matches(r#"{"type": "|"}"#).len(), 2);
+    }
+
+    #[test]
+    fn test_complete_key() {
+        assert_eq!(
+            matches(r#"{"type": "SQLTransform", "sq|"#),
+            vec!["sql", "sqlParams"]
+        );
+        assert_eq!(
+            matches(r#"{"out|", "type": "SQLTransform"}"#),
+            vec!["outputView"]
+        );
+        assert_eq!(matches(r#"{"|"#), vec!["type"]);
+    }
+
+    #[test]
+    fn test_complete_view() {
+        assert_eq!(matches(r#"{"inputView": "t|"#), vec!["trips"]);
+        assert_eq!(
+            matches(r#"{"inputViews": ["trips", "|"]}"#),
+            vec!["trips", "zones"]
+        );
+        assert!(matches(r#"{"description": "t|"#).is_empty());
+    }
+
+    #[test]
+    fn test_complete_sql() {
+        assert_eq!(
+            matches(r#"{"sql": "SELECT * FROM |"#),
+            vec!["trips", "zones"]
+        );
+        assert_eq!(matches(r#"{"sql": "SELECT f| FROM trips"}"#), vec!["fare"]);
+        assert_eq!(
+            matches(r#"{"sql": "SELECT z.b| FROM trips JOIN zones AS z"}"#),
+            vec!["z.borough"]
+        );
+        assert_eq!(
+            matches(r#"{"sql": "SELECT trips.t| FROM trips"}"#),
+            vec!["trips.trip_id"]
+        );
+        assert_eq!(matches(r#"{"sql": "SELECT z|"#), vec!["zone_id", "zones"]);
+
+        let completions = complete_at(r#"{"sql": "SELECT fa| FROM trips"}"#);
+        assert_eq!((completions.cursor_start, completions.cursor_end), (16, 18));
+    }
+
+    #[test]
+    fn test_no_completion_outside_string() {
+        assert!(matches(r#"{"type": "SQLTransform", |"#).is_empty());
+        assert!(matches(r#"{"type": "SQLTransform"|"#).is_empty());
+    }
+}
@@ -0,0 +1,211 @@
+//! Rendering of cell outputs as Jupyter mime bundles
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use datafusion::arrow::datatypes::{DataType, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::arrow::util::pretty::pretty_format_batches;
+use datafusion::prelude::*;
+use json::JsonValue;
+use serde_json::{json, Value};
+
+use crate::jupyter::chart::VEGA_LITE_MIME_TYPE;
+use crate::jupyter::session::Output;
+use crate::util::rows::{row_value, value_as_usize};
+use crate::util::*;
+
+/// The maximum number of rows embedded in a chart
+const MAX_CHART_ROWS: usize = 5000;
+
+/// The mime bundle of a cell's output or `None` if there is nothing to display. Tables show at
+/// most `max_rows` rows and the total number of rows.
+pub async fn mime_bundle(
+    output: Output,
+    max_rows: usize,
+) -> Result<Option<HashMap<String, JsonValue>>> {
+    let mut data: HashMap<String, JsonValue> = HashMap::new();
+    match output {
+        Output::DataFrame(df) => {
+            let (schema, head, summary) = collect_head(df, max_rows).await?;
+
+            let html = create_html_table(&schema, head.clone(), None, &["tex2jax_ignore"])?;
+            data.insert(
+                "text/html".into(),
+                json::from(format!("{}<p>{}</p>", html, summary)),
+            );
+            data.insert(
+                "text/plain".into(),
+                json::from(format!("{}\n{}", pretty_format_batches(&head)?, summary)),
+            );
+
+            let rows = json_rows(&head)?;
+            data.insert(
+                "application/vnd.dataresource+json".into(),
+                to_json(&json!({
+                    "schema": table_schema(&schema),
+                    "data": rows,
+                }))?,
+            );
+            data.insert("application/json".into(), to_json(&Value::Array(rows))?);
+        }
+        Output::Chart(chart, df) => {
+            let (schema, head, summary) = collect_head(df, MAX_CHART_ROWS).await?;
+            let spec = chart.spec(&schema, json_rows(&head)?)?;
+            data.insert(VEGA_LITE_MIME_TYPE.into(), to_json(&spec)?);
+            data.insert(
+                "text/plain".into(),
+                json::from(format!("Chart of {}", summary.to_lowercase())),
+            );
+        }
+        Output::Text(text) => {
+            data.insert("text/plain".into(), json::from(text));
+        }
+        Output::Json(value) => {
+            data.insert("application/json".into(), to_json(&value)?);
+            data.insert(
+                "text/plain".into(),
+                json::from(serde_json::to_string_pretty(&value)?),
+            );
+        }
+        Output::Empty => return Ok(None),
+    }
+    Ok(Some(data))
+}
+
+/// Collect the first `max_rows` rows of a DataFrame with its schema, which is known even if
+/// there are no rows, and a summary of the number of rows shown. The rows are only counted when
+/// there are more than are shown.
+async fn collect_head(
+    df: Arc<dyn DataFrame>,
+    max_rows: usize,
+) -> Result<(Schema, Vec<RecordBatch>, String)> {
+    let schema = Schema::new(
+        df.schema()
+            .fields()
+            .iter()
+            .map(|field| field.field().clone())
+            .collect(),
+    );
+    let head = df.limit(max_rows)?.collect().await?;
+    let shown_rows = head.iter().map(|batch| batch.num_rows()).sum::<usize>();
+    let total_rows = if shown_rows < max_rows {
+        shown_rows
+    } else {
+        let batches = df.aggregate(vec![], vec![count(lit(1))])?.collect().await?;
+        match batches.iter().find(|batch| batch.num_rows() == 1) {
+            Some(batch) => value_as_usize(batch.column(0))?.unwrap_or_default(),
+            None => 0,
+        }
+    };
+    Ok((schema, head, row_summary(shown_rows, total_rows)))
+}
+
+fn row_summary(shown_rows: usize, total_rows: usize) -> String {
+    let plural = if total_rows == 1 { "" } else { "s" };
+    if shown_rows < total_rows {
+        format!("Showing {} of {} row{}", shown_rows, total_rows, plural)
+    } else {
+        format!("{} row{}", total_rows, plural)
+    }
+}
+
+fn json_rows(batches: &[RecordBatch]) -> Result<Vec<Value>> {
+    let mut rows = vec![];
+    for batch in batches {
+        for row in 0..batch.num_rows() {
+            rows.push(row_value(batch, row)?);
+        }
+    }
+    Ok(rows)
+}
+
+/// Describe a schema as a Frictionless Table Schema as used by the dataresource mime type
+fn table_schema(schema: &Schema) -> Value {
+    let fields = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            let field_type = match field.data_type() {
+                DataType::Int8
+                | DataType::Int16
+                | DataType::Int32
+                | DataType::Int64
+                | DataType::UInt8
+                | DataType::UInt16
+                | DataType::UInt32
+                | DataType::UInt64 => "integer",
+                DataType::Float16
+                | DataType::Float32
+                | DataType::Float64
+                | DataType::Decimal(_, _) => "number",
+                DataType::Boolean => "boolean",
+                DataType::Utf8 | DataType::LargeUtf8 => "string",
+                DataType::Date32 | DataType::Date64 => "date",
+                DataType::Timestamp(_, _) => "datetime",
+                DataType::Time32(_) | DataType::Time64(_) => "time",
+                DataType::Duration(_) | DataType::Interval(_) => "duration",
+                DataType::List(_) | DataType::LargeList(_) | DataType::FixedSizeList(_, _) => {
+                    "array"
+                }
+                DataType::Struct(_) => "object",
+                _ => "any",
+            };
+            json!({"name": field.name(), "type": field_type})
+        })
+        .collect::<Vec<_>>();
+    json!({ "fields": fields })
+}
+
+fn to_json(value: &Value) -> Result<JsonValue> {
+    Ok(json::parse(&value.to_string())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use datafusion::arrow::array::{Float64Array, Int32Array};
+
+    #[tokio::test]
+    async fn test_collect_head() -> Result<()> {
+        let mut ctx = ExecutionContext::new();
+        register_view(
+            &mut ctx,
+            "trips",
+            vec![
+                ("id", Arc::new(Int32Array::from(vec![1, 2, 3, 4]))),
+                (
+                    "fare",
+                    Arc::new(Float64Array::from(vec![
+                        Some(1.5),
+                        None,
+                        Some(f64::NAN),
+                        Some(4.0),
+                    ])),
+                ),
+            ],
+        )?;
+
+        let (schema, head, summary) = collect_head(ctx.table("trips")?, 3).await?;
+        assert_eq!(
+            Value::Array(json_rows(&head)?),
+            json!([
+                {"id": 1, "fare": 1.5},
+                {"id": 2, "fare": null},
+                {"id": 3, "fare": "NaN"}
+            ])
+        );
+        assert_eq!(
+            table_schema(&schema),
+            json!({"fields": [{"name": "id", "type": "integer"}, {"name": "fare", "type": "number"}]})
+        );
+        assert_eq!(summary, "Showing 3 of 4 rows");
+
+        let df = ctx.table("trips")?.filter(col("id").eq(lit(1)))?;
+        let (_, head, summary) = collect_head(df, 3).await?;
+        assert_eq!(json_rows(&head)?, vec![json!({"id": 1, "fare": 1.5})]);
+        assert_eq!(summary, "1 row");
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+//! Export of a notebook's stage cells as a job
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::api::{parse_config, BoxContext};
+use crate::jupyter::magic::{row_limit, Magic};
+use crate::util::*;
+
+#[derive(Deserialize)]
+struct Notebook {
+    cells: Vec<Cell>,
+}
+
+#[derive(Deserialize)]
+struct Cell {
+    cell_type: String,
+    source: Source,
+}
+
+/// Cell source is either a string or a list of lines
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Source {
+    Text(String),
+    Lines(Vec<String>),
+}
+
+impl Source {
+    fn text(self) -> String {
+        match self {
+            Source::Text(text) => text,
+            Source::Lines(lines) => lines.concat(),
+        }
+    }
+}
+
+/// Convert the code cells of a notebook to a job of the form `{"stages": [...]}`. A `%sql` magic
+/// which names its result becomes a `SQLTransform`; other magics and markdown cells only display
+/// output so are skipped. Parameter placeholders are kept to be set when the job is executed.
+pub fn export_notebook(notebook: &str) -> Result<String> {
+    let notebook: Notebook = serde_json::from_str(notebook)?;
+    let mut stages = vec![];
+    for (index, cell) in notebook.cells.into_iter().enumerate() {
+        if cell.cell_type != "code" {
+            continue;
+        }
+        let source = cell.source.text();
+        let (_, source) = row_limit(&source)?;
+        if source.trim().is_empty() {
+            continue;
+        }
+        match Magic::parse(source)? {
+            Some(Magic::Sql {
+                name: Some(name),
+                sql,
+            }) => stages.push(json!({
+                "type": "SQLTransform",
+                "sql": sql,
+                "outputView": name,
+            })),
+            Some(_) => {}
+            None => {
+                let source = variables::replace_hocon_parameters(&format!("[{}]", source));
+                let values = serde_json::from_str::<Vec<Value>>(&source).map_err(|err| {
+                    BoxError::new(format!(
+                        "Unable to parse cell {} as a stage: {}",
+                        index + 1,
+                        err
+                    ))
+                })?;
+                stages.extend(values);
+            }
+        }
+    }
+
+    let job = serde_json::to_string_pretty(&json!({ "stages": stages }))?;
+    // validate the stages without parameters as those are given when the job is executed
+    parse_config(BoxContext::new(None, None), &job, true, true)?;
+    Ok(job)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_notebook() -> Result<()> {
+        let notebook = json!({
+            "cells": [
+                {"cell_type": "markdown", "metadata": {}, "source": ["# Trips"]},
+                {"cell_type": "code", "metadata": {}, "outputs": [], "source": "%env INPUT=/tmp"},
+                {
+                    "cell_type": "code",
+                    "metadata": {},
+                    "outputs": [],
+                    "source": [
+                        "{\n",
+                        "    \"type\": \"SQLTransform\",\n",
+                        "    \"sql\": \"SELECT * FROM ${INPUT}\",\n",
+                        "    \"sqlParams\": {\"year\": \"${YEAR}\"},\n",
+                        "    \"outputView\": \"trips\"\n",
+                        "}"
+                    ]
+                },
+                {
+                    "cell_type": "code",
+                    "metadata": {},
+                    "outputs": [],
+                    "source": "%limit 5\n%sql name=fares\nSELECT fare FROM trips"
+                },
+                {"cell_type": "code", "metadata": {}, "outputs": [], "source": "%sql\nSELECT * FROM fares"},
+                {"cell_type": "code", "metadata": {}, "outputs": [], "source": ""}
+            ]
+        })
+        .to_string();
+        assert_eq!(
+            serde_json::from_str::<Value>(&export_notebook(&notebook)?)?,
+            json!({
+                "stages": [
+                    {
+                        "type": "SQLTransform",
+                        "sql": "SELECT * FROM ${INPUT}",
+                        "sqlParams": {"year": "${YEAR}"},
+                        "outputView": "trips"
+                    },
+                    {
+                        "type": "SQLTransform",
+                        "sql": "SELECT fare FROM trips",
+                        "outputView": "fares"
+                    }
+                ]
+            })
+        );
+
+        let invalid =
+            json!({"cells": [{"cell_type": "code", "source": "{\"type\": \"Unknown\"}"}]});
+        assert!(export_notebook(&invalid.to_string()).is_err());
+        Ok(())
+    }
+}
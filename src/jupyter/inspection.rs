@@ -0,0 +1,184 @@
+//! Inspection of the name under the cursor: a stage type shows its documentation and a view or
+//! column shows its schema, row count and sample values.
+
+use std::sync::Arc;
+
+use datafusion::arrow::util::pretty::pretty_format_batches;
+use datafusion::logical_plan::DFField;
+use datafusion::prelude::*;
+
+use crate::jupyter::completion::{is_identifier, CompletionSource};
+use crate::plugin;
+use crate::util::rows::value_as_usize;
+use crate::util::*;
+
+const SAMPLE_ROWS: usize = 5;
+
+/// Views without an exact row count in their statistics are counted up to this many rows
+const MAX_COUNTED_ROWS: usize = 100_000;
+
+/// Describe the name under the cursor as plain text or `None` if it is not known
+pub async fn inspect(
+    ctx: &ExecutionContext,
+    code: &str,
+    cursor_pos: usize,
+) -> Result<Option<String>> {
+    let word = match word_at(code, cursor_pos) {
+        Some(word) => word,
+        None => return Ok(None),
+    };
+
+    if let Some(info) = plugin::registry().stage_info(&word) {
+        return Ok(Some(format!("{}\n{}", word, info)));
+    }
+
+    let views = CompletionSource::new(ctx).views;
+    if views.contains_key(&word) {
+        return describe_view(ctx, &word).await.map(Some);
+    }
+
+    // a qualified column or every view with a column of that name
+    let columns = match word.rsplit_once('.') {
+        Some((view, column)) => vec![(view.to_string(), column.to_string())],
+        None => views
+            .keys()
+            .map(|view| (view.to_owned(), word.to_owned()))
+            .collect(),
+    };
+    let mut descriptions = vec![];
+    for (view, column) in columns {
+        if views
+            .get(&view)
+            .is_some_and(|columns| columns.contains(&column))
+        {
+            descriptions.push(describe_column(ctx, &view, &column).await?);
+        }
+    }
+    if descriptions.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(descriptions.join("\n\n")))
+    }
+}
+
+async fn describe_view(ctx: &ExecutionContext, view: &str) -> Result<String> {
+    let df = ctx.table(view)?;
+    let mut lines = vec![
+        view.to_string(),
+        format!("Rows: {}", row_count(ctx, df.clone()).await?),
+        String::new(),
+        "Schema:".to_string(),
+    ];
+    lines.extend(
+        df.schema()
+            .fields()
+            .iter()
+            .map(|field| format!("  {}: {}", field.name(), data_type(field))),
+    );
+    lines.push(String::new());
+    lines.push(pretty_format_batches(
+        &df.limit(SAMPLE_ROWS)?.collect().await?,
+    )?);
+    Ok(lines.join("\n"))
+}
+
+async fn describe_column(ctx: &ExecutionContext, view: &str, column: &str) -> Result<String> {
+    let df = ctx.table(view)?.select_columns(&[column])?;
+    let lines = vec![
+        format!("{}.{}: {}", view, column, data_type(df.schema().field(0))),
+        format!("Rows: {}", row_count(ctx, df.clone()).await?),
+        String::new(),
+        pretty_format_batches(&df.limit(SAMPLE_ROWS)?.collect().await?)?,
+    ];
+    Ok(lines.join("\n"))
+}
+
+fn data_type(field: &DFField) -> String {
+    if field.is_nullable() {
+        format!("{} (nullable)", field.data_type())
+    } else {
+        field.data_type().to_string()
+    }
+}
+
+/// The row count from the statistics of the plan if exact. Otherwise, as inspection runs on the
+/// shell thread, at most `MAX_COUNTED_ROWS` rows are counted.
+async fn row_count(ctx: &ExecutionContext, df: Arc<dyn DataFrame>) -> Result<String> {
+    if let Some(row_count) = statistics::exact_row_count(ctx, df.clone()).await? {
+        return Ok(row_count.to_string());
+    }
+
+    let batches = df
+        .limit(MAX_COUNTED_ROWS + 1)?
+        .aggregate(vec![], vec![count(lit(1))])?
+        .collect()
+        .await?;
+    let rows = match batches.iter().find(|batch| batch.num_rows() == 1) {
+        Some(batch) => value_as_usize(batch.column(0))?.unwrap_or_default(),
+        None => 0,
+    };
+    if rows > MAX_COUNTED_ROWS {
+        Ok(format!("more than {}", MAX_COUNTED_ROWS))
+    } else {
+        Ok(rows.to_string())
+    }
+}
+
+/// The identifier, which may be qualified, containing or immediately before the cursor
+fn word_at(code: &str, cursor_pos: usize) -> Option<String> {
+    let chars = code.chars().collect::<Vec<_>>();
+    let cursor = cursor_pos.min(chars.len());
+    let mut start = cursor;
+    while start > 0 && is_identifier(chars[start - 1]) {
+        start -= 1;
+    }
+    let mut end = cursor;
+    while end < chars.len() && is_identifier(chars[end]) {
+        end += 1;
+    }
+    let word = chars[start..end]
+        .iter()
+        .collect::<String>()
+        .trim_matches('.')
+        .to_string();
+    if word.is_empty() {
+        None
+    } else {
+        Some(word)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_word_at() {
+        let code = r#"{"type": "SQLTransform", "sql": "SELECT trips.fare FROM trips."}"#;
+        assert_eq!(word_at(code, 12), Some("SQLTransform".to_string()));
+        assert_eq!(word_at(code, 22), Some("SQLTransform".to_string()));
+        assert_eq!(word_at(code, 48), Some("trips.fare".to_string()));
+        assert_eq!(word_at(code, 62), Some("trips".to_string()));
+        assert_eq!(word_at(code, 23), None);
+        assert_eq!(word_at("", 10), None);
+    }
+
+    #[tokio::test]
+    async fn test_row_count() -> Result<()> {
+        let mut ctx = ExecutionContext::new();
+        register_view(
+            &mut ctx,
+            "trips",
+            vec![(
+                "id",
+                Arc::new(datafusion::arrow::array::Int32Array::from(vec![1, 2, 3])),
+            )],
+        )?;
+        assert_eq!(row_count(&ctx, ctx.table("trips")?).await?, "3");
+
+        // a filter has no exact statistics so is counted
+        let df = ctx.table("trips")?.filter(col("id").gt(lit(1)))?;
+        assert_eq!(row_count(&ctx, df).await?, "2");
+        Ok(())
+    }
+}
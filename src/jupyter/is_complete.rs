@@ -0,0 +1,152 @@
+//! Whether a cell is ready to execute, which console frontends ask before running a line
+
+use crate::jupyter::magic::{row_limit, Magic};
+
+const INDENT: &str = "    ";
+
+#[derive(Debug, PartialEq)]
+pub enum Completeness {
+    Complete,
+    /// More lines are expected, indented by `indent`
+    Incomplete {
+        indent: String,
+    },
+    Invalid,
+}
+
+/// Check that the brackets and strings of a JSON or HOCON cell are closed and that magics which
+/// are followed by sql have some, ended by a blank line
+pub fn is_complete(code: &str) -> Completeness {
+    let code = match row_limit(code) {
+        Ok((Some(_), code)) if code.trim().is_empty() => return incomplete(0),
+        Ok((_, code)) => code,
+        Err(_) => return Completeness::Invalid,
+    };
+    if code.trim_start().starts_with('%') {
+        // a magic such as %sql is waiting for its sql if adding some makes it valid
+        return match Magic::parse(code) {
+            // sql may span lines so ends with a blank line
+            Ok(Some(Magic::Sql { .. } | Magic::Chart { .. }))
+                if !code.trim_end_matches(&[' ', '\t'][..]).ends_with('\n') =>
+            {
+                incomplete(0)
+            }
+            Ok(_) => Completeness::Complete,
+            Err(_) if Magic::parse(&format!("{}\nSELECT 1", code)).is_ok() => incomplete(0),
+            Err(_) => Completeness::Invalid,
+        };
+    }
+
+    let chars = code.chars().collect::<Vec<_>>();
+    let mut brackets = vec![];
+    let mut index = 0;
+    while index < chars.len() {
+        match chars[index] {
+            '"' if chars[index..].starts_with(&['"', '"', '"']) => {
+                // a hocon multi-line string
+                match (index + 3..chars.len()).find(|i| chars[*i..].starts_with(&['"', '"', '"'])) {
+                    Some(end) => index = end + 2,
+                    None => return incomplete(brackets.len()),
+                }
+            }
+            '"' => {
+                index += 1;
+                while index < chars.len() && chars[index] != '"' {
+                    match chars[index] {
+                        '\\' => index += 1,
+                        '\n' => return Completeness::Invalid,
+                        _ => {}
+                    }
+                    index += 1;
+                }
+                if index >= chars.len() {
+                    return Completeness::Invalid;
+                }
+            }
+            // hocon comments
+            '#' => {
+                while index < chars.len() && chars[index] != '\n' {
+                    index += 1;
+                }
+            }
+            '/' if chars.get(index + 1) == Some(&'/') => {
+                while index < chars.len() && chars[index] != '\n' {
+                    index += 1;
+                }
+            }
+            '{' | '[' => brackets.push(chars[index]),
+            '}' | ']' => {
+                let expected = if chars[index] == '}' { '{' } else { '[' };
+                if brackets.pop() != Some(expected) {
+                    return Completeness::Invalid;
+                }
+            }
+            _ => {}
+        }
+        index += 1;
+    }
+
+    // another stage or value is expected after a trailing comma or colon
+    if !brackets.is_empty() || code.trim_end().ends_with(&[',', ':'][..]) {
+        incomplete(brackets.len())
+    } else {
+        Completeness::Complete
+    }
+}
+
+fn incomplete(depth: usize) -> Completeness {
+    Completeness::Incomplete {
+        indent: INDENT.repeat(depth),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_complete() {
+        assert_eq!(is_complete(""), Completeness::Complete);
+        assert_eq!(
+            is_complete(r#"{"type": "SQLTransform", "sql": "SELECT '}'", "outputView": "a"}"#),
+            Completeness::Complete
+        );
+        assert_eq!(
+            is_complete("{\n    \"type\": \"SQLTransform\",\n    \"columns\": ["),
+            incomplete(2)
+        );
+        assert_eq!(is_complete("{\"type\": \"SQLTransform\"},"), incomplete(0));
+        assert_eq!(
+            is_complete("{\n    sql: \"\"\"SELECT *\n    FROM trips"),
+            incomplete(1)
+        );
+        assert_eq!(
+            is_complete("{\n    # a comment with a {\n    sql: \"\"\"SELECT 1\"\"\"\n}"),
+            Completeness::Complete
+        );
+        assert_eq!(
+            is_complete("{\"sql\": \"SELECT\n1\"}"),
+            Completeness::Invalid
+        );
+        assert_eq!(is_complete("{\"a\": [}"), Completeness::Invalid);
+
+        assert_eq!(is_complete("%sql name=trips"), incomplete(0));
+        assert_eq!(is_complete("%sql\nSELECT 1"), incomplete(0));
+        assert_eq!(
+            is_complete("%sql\nSELECT *\nFROM trips\n"),
+            Completeness::Complete
+        );
+        assert_eq!(
+            is_complete("%chart bar x=day y=fare\nSELECT 1"),
+            incomplete(0)
+        );
+        assert_eq!(
+            is_complete("%limit 5\n%chart bar x=day y=fare\nSELECT 1\n  "),
+            Completeness::Complete
+        );
+        assert_eq!(is_complete("%chart bar y=fare"), Completeness::Invalid);
+        assert_eq!(is_complete("%limit 5"), incomplete(0));
+        assert_eq!(is_complete("%views"), Completeness::Complete);
+        assert_eq!(is_complete("%unknown"), Completeness::Invalid);
+    }
+}
@@ -141,13 +141,29 @@ impl JupyterMessage {
         self.header["msg_type"].as_str().unwrap_or("")
     }
 
+    pub(crate) fn msg_id(&self) -> &str {
+        self.header["msg_id"].as_str().unwrap_or("")
+    }
+
+    pub(crate) fn parent_msg_id(&self) -> &str {
+        self.parent_header["msg_id"].as_str().unwrap_or("")
+    }
+
     pub(crate) fn code(&self) -> &str {
         self.content["code"].as_str().unwrap_or("")
     }
 
-    // pub(crate) fn cursor_pos(&self) -> usize {
-    //     self.content["cursor_pos"].as_usize().unwrap_or_default()
-    // }
+    pub(crate) fn cursor_pos(&self) -> usize {
+        self.content["cursor_pos"].as_usize().unwrap_or_default()
+    }
+
+    pub(crate) fn allow_stdin(&self) -> bool {
+        self.content["allow_stdin"].as_bool().unwrap_or_default()
+    }
+
+    pub(crate) fn value(&self) -> &str {
+        self.content["value"].as_str().unwrap_or("")
+    }
 
     // pub(crate) fn target_name(&self) -> &str {
     //     self.content["target_name"].as_str().unwrap_or("")
@@ -187,6 +203,14 @@ impl JupyterMessage {
         reply
     }
 
+    // Creates a request to the frontend which sent this message, such as an input_request on
+    // the stdin channel. ZMQ identities are transferred.
+    pub(crate) fn new_frontend_request(&self, msg_type: &str) -> JupyterMessage {
+        let mut request = self.new_message(msg_type);
+        request.zmq_identities = self.zmq_identities.clone();
+        request
+    }
+
     // #[must_use = "Need to send this message for it to have any effect"]
     // pub(crate) fn comm_close_message(&self) -> JupyterMessage {
     //     self.new_message("comm_close").with_content(object! {
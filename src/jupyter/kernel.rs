@@ -13,28 +13,50 @@
 // limitations under the License.
 
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
 
-use crate::api::{execute, parse_config, BoxContext};
+use crate::api::{new_execution_context, Event};
 use crate::util::*;
 
+use crate::jupyter::chart::VEGA_LITE_MIME_TYPE;
+use crate::jupyter::completion::{self, CompletionSource};
 use crate::jupyter::connection::Connection;
 use crate::jupyter::connection_file::ConnectionFile;
+use crate::jupyter::display::mime_bundle;
+use crate::jupyter::inspection;
+use crate::jupyter::is_complete::{is_complete, Completeness};
 use crate::jupyter::jupyter_message::JupyterMessage;
+use crate::jupyter::magic::row_limit;
+use crate::jupyter::progress::Progress;
+use crate::jupyter::session::Session;
+use crate::jupyter::traceback::Traceback;
 
 use datafusion::prelude::*;
+use futures::future::{AbortHandle, Abortable, Aborted};
 
 use json::JsonValue;
 
+/// How often a cell waiting for input checks for an interrupt
+const STDIN_POLL_MILLIS: i64 = 100;
+
 // Note, to avoid potential deadlocks, each thread should lock at most one mutex at a time.
 #[derive(Clone)]
 pub struct Server {
     iopub: Arc<Mutex<Connection>>,
-    _stdin: Arc<Mutex<Connection>>,
+    stdin: Arc<Mutex<Connection>>,
     latest_execution_request: Arc<Mutex<Option<JupyterMessage>>>,
+    // aborts the cell being executed when an interrupt is requested
+    execution_abort_handle: Arc<Mutex<Option<AbortHandle>>>,
+    // set by an interrupt so a cell waiting for input stops waiting
+    execution_interrupted: Arc<AtomicBool>,
     shutdown_requested_receiver: Arc<Mutex<mpsc::Receiver<()>>>,
     shutdown_requested_sender: Arc<Mutex<mpsc::Sender<()>>>,
+    // shared with the shell thread so completion and inspection can see the registered views
+    execution_ctx: ExecutionContext,
+    // lets the shell thread run queries for inspection
+    runtime: tokio::runtime::Handle,
     debug: bool,
 }
 
@@ -71,13 +93,18 @@ impl Server {
         )?));
 
         let (shutdown_requested_sender, shutdown_requested_receiver) = mpsc::channel();
+        let config = ExecutionConfig::new().with_batch_size(32768);
 
         let server = Server {
             iopub,
             latest_execution_request: Arc::new(Mutex::new(None)),
-            _stdin: Arc::new(Mutex::new(stdin_socket)),
+            execution_abort_handle: Arc::new(Mutex::new(None)),
+            execution_interrupted: Arc::new(AtomicBool::new(false)),
+            stdin: Arc::new(Mutex::new(stdin_socket)),
             shutdown_requested_receiver: Arc::new(Mutex::new(shutdown_requested_receiver)),
             shutdown_requested_sender: Arc::new(Mutex::new(shutdown_requested_sender)),
+            execution_ctx: new_execution_context(config),
+            runtime: tokio::runtime::Handle::current(),
             debug,
         };
 
@@ -149,9 +176,7 @@ impl Server {
         _: bool,
     ) -> Result<()> {
         let mut execution_count: i32 = 0;
-        let config = ExecutionConfig::new().with_batch_size(32768);
-        let mut execution_ctx = ExecutionContext::with_config(config);
-        let box_ctx = BoxContext::new(None, None);
+        let mut session = Session::default();
 
         loop {
             let message = execution_receiver.recv()?;
@@ -170,67 +195,141 @@ impl Server {
                 })
                 .send(&*self.iopub.lock().unwrap())?;
 
-            // replace
-            let src = variables::replace_hocon_parameters(src);
-
-            match parse_config(box_ctx.clone(), format!("[{}]", src).as_str(), true, false) {
-                Ok(stages) => {
-                    match execute(box_ctx.clone(), &mut execution_ctx, stages, false).await {
-                        Ok(result) => {
-                            let html = create_html_table(
-                                result.unwrap().collect().await.unwrap(),
-                                Some(10),
-                                &["tex2jax_ignore"],
-                            )
-                            .unwrap();
-
-                            let mut data: HashMap<String, JsonValue> = HashMap::new();
-                            data.insert("text/html".into(), json::from(html));
+            // Execute on a copy of the session in an abortable task so an interrupt drops the
+            // running query and leaves the session as it was before the cell. Views the cell has
+            // already registered remain.
+            let (abort_handle, abort_registration) = AbortHandle::new_pair();
+            *self.execution_abort_handle.lock().unwrap() = Some(abort_handle);
+            self.execution_interrupted.store(false, Ordering::SeqCst);
+            let interrupted = self.execution_interrupted.clone();
+            let mut cell_session = session.clone();
+            let mut cell_ctx = self.execution_ctx.clone();
+            let src = src.to_owned();
+            let iopub = self.iopub.clone();
+            let stdin = self.stdin.clone();
+            let allow_stdin = message.allow_stdin();
+            let debug = self.debug;
+            let cell_message = message.clone();
+            let task = tokio::spawn(Abortable::new(
+                async move {
+                    // show stage events as a progress display updated while the cell runs
+                    let mut progress = Progress::default();
+                    let mut on_event = |event: &Event| {
+                        if let Some((msg_type, content)) = progress.update(event) {
+                            if let Err(err) = cell_message
+                                .new_message(msg_type)
+                                .with_content(content)
+                                .send(&*iopub.lock().unwrap())
+                            {
+                                eprintln!("Error sending progress: {}", err);
+                            }
+                        }
+                    };
+                    // ask the frontend for input, such as a missing parameter, if it allows it. The
+                    // reply is polled for so an interrupt can end the wait.
+                    let mut prompt = |text: &str| -> Result<String> {
+                        tokio::task::block_in_place(|| {
+                            let stdin = stdin.lock().unwrap();
+                            let request = cell_message
+                                .new_frontend_request("input_request")
+                                .with_content(object! {
+                                    "prompt" => text,
+                                    "password" => false,
+                                });
+                            request.send(&stdin)?;
+                            loop {
+                                if interrupted.load(Ordering::SeqCst) {
+                                    return Err(BoxError::new("Execution interrupted".to_string()));
+                                }
+                                if stdin.socket.poll(zmq::POLLIN, STDIN_POLL_MILLIS)? == 0 {
+                                    continue;
+                                }
+                                let reply = JupyterMessage::read(&stdin, debug)?;
+                                if reply.message_type() == "input_reply"
+                                    && reply.parent_msg_id() == request.msg_id()
+                                {
+                                    return Ok(reply.value().to_string());
+                                }
+                                eprintln!(
+                                    "Got unexpected message on stdin channel: {}",
+                                    reply.message_type()
+                                );
+                            }
+                        })
+                    };
+                    let prompt = if allow_stdin {
+                        Some(&mut prompt as &mut (dyn FnMut(&str) -> Result<String> + Send))
+                    } else {
+                        None
+                    };
+                    let result = execute_cell(
+                        &mut cell_session,
+                        &mut cell_ctx,
+                        &src,
+                        &mut on_event,
+                        prompt,
+                    )
+                    .await
+                    .map_err(|error| Traceback::from_error(&error, &src));
+                    (cell_session, result)
+                },
+                abort_registration,
+            ));
+            let result = match task.await {
+                Ok(Ok((cell_session, result)))
+                    if !self.execution_interrupted.load(Ordering::SeqCst) =>
+                {
+                    session = cell_session;
+                    result
+                }
+                // an interrupt may also end a cell waiting for input rather than abort it
+                Ok(Ok(_)) | Ok(Err(Aborted)) => Err(Traceback::new(
+                    "KeyboardInterrupt",
+                    "Execution interrupted".to_string(),
+                )),
+                Err(e) => Err(Traceback::new("ExecutionError", e.to_string())),
+            };
+            *self.execution_abort_handle.lock().unwrap() = None;
+
+            match result {
+                Ok(data) => {
+                    match data {
+                        // charts are displays rather than the value of the cell
+                        Some(data) if data.contains_key(VEGA_LITE_MIME_TYPE) => {
                             message
-                                .new_message("execute_result")
+                                .new_message("display_data")
                                 .with_content(object! {
-                                    "execution_count" => execution_count,
                                     "data" => data,
                                     "metadata" => object!(),
+                                    "transient" => object!(),
                                 })
                                 .send(&*self.iopub.lock().unwrap())?;
-
-                            execution_response_sender.send(message.new_reply().with_content(
-                                object! {
-                                    "status" => "ok",
-                                    "execution_count" => execution_count,
-                                },
-                            ))?;
                         }
-                        Err(e) => {
+                        Some(data) => {
                             message
-                                .new_message("error")
+                                .new_message("execute_result")
                                 .with_content(object! {
-                                    "ename": "Error",
-                                    "evalue": e.to_string(),
-                                    "traceback" => array![
-                                        e.to_string()
-                                    ],
+                                    "execution_count" => execution_count,
+                                    "data" => data,
+                                    "metadata" => object!(),
                                 })
                                 .send(&*self.iopub.lock().unwrap())?;
-                            execution_response_sender.send(message.new_reply().with_content(
-                                object! {
-                                    "status" => "error",
-                                    "execution_count" => execution_count
-                                },
-                            ))?;
                         }
+                        None => {}
                     }
+
+                    execution_response_sender.send(message.new_reply().with_content(object! {
+                        "status" => "ok",
+                        "execution_count" => execution_count,
+                    }))?;
                 }
-                Err(e) => {
+                Err(traceback) => {
                     message
                         .new_message("error")
                         .with_content(object! {
-                            "ename": "Error",
-                                    "evalue": e.to_string(),
-                                    "traceback" => array![
-                                e.to_string()
-                            ],
+                            "ename": traceback.ename,
+                            "evalue": traceback.evalue,
+                            "traceback" => traceback.traceback,
                         })
                         .send(&*self.iopub.lock().unwrap())?;
                     execution_response_sender.send(message.new_reply().with_content(object! {
@@ -284,10 +383,15 @@ impl Server {
                 .with_content(kernel_info())
                 .send(connection)?;
         } else if message.message_type() == "is_complete_request" {
-            message
-                .new_reply()
-                .with_content(object! {"status" => "complete"})
-                .send(connection)?;
+            let content = match is_complete(message.code()) {
+                Completeness::Complete => object! {"status" => "complete"},
+                Completeness::Incomplete { indent } => object! {
+                    "status" => "incomplete",
+                    "indent" => indent,
+                },
+                Completeness::Invalid => object! {"status" => "invalid"},
+            };
+            message.new_reply().with_content(content).send(connection)?;
         } else if message.message_type() == "execute_request" {
             execution_sender.send(message)?;
             execution_response_receiver.recv()?.send(connection)?;
@@ -295,18 +399,52 @@ impl Server {
             || message.message_type() == "comm_info_request"
         {
             // We don't handle this yet.
-            // } else if message.message_type() == "complete_request" {
-            //     let reply = message.new_reply().with_content(
-            //         match handle_completion_request(&context, message) {
-            //             Ok(response_content) => response_content,
-            //             Err(error) => object! {
-            //                 "status" => "error",
-            //                 "ename" => error.to_string(),
-            //                 "evalue" => "",
-            //             },
-            //         },
-            //     );
-            //     reply.send(&connection)?;
+        } else if message.message_type() == "complete_request" {
+            let completions = completion::complete(
+                message.code(),
+                message.cursor_pos(),
+                &CompletionSource::new(&self.execution_ctx),
+            );
+            message
+                .new_reply()
+                .with_content(object! {
+                    "status" => "ok",
+                    "matches" => completions.matches,
+                    "cursor_start" => completions.cursor_start,
+                    "cursor_end" => completions.cursor_end,
+                    "metadata" => object!(),
+                })
+                .send(connection)?;
+        } else if message.message_type() == "inspect_request" {
+            let inspection = self.runtime.block_on(inspection::inspect(
+                &self.execution_ctx,
+                message.code(),
+                message.cursor_pos(),
+            ));
+            let content = match inspection {
+                Ok(Some(text)) => object! {
+                    "status" => "ok",
+                    "found" => true,
+                    "data" => object!{"text/plain" => text},
+                    "metadata" => object!(),
+                },
+                Ok(None) => object! {
+                    "status" => "ok",
+                    "found" => false,
+                    "data" => object!(),
+                    "metadata" => object!(),
+                },
+                Err(e) => {
+                    let traceback = Traceback::from_error(&e, message.code());
+                    object! {
+                        "status" => "error",
+                        "ename" => traceback.ename,
+                        "evalue" => traceback.evalue,
+                        "traceback" => traceback.traceback,
+                    }
+                }
+            };
+            message.new_reply().with_content(content).send(connection)?;
         } else {
             eprintln!(
                 "Got unrecognized message type on shell channel: {}",
@@ -323,10 +461,14 @@ impl Server {
             match message.message_type() {
                 "shutdown_request" => self.signal_shutdown(),
                 "interrupt_request" => {
-                    message.new_reply().send(&connection)?;
-                    eprintln!(
-                        "Rust doesn't support interrupting execution. Perhaps restart kernel?"
-                    );
+                    self.execution_interrupted.store(true, Ordering::SeqCst);
+                    if let Some(abort_handle) = self.execution_abort_handle.lock().unwrap().take() {
+                        abort_handle.abort();
+                    }
+                    message
+                        .new_reply()
+                        .with_content(object! {"status" => "ok"})
+                        .send(&connection)?;
                 }
                 _ => {
                     eprintln!(
@@ -339,6 +481,24 @@ impl Server {
     }
 }
 
+/// Execute a cell, which may start with `%limit N` to override the session's row limit, and
+/// render its output
+async fn execute_cell(
+    session: &mut Session,
+    ctx: &mut ExecutionContext,
+    src: &str,
+    on_event: &mut (dyn FnMut(&Event) + Send),
+    prompt: Option<&mut (dyn FnMut(&str) -> Result<String> + Send)>,
+) -> Result<Option<HashMap<String, JsonValue>>> {
+    let (limit, src) = row_limit(src).map_err(|error| BoxError::ParseError {
+        stage: None,
+        path: None,
+        error: Box::new(error),
+    })?;
+    let output = session.execute(ctx, src, on_event, prompt).await?;
+    mime_bundle(output, limit.unwrap_or(session.num_rows)).await
+}
+
 fn bind_socket(
     connection_file: &ConnectionFile,
     port: u16,
@@ -0,0 +1,310 @@
+//! Notebook magics are cells starting with `%` which are commands rather than a stage.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::jupyter::chart::{Chart, Mark};
+use crate::util::*;
+
+const MAGICS: [&str; 8] = [
+    "chart",
+    "conf",
+    "env",
+    "limit",
+    "metadata",
+    "printschema",
+    "sql",
+    "views",
+];
+
+lazy_static! {
+    static ref ARGUMENT: Regex = Regex::new(r#"([^\s=]+)=(?:"([^"]*)"|(\S*))"#).unwrap();
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Magic {
+    /// `%sql [name=view]` followed by a sql script. A named result is registered as a view.
+    Sql { name: Option<String>, sql: String },
+
+    /// `%chart mark x=column [y=column] [color=column] [title=text]` followed by a sql script
+    /// charts its result
+    Chart { chart: Chart, sql: String },
+
+    /// `%env [KEY=VALUE ...]` sets job parameters, or lists them without arguments
+    Env(Vec<(String, String)>),
+
+    /// `%conf [key=value ...]` sets the kernel's `numRows` and DataFusion options, or lists them
+    /// without arguments
+    Conf(Vec<(String, String)>),
+
+    /// `%printschema view`
+    PrintSchema(String),
+
+    /// `%views` lists the registered views
+    Views,
+
+    /// `%metadata view` describes a view in the Arc metadata format
+    Metadata(String),
+}
+
+impl Magic {
+    /// Parse a cell starting with `%` or return `None` for any other cell
+    pub fn parse(src: &str) -> Result<Option<Magic>> {
+        let src = src.trim();
+        let src = match src.strip_prefix('%') {
+            Some(src) => src,
+            None => return Ok(None),
+        };
+        let (line, body) = src.split_once('\n').unwrap_or((src, ""));
+        let (name, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+
+        let magic = match name {
+            "sql" => {
+                let mut name = None;
+                for (key, value) in arguments(rest)? {
+                    match key.as_str() {
+                        "name" => name = Some(value),
+                        _ => {
+                            return Err(BoxError::new(format!(
+                                "Unknown %sql argument '{}'. Expected one of ['name'].",
+                                key
+                            )))
+                        }
+                    }
+                }
+                if body.trim().is_empty() {
+                    return Err(BoxError::new(
+                        "Expected sql on the lines after %sql.".to_string(),
+                    ));
+                }
+                Magic::Sql {
+                    name,
+                    sql: body.to_string(),
+                }
+            }
+            "chart" => chart(rest, body)?,
+            "env" => Magic::Env(arguments(&format!("{}\n{}", rest, body))?),
+            "conf" => Magic::Conf(arguments(&format!("{}\n{}", rest, body))?),
+            "printschema" => Magic::PrintSchema(view_argument(name, rest, body)?),
+            "views" => {
+                no_arguments(name, rest, body)?;
+                Magic::Views
+            }
+            "metadata" => Magic::Metadata(view_argument(name, rest, body)?),
+            "limit" => {
+                return Err(BoxError::new(
+                    "Expected %limit to be the first line of a cell.".to_string(),
+                ))
+            }
+            _ => {
+                return Err(BoxError::new(format!(
+                    "Unknown magic '%{}'. Expected one of [{}].",
+                    name,
+                    MAGICS
+                        .iter()
+                        .map(|magic| format!("'%{}'", magic))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )))
+            }
+        };
+        Ok(Some(magic))
+    }
+}
+
+/// Strip a leading `%limit N` line, which sets the maximum number of rows the cell displays,
+/// returning the limit and the rest of the cell
+pub fn row_limit(src: &str) -> Result<(Option<usize>, &str)> {
+    let rest = match src.trim_start().strip_prefix("%limit") {
+        Some(rest) if rest.is_empty() || rest.starts_with(char::is_whitespace) => rest,
+        _ => return Ok((None, src)),
+    };
+    let (line, body) = rest.split_once('\n').unwrap_or((rest, ""));
+    let limit = line.trim().parse().map_err(|_| {
+        BoxError::new(format!(
+            "Expected %limit to have a single row count argument. Got '{}'.",
+            line.trim()
+        ))
+    })?;
+    Ok((Some(limit), body))
+}
+
+/// Parse `%chart mark [key=value ...]` followed by sql
+fn chart(rest: &str, body: &str) -> Result<Magic> {
+    let (mark, rest) = rest
+        .trim()
+        .split_once(char::is_whitespace)
+        .unwrap_or((rest.trim(), ""));
+    let mark = Mark::parse(mark)?;
+    let (mut x, mut y, mut color, mut title) = (None, None, None, None);
+    for (key, value) in arguments(rest)? {
+        match key.as_str() {
+            "x" => x = Some(value),
+            "y" => y = Some(value),
+            "color" => color = Some(value),
+            "title" => title = Some(value),
+            _ => {
+                return Err(BoxError::new(format!(
+                    "Unknown %chart argument '{}'. Expected one of ['x', 'y', 'color', 'title'].",
+                    key
+                )))
+            }
+        }
+    }
+    let x = x.ok_or_else(|| BoxError::new("Expected %chart to have an x argument.".to_string()))?;
+    match (&mark, &y) {
+        (Mark::Histogram, Some(_)) => return Err(BoxError::new(
+            "Expected %chart histogram to have no y argument as it counts the rows of each bin."
+                .to_string(),
+        )),
+        (Mark::Bar | Mark::Line | Mark::Scatter, None) => {
+            return Err(BoxError::new(
+                "Expected %chart to have a y argument.".to_string(),
+            ))
+        }
+        _ => {}
+    }
+    if body.trim().is_empty() {
+        return Err(BoxError::new(
+            "Expected sql on the lines after %chart.".to_string(),
+        ));
+    }
+    Ok(Magic::Chart {
+        chart: Chart {
+            mark,
+            x,
+            y,
+            color,
+            title,
+        },
+        sql: body.to_string(),
+    })
+}
+
+/// Parse whitespace separated `key=value` or `key="value"` arguments
+fn arguments(text: &str) -> Result<Vec<(String, String)>> {
+    let remainder = ARGUMENT.replace_all(text, "");
+    if !remainder.trim().is_empty() {
+        return Err(BoxError::new(format!(
+            "Expected arguments of the form key=value. Got '{}'.",
+            remainder.trim()
+        )));
+    }
+    Ok(ARGUMENT
+        .captures_iter(text)
+        .map(|captures| {
+            let value = captures.get(2).or_else(|| captures.get(3)).unwrap();
+            (captures[1].to_string(), value.as_str().to_string())
+        })
+        .collect())
+}
+
+fn view_argument(name: &str, rest: &str, body: &str) -> Result<String> {
+    let words = format!("{}\n{}", rest, body);
+    let words = words.split_whitespace().collect::<Vec<_>>();
+    match words.as_slice() {
+        [view] => Ok(view.to_string()),
+        _ => Err(BoxError::new(format!(
+            "Expected %{} to have a single view argument. Got {} arguments.",
+            name,
+            words.len()
+        ))),
+    }
+}
+
+fn no_arguments(name: &str, rest: &str, body: &str) -> Result<()> {
+    if rest.trim().is_empty() && body.trim().is_empty() {
+        Ok(())
+    } else {
+        Err(BoxError::new(format!(
+            "Expected %{} to have no arguments.",
+            name
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        assert_eq!(Magic::parse(r#"{"type": "SQLTransform"}"#)?, None);
+        assert_eq!(
+            Magic::parse("%sql name=out\nSELECT *\nFROM trips")?,
+            Some(Magic::Sql {
+                name: Some("out".to_string()),
+                sql: "SELECT *\nFROM trips".to_string()
+            })
+        );
+        assert_eq!(
+            Magic::parse("%sql\nSELECT 1")?,
+            Some(Magic::Sql {
+                name: None,
+                sql: "SELECT 1".to_string()
+            })
+        );
+        assert_eq!(
+            Magic::parse("%env ETL_CONF_ENV=test\nETL_CONF_PATH=\"/tmp/a b\"")?,
+            Some(Magic::Env(vec![
+                ("ETL_CONF_ENV".to_string(), "test".to_string()),
+                ("ETL_CONF_PATH".to_string(), "/tmp/a b".to_string())
+            ]))
+        );
+        assert_eq!(
+            Magic::parse(
+                "%chart bar x=vendor y=fare title=\"Fare by vendor\"\nSELECT * FROM trips"
+            )?,
+            Some(Magic::Chart {
+                chart: Chart {
+                    mark: Mark::Bar,
+                    x: "vendor".to_string(),
+                    y: Some("fare".to_string()),
+                    color: None,
+                    title: Some("Fare by vendor".to_string())
+                },
+                sql: "SELECT * FROM trips".to_string()
+            })
+        );
+        assert_eq!(Magic::parse("%conf")?, Some(Magic::Conf(vec![])));
+        assert_eq!(
+            Magic::parse("  %printschema trips ")?,
+            Some(Magic::PrintSchema("trips".to_string()))
+        );
+        assert_eq!(Magic::parse("%views")?, Some(Magic::Views));
+        assert_eq!(
+            Magic::parse("%metadata trips")?,
+            Some(Magic::Metadata("trips".to_string()))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Magic::parse("%sql name=out").is_err());
+        assert!(Magic::parse("%sql persist=true\nSELECT 1").is_err());
+        assert!(Magic::parse("%chart pie x=a y=b\nSELECT 1").is_err());
+        assert!(Magic::parse("%chart line y=b\nSELECT 1").is_err());
+        assert!(Magic::parse("%chart histogram x=a y=b\nSELECT 1").is_err());
+        assert!(Magic::parse("%chart scatter x=a y=b").is_err());
+        assert!(Magic::parse("%env KEY").is_err());
+        assert!(Magic::parse("%printschema").is_err());
+        assert!(Magic::parse("%views trips").is_err());
+        assert!(Magic::parse("%unknown").is_err());
+        assert!(Magic::parse("%limit 5").is_err());
+    }
+
+    #[test]
+    fn test_row_limit() -> Result<()> {
+        assert_eq!(
+            row_limit("%limit 100\n%sql\nSELECT 1")?,
+            (Some(100), "%sql\nSELECT 1")
+        );
+        assert_eq!(row_limit("\n %limit 5")?, (Some(5), ""));
+        assert_eq!(row_limit("%sql\nSELECT 1")?, (None, "%sql\nSELECT 1"));
+        assert_eq!(row_limit("%limited")?, (None, "%limited"));
+        assert!(row_limit("%limit all\n%views").is_err());
+        assert!(row_limit("%limit").is_err());
+        Ok(())
+    }
+}
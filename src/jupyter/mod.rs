@@ -1,10 +1,21 @@
+mod chart;
+mod completion;
 mod connection;
 mod connection_file;
+mod display;
+mod export;
+mod inspection;
 mod install;
+mod is_complete;
 mod jupyter_message;
 mod kernel;
+mod magic;
+mod progress;
+mod session;
+mod traceback;
 
 pub use connection_file::ConnectionFile;
+pub use export::export_notebook;
 pub use install::install;
-pub use jupyter_message::JupyterMessage;
+pub(crate) use jupyter_message::JupyterMessage;
 pub use kernel::Server;
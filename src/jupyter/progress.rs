@@ -0,0 +1,150 @@
+//! Progress of the stages of a cell shown as a single display updated as stage events happen
+
+use json::JsonValue;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::api::Event;
+
+pub struct Progress {
+    display_id: String,
+    lines: Vec<String>,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self {
+            display_id: Uuid::new_v4().to_string(),
+            lines: vec![],
+        }
+    }
+}
+
+impl Progress {
+    /// Record a stage event returning the iopub message type and content which show the progress
+    /// so far. The first event creates the display and later events update it.
+    pub fn update(&mut self, event: &Event) -> Option<(&'static str, JsonValue)> {
+        let stage = event.stage.as_ref()?;
+        let line = match (event.event.as_str(), event.success) {
+            ("enter", _) => format!("▶ {} running", describe_stage(stage)),
+            ("exit", Some(false)) => format!(
+                "✗ {} failed: {}",
+                describe_stage(stage),
+                event.error.as_deref().unwrap_or_default()
+            ),
+            ("exit", _) => {
+                let mut details = vec![format!("{} ms", event.duration.unwrap_or_default())];
+                if let Some(row_count) = event.row_count {
+                    details.push(format!(
+                        "{} row{}",
+                        row_count,
+                        if row_count == 1 { "" } else { "s" }
+                    ));
+                }
+                format!("✓ {} ({})", describe_stage(stage), details.join(", "))
+            }
+            _ => return None,
+        };
+
+        // an exit replaces the line of the stage's enter
+        let msg_type = if self.lines.is_empty() {
+            "display_data"
+        } else {
+            "update_display_data"
+        };
+        if event.event == "exit" {
+            self.lines.pop();
+        }
+        self.lines.push(line);
+
+        Some((
+            msg_type,
+            object! {
+                "data" => object! {
+                    "text/plain" => self.lines.join("\n"),
+                },
+                "metadata" => object!(),
+                "transient" => object! {
+                    "display_id" => self.display_id.clone(),
+                },
+            },
+        ))
+    }
+}
+
+/// The type of a stage and the view it creates or its name
+fn describe_stage(stage: &Value) -> String {
+    let stage_type = stage["type"].as_str().unwrap_or("stage");
+    match stage["outputView"]
+        .as_str()
+        .or_else(|| stage["name"].as_str())
+    {
+        Some(name) => format!("{} {}", stage_type, name),
+        None => stage_type.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_update() {
+        let mut progress = Progress::default();
+        let stage = json!({"type": "SQLTransform", "outputView": "trips"});
+        let (msg_type, content) = progress
+            .update(&Event {
+                event: "enter".to_string(),
+                stage: Some(stage.clone()),
+                ..Event::default()
+            })
+            .unwrap();
+        assert_eq!(msg_type, "display_data");
+        assert_eq!(
+            content["data"]["text/plain"],
+            "▶ SQLTransform trips running"
+        );
+
+        let (msg_type, content) = progress
+            .update(&Event {
+                event: "exit".to_string(),
+                duration: Some(12),
+                stage: Some(stage),
+                row_count: Some(1000),
+                ..Event::default()
+            })
+            .unwrap();
+        assert_eq!(msg_type, "update_display_data");
+        assert_eq!(
+            content["data"]["text/plain"],
+            "✓ SQLTransform trips (12 ms, 1000 rows)"
+        );
+        assert_eq!(
+            content["transient"]["display_id"],
+            progress.display_id.as_str()
+        );
+
+        progress.update(&Event {
+            event: "enter".to_string(),
+            stage: Some(json!({"type": "EqualityValidate"})),
+            ..Event::default()
+        });
+        let (_, content) = progress
+            .update(&Event {
+                event: "exit".to_string(),
+                success: Some(false),
+                error: Some("Expected 1 row.".to_string()),
+                stage: Some(json!({"type": "EqualityValidate"})),
+                ..Event::default()
+            })
+            .unwrap();
+        assert_eq!(
+            content["data"]["text/plain"],
+            "✓ SQLTransform trips (12 ms, 1000 rows)\n✗ EqualityValidate failed: Expected 1 row."
+        );
+
+        // job events have no stage
+        assert!(progress.update(&Event::default()).is_none());
+    }
+}
@@ -0,0 +1,293 @@
+//! The state a notebook kernel keeps between cells and the execution of a cell
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::prelude::*;
+use serde_json::Value;
+
+use crate::api::{execute_with, missing_parameters, params, parse_config, BoxContext, Event};
+use crate::jupyter::chart::Chart;
+use crate::jupyter::completion::CompletionSource;
+use crate::jupyter::magic::Magic;
+use crate::plugin;
+use crate::util::metadata::arc_metadata;
+use crate::util::*;
+
+/// The view the result of a `%sql` or `%chart` magic without a name is registered as while its
+/// stage runs
+const UNNAMED_VIEW: &str = "__box_sql";
+
+/// The result of a cell
+pub enum Output {
+    DataFrame(Arc<dyn DataFrame>),
+    Chart(Chart, Arc<dyn DataFrame>),
+    Text(String),
+    Json(Value),
+    Empty,
+}
+
+#[derive(Clone)]
+pub struct Session {
+    /// Parameters set with `%env` are added to the command line arguments
+    pub box_ctx: BoxContext,
+
+    /// The maximum number of rows displayed
+    pub num_rows: usize,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            box_ctx: BoxContext::new(None, None),
+            num_rows: 10,
+        }
+    }
+}
+
+impl Session {
+    /// Execute a cell which is either a magic or a stage passing each stage event to `on_event`.
+    /// Parameters the stages need which are not set are asked for with `prompt` if given.
+    pub async fn execute(
+        &mut self,
+        ctx: &mut ExecutionContext,
+        src: &str,
+        on_event: &mut (dyn FnMut(&Event) + Send),
+        prompt: Option<&mut (dyn FnMut(&str) -> Result<String> + Send)>,
+    ) -> Result<Output> {
+        let magic = Magic::parse(src).map_err(|error| BoxError::ParseError {
+            stage: None,
+            path: None,
+            error: Box::new(error),
+        })?;
+        match magic {
+            Some(magic) => self.execute_magic(ctx, magic, on_event).await,
+            None => {
+                let config = variables::replace_hocon_parameters(&format!("[{}]", src));
+                if let Some(prompt) = prompt {
+                    for name in missing_parameters(&self.box_ctx, &config)? {
+                        let value = prompt(&format!("{}: ", name))?;
+                        self.box_ctx
+                            .commandline_arguments
+                            .get_or_insert_with(HashMap::new)
+                            .insert(name, value);
+                    }
+                }
+                let stages = parse_config(self.box_ctx.clone(), &config, true, false)?;
+                // stages such as validations may not return a result
+                Ok(
+                    execute_with(self.box_ctx.clone(), ctx, stages, false, on_event)
+                        .await?
+                        .result
+                        .map_or(Output::Empty, Output::DataFrame),
+                )
+            }
+        }
+    }
+
+    async fn execute_magic(
+        &mut self,
+        ctx: &mut ExecutionContext,
+        magic: Magic,
+        on_event: &mut (dyn FnMut(&Event) + Send),
+    ) -> Result<Output> {
+        match magic {
+            Magic::Sql { name, sql } => Ok(self
+                .execute_sql(ctx, sql, name, on_event)
+                .await?
+                .map_or(Output::Empty, Output::DataFrame)),
+            Magic::Chart { chart, sql } => Ok(self
+                .execute_sql(ctx, sql, None, on_event)
+                .await?
+                .map_or(Output::Empty, |df| Output::Chart(chart, df))),
+            Magic::Env(parameters) if parameters.is_empty() => {
+                let mut parameters = self
+                    .box_ctx
+                    .commandline_arguments
+                    .iter()
+                    .flatten()
+                    .map(|(key, value)| format!("{}={}", key, value))
+                    .collect::<Vec<_>>();
+                parameters.sort();
+                Ok(Output::Text(parameters.join("\n")))
+            }
+            Magic::Env(parameters) => {
+                self.box_ctx
+                    .commandline_arguments
+                    .get_or_insert_with(HashMap::new)
+                    .extend(parameters);
+                Ok(Output::Empty)
+            }
+            Magic::Conf(options) if options.is_empty() => {
+                let state = ctx.state.lock().unwrap();
+                let config = &state.config;
+                Ok(Output::Text(
+                    [
+                        format!("numRows={}", self.num_rows),
+                        format!("batch_size={}", config.batch_size),
+                        format!(
+                            "repartition_aggregations={}",
+                            config.repartition_aggregations
+                        ),
+                        format!("repartition_joins={}", config.repartition_joins),
+                        format!("repartition_windows={}", config.repartition_windows),
+                        format!("target_partitions={}", config.target_partitions),
+                    ]
+                    .join("\n"),
+                ))
+            }
+            Magic::Conf(options) => {
+                for (key, value) in options {
+                    if key == "numRows" {
+                        self.num_rows = value.parse().map_err(|_| {
+                            BoxError::new(format!(
+                                "Unable to parse value '{}' for option 'numRows'.",
+                                value
+                            ))
+                        })?;
+                    } else {
+                        sql_script::set_option(ctx, &key, &value)?;
+                    }
+                }
+                Ok(Output::Empty)
+            }
+            Magic::PrintSchema(view) => Ok(Output::Text(print_schema(&fields(ctx, &view)?))),
+            Magic::Views => Ok(Output::Text(
+                CompletionSource::new(ctx)
+                    .views
+                    .into_keys()
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            )),
+            Magic::Metadata(view) => Ok(Output::Json(arc_metadata(&Schema::new(fields(
+                ctx, &view,
+            )?))?)),
+        }
+    }
+
+    /// Execute the sql of a magic as a `SQLTransform` registering the result as `name`, if given,
+    /// so it is executed and reported the same way as a stage
+    async fn execute_sql(
+        &self,
+        ctx: &mut ExecutionContext,
+        sql: String,
+        name: Option<String>,
+        on_event: &mut (dyn FnMut(&Event) + Send),
+    ) -> Result<Option<Arc<dyn DataFrame>>> {
+        let output_view = name.clone().unwrap_or_else(|| UNNAMED_VIEW.to_string());
+        let mut object = serde_json::Map::new();
+        object.insert("type".to_string(), Value::from("SQLTransform"));
+        object.insert("sql".to_string(), Value::from(sql));
+        object.insert("outputView".to_string(), Value::from(output_view));
+        let stage =
+            plugin::registry().create_stage(&object, &params(&self.box_ctx), true, false)?;
+        let result = execute_with(self.box_ctx.clone(), ctx, vec![stage], false, on_event).await;
+        // the result holds its table so remains valid once the view is removed
+        if name.is_none() {
+            ctx.deregister_table(UNNAMED_VIEW)?;
+        }
+        Ok(result?.result)
+    }
+}
+
+fn fields(ctx: &ExecutionContext, view: &str) -> Result<Vec<Field>> {
+    Ok(ctx
+        .table(view)?
+        .schema()
+        .fields()
+        .iter()
+        .map(|field| field.field().clone())
+        .collect())
+}
+
+/// Format a schema as a tree in the style of Spark's `printSchema`
+fn print_schema(fields: &[Field]) -> String {
+    let mut lines = vec!["root".to_string()];
+    for field in fields {
+        print_field(&mut lines, field, 0);
+    }
+    lines.join("\n")
+}
+
+fn print_field(lines: &mut Vec<String>, field: &Field, depth: usize) {
+    let (data_type, children) = match field.data_type() {
+        DataType::Struct(children) => ("struct".to_string(), children.clone()),
+        DataType::List(child) | DataType::LargeList(child) => {
+            ("array".to_string(), vec![child.as_ref().clone()])
+        }
+        data_type => (data_type.to_string(), vec![]),
+    };
+    lines.push(format!(
+        " |{}-- {}: {} (nullable = {})",
+        "    |".repeat(depth),
+        field.name(),
+        data_type,
+        field.is_nullable()
+    ));
+    for child in &children {
+        print_field(lines, child, depth + 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_print_schema() {
+        let fields = vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new(
+                "address",
+                DataType::Struct(vec![
+                    Field::new("city", DataType::Utf8, true),
+                    Field::new(
+                        "lines",
+                        DataType::List(Box::new(Field::new("item", DataType::Utf8, true))),
+                        true,
+                    ),
+                ]),
+                true,
+            ),
+        ];
+        assert_eq!(
+            print_schema(&fields),
+            "root
+ |-- id: Int64 (nullable = false)
+ |-- address: struct (nullable = true)
+ |    |-- city: Utf8 (nullable = true)
+ |    |-- lines: array (nullable = true)
+ |    |    |-- item: Utf8 (nullable = true)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sql_magic() -> Result<()> {
+        let mut ctx = ExecutionContext::new();
+        let mut session = Session::default();
+        let mut events = vec![];
+        let mut on_event = |event: &Event| events.push(event.event.to_owned());
+
+        let output = session
+            .execute(&mut ctx, "%sql\nSELECT 1 AS one", &mut on_event, None)
+            .await?;
+        assert!(matches!(output, Output::DataFrame(_)));
+        assert!(ctx.table(UNNAMED_VIEW).is_err());
+
+        let output = session
+            .execute(
+                &mut ctx,
+                "%sql name=two\nSELECT 2 AS two",
+                &mut on_event,
+                None,
+            )
+            .await?;
+        assert!(matches!(output, Output::DataFrame(_)));
+        assert!(ctx.table("two").is_ok());
+
+        // each magic runs as a stage
+        assert_eq!(events, vec!["enter", "exit", "enter", "exit"]);
+        Ok(())
+    }
+}
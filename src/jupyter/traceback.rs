@@ -0,0 +1,229 @@
+//! Errors of a cell described for the notebook: a name for the kind of error, the message and a
+//! traceback with the field, SQL position and stage the error relates to
+
+use datafusion::error::DataFusionError;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::jupyter::magic::{row_limit, Magic};
+use crate::util::*;
+
+lazy_static! {
+    // the unexpected token of a sql parser error
+    static ref FOUND: Regex = Regex::new(r#"found: ([^\s"\\]+)"#).unwrap();
+
+    // the first quoted name of a planning error such as 'No field named 'fare'.'
+    static ref QUOTED: Regex = Regex::new(r#"'([^']+)'"#).unwrap();
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Traceback {
+    pub ename: String,
+    pub evalue: String,
+    pub traceback: Vec<String>,
+}
+
+impl Traceback {
+    pub fn new(ename: &str, evalue: String) -> Self {
+        Self {
+            ename: ename.to_string(),
+            traceback: vec![format!("{}: {}", ename, evalue)],
+            evalue,
+        }
+    }
+
+    /// Describe the error of a cell with source `src`
+    pub fn from_error(error: &BoxError, src: &str) -> Self {
+        let (stage, path, inner, parsing) = match error {
+            BoxError::ParseError { stage, path, error } => {
+                (stage.as_ref(), path.as_deref(), error.as_ref(), true)
+            }
+            BoxError::ExecutionError { stage, error } => (Some(stage), None, error.as_ref(), false),
+            error => (None, None, error, false),
+        };
+        let mut traceback = Traceback::new(error_name(inner, parsing), inner.to_string());
+
+        if let Some(path) = path {
+            traceback.traceback.push(format!("Field: {}", path));
+        }
+
+        // the sql of a stage or a %sql or %chart cell
+        let sql = match stage {
+            Some(stage) => stage["sql"]
+                .as_str()
+                .or_else(|| stage["resolvedSql"].as_str())
+                .map(str::to_string),
+            None => cell_sql(src),
+        };
+        if let (BoxError::DataFusionError(_), Some(sql)) = (inner, sql) {
+            if let Some((line, column)) = sql_position(&sql, &traceback.evalue) {
+                traceback.traceback.push(format!(
+                    "SQL line {}, column {}:\n{}\n{}^",
+                    line,
+                    column,
+                    sql.lines().nth(line - 1).unwrap_or_default(),
+                    " ".repeat(column - 1)
+                ));
+            }
+        }
+
+        if let Some(stage) = stage {
+            if let Ok(stage) = serde_json::to_string_pretty(stage) {
+                traceback.traceback.push(format!("Stage:\n{}", stage));
+            }
+        }
+        traceback
+    }
+}
+
+fn error_name(error: &BoxError, parsing: bool) -> &'static str {
+    match error {
+        BoxError::SerdeError(_)
+        | BoxError::JsonError(_)
+        | BoxError::DataFusionError(DataFusionError::SQL(_)) => "ParseError",
+        BoxError::DataFusionError(DataFusionError::Plan(_)) => "PlanError",
+        BoxError::IoError(_)
+        | BoxError::HttpError(_)
+        | BoxError::DataFusionError(DataFusionError::IoError(_)) => "IoError",
+        _ if parsing => "ParseError",
+        _ => "ExecutionError",
+    }
+}
+
+fn cell_sql(src: &str) -> Option<String> {
+    let (_, src) = row_limit(src).ok()?;
+    match Magic::parse(src).ok()?? {
+        Magic::Sql { sql, .. } | Magic::Chart { sql, .. } => Some(sql),
+        _ => None,
+    }
+}
+
+/// The line and column, counting from one, of the token or name an error message refers to
+fn sql_position(sql: &str, message: &str) -> Option<(usize, usize)> {
+    let token = match FOUND.captures(message) {
+        Some(captures) if &captures[1] == "EOF" => {
+            let line = sql.trim_end().lines().count().max(1);
+            let column = sql
+                .trim_end()
+                .lines()
+                .last()
+                .unwrap_or_default()
+                .chars()
+                .count()
+                + 1;
+            return Some((line, column));
+        }
+        Some(captures) => captures[1].to_string(),
+        None => QUOTED
+            .captures(message)?
+            .get(1)?
+            .as_str()
+            .trim_start_matches('#')
+            .to_string(),
+    };
+    // a qualified name may be written unqualified
+    let offset = find_token(sql, &token).or_else(|| {
+        token
+            .rsplit_once('.')
+            .and_then(|(_, name)| find_token(sql, name))
+    })?;
+
+    let before = sql[..offset].chars().collect::<Vec<_>>();
+    let line = before.iter().filter(|c| **c == '\n').count() + 1;
+    let column = before.iter().rev().take_while(|c| **c != '\n').count() + 1;
+    Some((line, column))
+}
+
+/// The byte offset of the first case insensitive occurrence of a token which is not part of a
+/// longer identifier
+fn find_token(sql: &str, token: &str) -> Option<usize> {
+    let lower_sql = sql.to_lowercase();
+    let lower_token = token.to_lowercase();
+    if lower_token.is_empty() || lower_sql.len() != sql.len() {
+        return None;
+    }
+    let is_identifier = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+    lower_sql
+        .match_indices(&lower_token)
+        .map(|(offset, _)| offset)
+        .find(|offset| {
+            let before = lower_sql[..*offset].chars().last();
+            let after = lower_sql[offset + lower_token.len()..].chars().next();
+            let joins_before = is_identifier(before) && is_identifier(lower_token.chars().next());
+            let joins_after = is_identifier(after) && is_identifier(lower_token.chars().last());
+            !joins_before && !joins_after
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_sql_position() {
+        let sql = "SELECT fare\nFORM trips";
+        assert_eq!(
+            sql_position(
+                sql,
+                "SQL error: ParserError(\"Expected end of statement, found: FORM\")"
+            ),
+            Some((2, 1))
+        );
+        assert_eq!(
+            sql_position(
+                "SELECT farex, fare FROM trips",
+                "Error during planning: Invalid identifier '#fare' for schema farex"
+            ),
+            Some((1, 15))
+        );
+        assert_eq!(
+            sql_position(
+                "SELECT * FROM trips t\nWHERE t.distance > 1",
+                "Error during planning: No field named 't.distance'."
+            ),
+            Some((2, 7))
+        );
+        assert_eq!(
+            sql_position(
+                "SELECT * FROM\n",
+                "SQL error: ParserError(\"Expected identifier, found: EOF\")"
+            ),
+            Some((1, 14))
+        );
+        assert_eq!(sql_position("SELECT 1", "Execution error"), None);
+    }
+
+    #[test]
+    fn test_from_error() {
+        let error = BoxError::ParseError {
+            stage: Some(json!({"type": "SQLTransform", "persist": "yes"})),
+            path: Some("persist".to_string()),
+            error: Box::new(BoxError::new("invalid type".to_string())),
+        };
+        assert_eq!(
+            Traceback::from_error(&error, ""),
+            Traceback {
+                ename: "ParseError".to_string(),
+                evalue: "invalid type".to_string(),
+                traceback: vec![
+                    "ParseError: invalid type".to_string(),
+                    "Field: persist".to_string(),
+                    "Stage:\n{\n  \"type\": \"SQLTransform\",\n  \"persist\": \"yes\"\n}"
+                        .to_string()
+                ]
+            }
+        );
+
+        let error = BoxError::DataFusionError(DataFusionError::Plan(
+            "No field named 'distance'.".to_string(),
+        ));
+        assert_eq!(
+            Traceback::from_error(&error, "%limit 5\n%sql\nSELECT distance FROM trips").traceback,
+            vec![
+                "PlanError: Error during planning: No field named 'distance'.".to_string(),
+                "SQL line 1, column 8:\nSELECT distance FROM trips\n       ^".to_string()
+            ]
+        );
+    }
+}
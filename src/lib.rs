@@ -0,0 +1,22 @@
+//! The box pipeline engine: parse Arc style jobs and execute their stages with DataFusion.
+//!
+//! Jobs can be parsed from job files with [`api::parse_config`] and run with [`api::execute`] or
+//! built and run in code with [`api::Job`].
+
+#[macro_use]
+extern crate lazy_static;
+#[macro_use]
+extern crate json;
+
+pub mod api;
+pub mod extract;
+pub mod jupyter;
+pub mod plugin;
+pub mod transform;
+mod udf;
+pub mod util;
+pub mod validate;
+
+pub use api::{
+    execute, execute_with, parse_config, BoxContext, Event, Job, JobError, JobResult, PipelineStage,
+};
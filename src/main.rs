@@ -1,16 +1,10 @@
 #[macro_use]
 extern crate lazy_static;
-#[macro_use]
-extern crate json;
-
-mod api;
-mod extract;
-mod jupyter;
-mod transform;
-mod util;
 
-use api::*;
-use util::*;
+use box_lib::api::{self, BoxContext};
+use box_lib::jupyter;
+use box_lib::plugin;
+use box_lib::util::*;
 
 use std::collections::HashMap;
 use std::fs;
@@ -24,10 +18,7 @@ use datafusion_objectstore_s3::object_store::aws::AmazonS3FileSystem;
 
 use regex::Regex;
 
-#[allow(unused_imports)]
 use datafusion::arrow::util::pretty::print_batches;
-#[allow(unused_imports)]
-use util::create_html_table;
 
 lazy_static! {
     static ref PARAMETER_RE: Regex = Regex::new("^([a-zA-Z0-9_-]+)=(.+)$").unwrap();
@@ -46,6 +37,41 @@ struct ExecuteOpt {
     #[structopt(short, long)]
     job_path: String,
 
+    /// Emit OpenLineage events to an http(s) endpoint or append them to a JSON-lines file
+    #[structopt(long)]
+    openlineage_uri: Option<String>,
+
+    /// The OpenLineage namespace of the job and its views
+    #[structopt(long, default_value = "box")]
+    openlineage_namespace: String,
+
+    /// Load stage and function plugins from the shared libraries in this directory
+    #[structopt(long)]
+    plugin_dir: Option<String>,
+
+    // `external_subcommand` tells structopt to put
+    // all the extra arguments into this Vec
+    #[structopt(subcommand)]
+    arguments: Option<Subcommands>,
+}
+
+#[derive(Debug, StructOpt)]
+struct GraphOpt {
+    #[structopt(short, long)]
+    job_path: String,
+
+    /// The output format: dot, mermaid or json
+    #[structopt(short, long, default_value = "dot")]
+    format: String,
+
+    /// Write the graph to this path instead of stdout
+    #[structopt(short, long)]
+    output: Option<String>,
+
+    /// Load stage and function plugins from the shared libraries in this directory
+    #[structopt(long)]
+    plugin_dir: Option<String>,
+
     // `external_subcommand` tells structopt to put
     // all the extra arguments into this Vec
     #[structopt(subcommand)]
@@ -56,6 +82,25 @@ struct ExecuteOpt {
 struct NotebookOpt {
     #[structopt(short, long)]
     connection_file: String,
+
+    /// Load stage and function plugins from the shared libraries in this directory
+    #[structopt(long)]
+    plugin_dir: Option<String>,
+}
+
+#[derive(Debug, StructOpt)]
+struct ExportOpt {
+    /// The notebook to convert to a job
+    #[structopt(short, long)]
+    notebook: String,
+
+    /// Write the job to this path instead of stdout
+    #[structopt(short, long)]
+    output: Option<String>,
+
+    /// Load stage and function plugins from the shared libraries in this directory
+    #[structopt(long)]
+    plugin_dir: Option<String>,
 }
 
 #[derive(Debug, StructOpt)]
@@ -65,7 +110,9 @@ struct InstallOpt {}
 #[structopt(name = "box", about = "arc.tripl.ai")]
 enum Opt {
     Execute(ExecuteOpt),
+    Graph(GraphOpt),
     Notebook(NotebookOpt),
+    Export(ExportOpt),
     Install(InstallOpt),
 }
 
@@ -73,14 +120,24 @@ enum Opt {
 async fn main() -> Result<()> {
     match Opt::from_args() {
         Opt::Execute(opt) => execute(opt).await,
+        Opt::Graph(opt) => graph(opt).await,
         Opt::Notebook(opt) => notebook(opt).await.map(|_| ()),
+        Opt::Export(opt) => export(opt).await,
         Opt::Install(opt) => install(opt).await.map(|_| ()),
     }
 }
 
-async fn execute(opt: ExecuteOpt) -> Result<()> {
-    // read and validate command line arguments to hashmap
-    let commandline_arguments = match opt.arguments {
+/// load any plugins before jobs are parsed
+fn load_plugins(plugin_dir: Option<String>) -> Result<()> {
+    match plugin_dir {
+        Some(plugin_dir) => plugin::registry_mut().load_dir(Path::new(&plugin_dir)),
+        None => Ok(()),
+    }
+}
+
+/// read and validate command line arguments to hashmap
+fn parse_arguments(arguments: Option<Subcommands>) -> Result<HashMap<String, String>> {
+    match arguments {
         Some(Subcommands::Other(subcommands)) => subcommands
             .iter()
             .map(|subcommand| match PARAMETER_RE.captures(subcommand) {
@@ -90,18 +147,35 @@ async fn execute(opt: ExecuteOpt) -> Result<()> {
                     subcommand
                 ))),
             })
-            .collect::<Result<HashMap<String, String>>>()?,
-        _ => HashMap::new(),
-    };
+            .collect::<Result<HashMap<String, String>>>(),
+        _ => Ok(HashMap::new()),
+    }
+}
+
+async fn execute(opt: ExecuteOpt) -> Result<()> {
+    load_plugins(opt.plugin_dir)?;
+    let commandline_arguments = parse_arguments(opt.arguments)?;
 
     let path = fs::canonicalize(opt.job_path)?;
-    let box_ctx = BoxContext::new(
+    let job_name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut box_ctx = BoxContext::new(
         Some(path.into_os_string().into_string().unwrap()),
         Some(commandline_arguments),
     );
 
+    if let Some(openlineage_uri) = opt.openlineage_uri {
+        box_ctx = box_ctx.with_openlineage(openlineage::OpenLineageClient::new(
+            &openlineage_uri,
+            opt.openlineage_namespace,
+            job_name,
+        ));
+    }
+
     let execution_config = ExecutionConfig::new().with_batch_size(32768);
-    let mut execution_ctx = ExecutionContext::with_config(execution_config);
+    let mut execution_ctx = api::new_execution_context(execution_config);
 
     #[cfg(feature = "s3")]
     execution_ctx.register_object_store(
@@ -115,16 +189,79 @@ async fn execute(opt: ExecuteOpt) -> Result<()> {
     // convert hocon to json
     let config = variables::replace_hocon_parameters(config.as_str());
 
+    let lifecycle_plugins =
+        api::parse_lifecycle_plugins(box_ctx.clone(), config.as_str(), true, false)?;
+    let box_ctx = box_ctx.with_lifecycle_plugins(lifecycle_plugins);
+
     let stages = api::parse_config(box_ctx.clone(), config.as_str(), true, false)?;
 
-    if let Ok(result) = api::execute(box_ctx, &mut execution_ctx, stages, true).await {
-        print_batches(result.unwrap().collect().await.unwrap().as_ref()).unwrap()
+    if let Ok(Some(result)) = api::execute(box_ctx, &mut execution_ctx, stages, true).await {
+        print_batches(result.collect().await.unwrap().as_ref()).unwrap()
+    }
+
+    Ok(())
+}
+
+async fn graph(opt: GraphOpt) -> Result<()> {
+    load_plugins(opt.plugin_dir)?;
+    let commandline_arguments = parse_arguments(opt.arguments)?;
+
+    let path = fs::canonicalize(opt.job_path)?;
+    let box_ctx = BoxContext::new(
+        Some(path.into_os_string().into_string().unwrap()),
+        Some(commandline_arguments),
+    );
+
+    let config = fs::read_to_string(Path::new(&box_ctx.clone().job_path.unwrap()))
+        .map_err(BoxError::from)?;
+
+    // convert hocon to json
+    let config = variables::replace_hocon_parameters(config.as_str());
+
+    // parse (but do not execute) the stages allowing parameters to be missing
+    let mut stages = api::parse_config(box_ctx.clone(), config.as_str(), true, true)?
+        .iter()
+        .map(|stage| stage.to_value())
+        .collect::<Vec<_>>();
+
+    // read the sql of any SQLTransform which keeps it in a file
+    let execution_ctx = api::new_execution_context(ExecutionConfig::new());
+    for stage in &mut stages {
+        let input_uri = match (
+            stage["type"].as_str(),
+            stage.get("sql"),
+            stage["inputURI"].as_str(),
+        ) {
+            (Some("SQLTransform"), None, Some(input_uri)) => input_uri.to_owned(),
+            _ => continue,
+        };
+        let sql = uri::read_to_string(&box_ctx, &execution_ctx, &input_uri).await?;
+        stage["sql"] = serde_json::Value::String(sql);
+    }
+
+    let graph = graph::JobGraph::try_new(&stages)?;
+    let output = match opt.format.as_str() {
+        "dot" => graph.to_dot(),
+        "mermaid" => graph.to_mermaid(),
+        "json" => graph.to_json()?,
+        format => {
+            return Err(BoxError::new(format!(
+                "Expected format to be one of ['dot', 'mermaid', 'json']. Got '{}'.",
+                format
+            )))
+        }
+    };
+
+    match opt.output {
+        Some(output_path) => fs::write(output_path, output)?,
+        None => println!("{}", output),
     }
 
     Ok(())
 }
 
 async fn notebook(opt: NotebookOpt) -> Result<()> {
+    load_plugins(opt.plugin_dir)?;
     let connection_file = fs::read_to_string(Path::new(&opt.connection_file))?;
     let connection_file: jupyter::ConnectionFile = serde_json::from_str(connection_file.as_str())?;
     let server = jupyter::Server::start(&connection_file, false)?;
@@ -133,6 +270,19 @@ async fn notebook(opt: NotebookOpt) -> Result<()> {
     Ok(())
 }
 
+async fn export(opt: ExportOpt) -> Result<()> {
+    load_plugins(opt.plugin_dir)?;
+    let notebook = fs::read_to_string(Path::new(&opt.notebook))?;
+    let job = jupyter::export_notebook(&notebook)?;
+
+    match opt.output {
+        Some(output_path) => fs::write(output_path, job)?,
+        None => println!("{}", job),
+    }
+
+    Ok(())
+}
+
 async fn install(_: InstallOpt) -> Result<()> {
     jupyter::install()?;
     Ok(())
@@ -0,0 +1,89 @@
+use std::fmt;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datafusion::arrow::util::pretty::pretty_format_batches;
+use datafusion::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::api::BoxContext;
+use crate::plugin::LifecyclePlugin;
+use crate::util::*;
+
+/// Prints the first rows of the result of each stage
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct DataFramePrinter {
+    #[serde(rename(serialize = "type", deserialize = "type"))]
+    _type: String,
+
+    #[serde(rename = "numRows", default = "default_num_rows")]
+    num_rows: usize,
+}
+
+fn default_num_rows() -> usize {
+    20
+}
+
+impl fmt::Display for DataFramePrinter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", serde_json::to_string(self).unwrap())
+    }
+}
+
+impl DataFramePrinter {
+    pub fn try_new(json: String) -> Result<DataFramePrinter> {
+        serde_json::from_str::<DataFramePrinter>(&json).map_err(BoxError::from)
+    }
+
+    /// Format the first `num_rows` rows of a result as a table
+    async fn format(&self, df: Arc<dyn DataFrame>) -> Result<String> {
+        let batches = df.limit(self.num_rows)?.collect().await?;
+        Ok(pretty_format_batches(&batches)?)
+    }
+}
+
+#[async_trait]
+impl LifecyclePlugin for DataFramePrinter {
+    fn to_value(&self) -> Value {
+        serde_json::to_value(&self).unwrap()
+    }
+
+    async fn after_stage(
+        &self,
+        _: &BoxContext,
+        _: &Value,
+        result: Option<Arc<dyn DataFrame>>,
+    ) -> Result<()> {
+        if let Some(df) = result {
+            println!("{}", self.format(df).await?);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{ArrayRef, Int64Array};
+
+    #[tokio::test]
+    async fn test_format() -> Result<()> {
+        let mut ctx = ExecutionContext::new();
+        register_view(
+            &mut ctx,
+            "ids",
+            vec![("id", Arc::new(Int64Array::from(vec![1, 2, 3])) as ArrayRef)],
+        )?;
+        let printer =
+            DataFramePrinter::try_new(r#"{"type": "DataFramePrinter", "numRows": 2}"#.to_string())?;
+        assert_eq!(
+            printer
+                .format(ctx.sql("SELECT id FROM ids ORDER BY id").await?)
+                .await?,
+            "+----+\n| id |\n+----+\n| 1  |\n| 2  |\n+----+"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,542 @@
+//! The C ABI between box and plugins.
+//!
+//! A plugin exports a [`PluginDeclaration`] named `box_plugin_declaration`. When it is loaded its
+//! `register` is called with a [`PluginHost`] to register [`PluginStage`]s and
+//! [`PluginFunction`]s. Only these `repr(C)` structs, null terminated UTF-8 strings and arrays in
+//! the [Arrow C data interface](https://arrow.apache.org/docs/format/CDataInterface.html) cross
+//! the boundary so a plugin may be written in any language and built with any compiler.
+//!
+//! Strings passed by either side are borrowed for the duration of the call. An array passed to a
+//! plugin is owned by the plugin which must release it and an array written by a plugin is
+//! released by box. A plugin reports a failed call with [`PluginError::set_message`].
+
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datafusion::arrow::array::{make_array, Array, ArrayData, ArrayRef, StructArray};
+use datafusion::arrow::datatypes::{DataType, SchemaRef, TimeUnit};
+use datafusion::arrow::ffi::{ArrowArray, FFI_ArrowArray, FFI_ArrowSchema};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::datasource::MemTable;
+use datafusion::error::DataFusionError;
+use datafusion::physical_plan::functions::{
+    make_scalar_function, ReturnTypeFunction, Signature, Volatility,
+};
+use datafusion::physical_plan::udf::ScalarUDF;
+use datafusion::prelude::*;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::api::{BoxContext, PipelineStage};
+use crate::plugin::{StageFactory, StageInfo};
+use crate::util::*;
+
+/// Incremented whenever the declaration, the host or the callbacks change incompatibly
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+/// Returned by a successful call without a result
+pub const PLUGIN_OK: i32 = 0;
+
+/// Returned by a successful call which has written its result
+pub const PLUGIN_RESULT: i32 = 1;
+
+/// Returned by a failed call after setting the error message
+pub const PLUGIN_ERROR: i32 = -1;
+
+/// The `box_plugin_declaration` a plugin exports
+#[repr(C)]
+pub struct PluginDeclaration {
+    /// The `PLUGIN_ABI_VERSION` the plugin implements
+    pub abi_version: u32,
+
+    /// Register the stages and functions of the plugin with the host
+    pub register: unsafe extern "C" fn(host: *mut PluginHost),
+}
+
+/// The registration functions passed to a plugin's `register`
+#[repr(C)]
+pub struct PluginHost {
+    pub register_stage: unsafe extern "C" fn(host: *mut PluginHost, stage: *const PluginStage),
+    pub register_function:
+        unsafe extern "C" fn(host: *mut PluginHost, function: *const PluginFunction),
+
+    /// The registrations of the plugin being loaded
+    registrations: *mut c_void,
+}
+
+/// A stage type. `data` is passed to every call of `execute`, which may happen on any thread
+/// and on several at once, and must live as long as the process.
+#[repr(C)]
+pub struct PluginStage {
+    /// The `type` of the stage
+    pub stage_type: *const c_char,
+
+    /// The documentation of the stage as json such as `{"description": "Read a view",
+    /// "required": ["inputView"], "optional": ["limit"], "defaults": {"persist": false}}`
+    pub info: *const c_char,
+
+    pub data: *mut c_void,
+
+    /// Execute a stage given its json configuration and, if it has an `inputView`, the rows of
+    /// that view as a struct array, otherwise `input` and `input_schema` are null. Returns
+    /// `PLUGIN_RESULT` after writing a struct array to `output` and `output_schema`, which is
+    /// registered as its `outputView` if it has one, `PLUGIN_OK` if there is no result or
+    /// `PLUGIN_ERROR`.
+    pub execute: unsafe extern "C" fn(
+        data: *mut c_void,
+        config: *const c_char,
+        input: *mut FFI_ArrowArray,
+        input_schema: *mut FFI_ArrowSchema,
+        output: *mut FFI_ArrowArray,
+        output_schema: *mut FFI_ArrowSchema,
+        error: *mut PluginError,
+    ) -> i32,
+}
+
+/// A scalar function taking `num_args` arguments of any type. `data` is passed to every call of
+/// `invoke`, which may happen on any thread and on several at once, and must live as long as the
+/// process.
+#[repr(C)]
+pub struct PluginFunction {
+    pub name: *const c_char,
+
+    pub num_args: usize,
+
+    /// The Arrow C data interface format of the result, a primitive type such as `l` for a 64 bit
+    /// integer or `u` for a string
+    pub return_type: *const c_char,
+
+    /// True if the function may return a different result for the same arguments
+    pub volatile: bool,
+
+    pub data: *mut c_void,
+
+    /// Evaluate the function over `num_args` arrays of the same length, writing an array of that
+    /// length to `output` and `output_schema`. Returns `PLUGIN_OK` or `PLUGIN_ERROR`.
+    pub invoke: unsafe extern "C" fn(
+        data: *mut c_void,
+        args: *const *mut FFI_ArrowArray,
+        arg_schemas: *const *mut FFI_ArrowSchema,
+        num_args: usize,
+        output: *mut FFI_ArrowArray,
+        output_schema: *mut FFI_ArrowSchema,
+        error: *mut PluginError,
+    ) -> i32,
+}
+
+/// The error of a failed call
+#[repr(C)]
+pub struct PluginError {
+    /// Set the message of the error
+    pub set_message: unsafe extern "C" fn(error: *mut PluginError, message: *const c_char),
+
+    /// The `Option<String>` the message is copied to
+    message: *mut c_void,
+}
+
+impl PluginError {
+    fn new(message: &mut Option<String>) -> Self {
+        Self {
+            set_message,
+            message: message as *mut Option<String> as *mut c_void,
+        }
+    }
+}
+
+unsafe extern "C" fn set_message(error: *mut PluginError, message: *const c_char) {
+    let target = &mut *((*error).message as *mut Option<String>);
+    *target = Some(CStr::from_ptr(message).to_string_lossy().into_owned());
+}
+
+fn failure(name: &str, message: Option<String>) -> BoxError {
+    BoxError::new(message.unwrap_or_else(|| format!("Plugin '{}' failed.", name)))
+}
+
+/// The stages and functions a plugin registered
+#[derive(Default)]
+pub(crate) struct Registrations {
+    pub(crate) stages: Vec<(String, StageInfo, StageFactory)>,
+    pub(crate) functions: Vec<ScalarUDF>,
+    error: Option<BoxError>,
+}
+
+/// Call the `register` of a plugin collecting its registrations
+pub(crate) unsafe fn register(declaration: &PluginDeclaration) -> Result<Registrations> {
+    let mut registrations = Registrations::default();
+    let mut host = PluginHost {
+        register_stage,
+        register_function,
+        registrations: &mut registrations as *mut Registrations as *mut c_void,
+    };
+    (declaration.register)(&mut host);
+    match registrations.error.take() {
+        Some(error) => Err(error),
+        None => Ok(registrations),
+    }
+}
+
+unsafe extern "C" fn register_stage(host: *mut PluginHost, stage: *const PluginStage) {
+    let registrations = &mut *((*host).registrations as *mut Registrations);
+    match stage_registration(&*stage) {
+        Ok(stage) => registrations.stages.push(stage),
+        Err(error) => {
+            registrations.error.get_or_insert(error);
+        }
+    }
+}
+
+unsafe extern "C" fn register_function(host: *mut PluginHost, function: *const PluginFunction) {
+    let registrations = &mut *((*host).registrations as *mut Registrations);
+    match scalar_udf(&*function) {
+        Ok(udf) => registrations.functions.push(udf),
+        Err(error) => {
+            registrations.error.get_or_insert(error);
+        }
+    }
+}
+
+unsafe fn string(value: *const c_char, name: &str) -> Result<String> {
+    if value.is_null() {
+        return Err(BoxError::new(format!("Expected '{}' to be set.", name)));
+    }
+    CStr::from_ptr(value)
+        .to_str()
+        .map(str::to_owned)
+        .map_err(BoxError::from)
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Info {
+    description: String,
+    #[serde(default)]
+    required: Vec<String>,
+    #[serde(default)]
+    optional: Vec<String>,
+    #[serde(default)]
+    defaults: serde_json::Map<String, Value>,
+}
+
+// the registry documents builtin stages with static strings and plugins are never unloaded
+fn leak(value: String) -> &'static str {
+    Box::leak(value.into_boxed_str())
+}
+
+unsafe fn stage_registration(stage: &PluginStage) -> Result<(String, StageInfo, StageFactory)> {
+    let stage_type = string(stage.stage_type, "stage_type")?;
+    let info = serde_json::from_str::<Info>(&string(stage.info, "info")?)?;
+
+    let required = info.required.clone();
+    let mut stage_info = StageInfo::new(leak(info.description));
+    for name in info.required {
+        stage_info = stage_info.required(leak(name));
+    }
+    for name in info.optional {
+        stage_info = stage_info.optional(leak(name));
+    }
+    for (name, default) in info.defaults {
+        stage_info = stage_info.with_default(leak(name), default);
+    }
+
+    let callback = StageCallback {
+        stage_type: stage_type.clone(),
+        execute: stage.execute,
+        data: stage.data,
+    };
+    let factory: StageFactory = Box::new(move |json: String| {
+        let config = serde_json::from_str::<Value>(&json)?;
+        if let Some(name) = required.iter().find(|name| config.get(name).is_none()) {
+            return Err(BoxError::new(format!("Missing required field '{}'.", name)));
+        }
+        Ok(Box::new(ForeignStage {
+            callback: callback.clone(),
+            config,
+        }) as Box<dyn PipelineStage>)
+    });
+    Ok((stage_type, stage_info, factory))
+}
+
+unsafe fn scalar_udf(function: &PluginFunction) -> Result<ScalarUDF> {
+    let name = string(function.name, "name")?;
+    let return_type = data_type(&string(function.return_type, "return_type")?)?;
+    let volatility = if function.volatile {
+        Volatility::Volatile
+    } else {
+        Volatility::Immutable
+    };
+    let callback = FunctionCallback {
+        name: name.clone(),
+        invoke: function.invoke,
+        data: function.data,
+    };
+
+    let return_type: ReturnTypeFunction =
+        Arc::new(move |_: &[DataType]| Ok(Arc::new(return_type.clone())));
+    Ok(ScalarUDF::new(
+        &name,
+        &Signature::any(function.num_args, volatility),
+        &return_type,
+        &make_scalar_function(move |args: &[ArrayRef]| {
+            callback
+                .invoke(args)
+                .map_err(|error| DataFusionError::Execution(error.to_string()))
+        }),
+    ))
+}
+
+/// The data type of a primitive Arrow C data interface format
+fn data_type(format: &str) -> Result<DataType> {
+    let data_type = match format {
+        "b" => DataType::Boolean,
+        "c" => DataType::Int8,
+        "C" => DataType::UInt8,
+        "s" => DataType::Int16,
+        "S" => DataType::UInt16,
+        "i" => DataType::Int32,
+        "I" => DataType::UInt32,
+        "l" => DataType::Int64,
+        "L" => DataType::UInt64,
+        "f" => DataType::Float32,
+        "g" => DataType::Float64,
+        "z" => DataType::Binary,
+        "u" => DataType::Utf8,
+        "tdD" => DataType::Date32,
+        "tdm" => DataType::Date64,
+        "tsn:" => DataType::Timestamp(TimeUnit::Nanosecond, None),
+        _ => {
+            return Err(BoxError::new(format!(
+                "Unsupported return type '{}'. Expected one of ['b', 'c', 'C', 's', 'S', 'i', 'I', 'l', 'L', 'f', 'g', 'z', 'u', 'tdD', 'tdm', 'tsn:'].",
+                format
+            )))
+        }
+    };
+    Ok(data_type)
+}
+
+type Pointers = (*const FFI_ArrowArray, *const FFI_ArrowSchema);
+
+/// Export an array for a plugin to take
+fn export(array: &ArrayRef) -> Result<Pointers> {
+    Ok(ArrowArray::into_raw(ArrowArray::try_from(
+        array.data().clone(),
+    )?))
+}
+
+/// Empty structs for a plugin to write an array to
+unsafe fn empty() -> Pointers {
+    ArrowArray::into_raw(ArrowArray::empty())
+}
+
+/// Take the array a plugin has written
+unsafe fn import((array, schema): Pointers) -> Result<ArrayRef> {
+    let array = ArrowArray::try_from_raw(array, schema)?;
+    Ok(make_array(ArrayData::try_from(array)?))
+}
+
+/// Free structs which a plugin has taken or not written, releasing any array left in them
+unsafe fn reclaim((array, schema): Pointers) {
+    drop(ArrowArray::try_from_raw(array, schema));
+}
+
+/// The `execute` of a plugin stage with its `data`
+#[derive(Clone)]
+struct StageCallback {
+    stage_type: String,
+    execute: unsafe extern "C" fn(
+        *mut c_void,
+        *const c_char,
+        *mut FFI_ArrowArray,
+        *mut FFI_ArrowSchema,
+        *mut FFI_ArrowArray,
+        *mut FFI_ArrowSchema,
+        *mut PluginError,
+    ) -> i32,
+    data: *mut c_void,
+}
+
+// plugins must allow their data to be used from any thread
+unsafe impl Send for StageCallback {}
+unsafe impl Sync for StageCallback {}
+
+impl StageCallback {
+    fn execute(&self, config: &Value, input: Option<ArrayRef>) -> Result<Option<ArrayRef>> {
+        let config =
+            CString::new(config.to_string()).map_err(|err| BoxError::new(err.to_string()))?;
+        let input = match input {
+            Some(input) => Some(export(&input)?),
+            None => None,
+        };
+        let (input_array, input_schema) = input.unwrap_or((ptr::null(), ptr::null()));
+
+        unsafe {
+            let output = empty();
+            let mut message = None;
+            let mut error = PluginError::new(&mut message);
+            let status = (self.execute)(
+                self.data,
+                config.as_ptr(),
+                input_array as *mut FFI_ArrowArray,
+                input_schema as *mut FFI_ArrowSchema,
+                output.0 as *mut FFI_ArrowArray,
+                output.1 as *mut FFI_ArrowSchema,
+                &mut error,
+            );
+            if let Some(input) = input {
+                reclaim(input);
+            }
+            match status {
+                PLUGIN_RESULT => import(output).map(Some),
+                PLUGIN_OK => {
+                    reclaim(output);
+                    Ok(None)
+                }
+                _ => {
+                    reclaim(output);
+                    Err(failure(&self.stage_type, message))
+                }
+            }
+        }
+    }
+}
+
+/// A stage executed by a plugin
+struct ForeignStage {
+    callback: StageCallback,
+    config: Value,
+}
+
+#[async_trait]
+impl PipelineStage for ForeignStage {
+    fn to_value(&self) -> Value {
+        self.config.clone()
+    }
+
+    async fn execute(
+        &mut self,
+        _: BoxContext,
+        ctx: &mut ExecutionContext,
+    ) -> Result<Option<Arc<dyn DataFrame>>> {
+        let input = match self.config.get("inputView").and_then(Value::as_str) {
+            Some(view) => {
+                let df = ctx.table(view)?;
+                let schema: SchemaRef = df.schema().clone().into();
+                let batch = RecordBatch::concat(&schema, &df.collect().await?)?;
+                Some(Arc::new(StructArray::from(batch)) as ArrayRef)
+            }
+            None => None,
+        };
+
+        let output = match self.callback.execute(&self.config, input)? {
+            Some(output) => output,
+            None => return Ok(None),
+        };
+        let batch = match output.as_any().downcast_ref::<StructArray>() {
+            Some(output) => RecordBatch::from(output),
+            None => {
+                return Err(BoxError::new(format!(
+                    "Expected plugin stage '{}' to return a struct array. Got {}.",
+                    self.callback.stage_type,
+                    output.data_type()
+                )))
+            }
+        };
+        let table_provider = Arc::new(MemTable::try_new(batch.schema(), vec![vec![batch]])?);
+        match self.config.get("outputView").and_then(Value::as_str) {
+            Some(view) => {
+                ctx.register_table(view, table_provider)?;
+                ctx.table(view).map(Some).map_err(BoxError::from)
+            }
+            None => ctx
+                .read_table(table_provider)
+                .map(Some)
+                .map_err(BoxError::from),
+        }
+    }
+}
+
+/// The `invoke` of a plugin function with its `data`
+struct FunctionCallback {
+    name: String,
+    invoke: unsafe extern "C" fn(
+        *mut c_void,
+        *const *mut FFI_ArrowArray,
+        *const *mut FFI_ArrowSchema,
+        usize,
+        *mut FFI_ArrowArray,
+        *mut FFI_ArrowSchema,
+        *mut PluginError,
+    ) -> i32,
+    data: *mut c_void,
+}
+
+// plugins must allow their data to be used from any thread
+unsafe impl Send for FunctionCallback {}
+unsafe impl Sync for FunctionCallback {}
+
+impl FunctionCallback {
+    fn invoke(&self, args: &[ArrayRef]) -> Result<ArrayRef> {
+        let len = args.first().map_or(0, |arg| arg.len());
+        let exported = args.iter().map(export).collect::<Result<Vec<_>>>()?;
+        let arrays = exported
+            .iter()
+            .map(|(array, _)| *array as *mut FFI_ArrowArray)
+            .collect::<Vec<_>>();
+        let schemas = exported
+            .iter()
+            .map(|(_, schema)| *schema as *mut FFI_ArrowSchema)
+            .collect::<Vec<_>>();
+
+        let result = unsafe {
+            let output = empty();
+            let mut message = None;
+            let mut error = PluginError::new(&mut message);
+            let status = (self.invoke)(
+                self.data,
+                arrays.as_ptr(),
+                schemas.as_ptr(),
+                arrays.len(),
+                output.0 as *mut FFI_ArrowArray,
+                output.1 as *mut FFI_ArrowSchema,
+                &mut error,
+            );
+            for arg in exported {
+                reclaim(arg);
+            }
+            if status == PLUGIN_OK {
+                import(output)
+            } else {
+                reclaim(output);
+                Err(failure(&self.name, message))
+            }
+        }?;
+
+        if result.len() != len {
+            return Err(BoxError::new(format!(
+                "Expected plugin function '{}' to return {} rows. Got {}.",
+                self.name,
+                len,
+                result.len()
+            )));
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_data_type() -> Result<()> {
+        assert_eq!(data_type("l")?, DataType::Int64);
+        assert_eq!(
+            data_type("tsn:")?,
+            DataType::Timestamp(TimeUnit::Nanosecond, None)
+        );
+        assert!(data_type("+s").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,190 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datafusion::prelude::*;
+use serde_json::Value;
+
+use crate::api::BoxContext;
+use crate::util::*;
+
+/// Hooks invoked around the stages of a job. Errors from `before_stage` and `after_stage` fail the
+/// stage whereas errors from `on_error` and `job_end` are only reported.
+#[async_trait]
+pub trait LifecyclePlugin: Send + Sync {
+    fn to_value(&self) -> Value;
+
+    async fn before_stage(&self, _box_ctx: &BoxContext, _stage: &Value) -> Result<()> {
+        Ok(())
+    }
+
+    async fn after_stage(
+        &self,
+        _box_ctx: &BoxContext,
+        _stage: &Value,
+        _result: Option<Arc<dyn DataFrame>>,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    async fn on_error(
+        &self,
+        _box_ctx: &BoxContext,
+        _stage: &Value,
+        _error: &BoxError,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    async fn job_end(&self, _box_ctx: &BoxContext, _success: bool) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{new_execution_context, parse_lifecycle_plugins, Job};
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    /// Records each hook with the output view of its stage
+    #[derive(Default)]
+    struct Recorder {
+        calls: Mutex<Vec<String>>,
+    }
+
+    impl Recorder {
+        fn record(&self, hook: &str, stage: &Value) {
+            self.calls.lock().unwrap().push(format!(
+                "{} {}",
+                hook,
+                stage["outputView"].as_str().unwrap_or_default()
+            ));
+        }
+    }
+
+    #[async_trait]
+    impl LifecyclePlugin for Recorder {
+        fn to_value(&self) -> Value {
+            json!({"type": "Recorder"})
+        }
+
+        async fn before_stage(&self, _: &BoxContext, stage: &Value) -> Result<()> {
+            self.record("before_stage", stage);
+            Ok(())
+        }
+
+        async fn after_stage(
+            &self,
+            _: &BoxContext,
+            stage: &Value,
+            _: Option<Arc<dyn DataFrame>>,
+        ) -> Result<()> {
+            self.record("after_stage", stage);
+            Ok(())
+        }
+
+        async fn on_error(&self, _: &BoxContext, stage: &Value, _: &BoxError) -> Result<()> {
+            self.record("on_error", stage);
+            Ok(())
+        }
+
+        async fn job_end(&self, _: &BoxContext, success: bool) -> Result<()> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("job_end {}", success));
+            Ok(())
+        }
+    }
+
+    async fn run(sql: &[&str]) -> Result<Vec<String>> {
+        let recorder = Arc::new(Recorder::default());
+        let mut job = Job::new(BoxContext::new(None, None))
+            .with_show_entry_exit(false)
+            .with_lifecycle_plugin(recorder.clone());
+        for (index, sql) in sql.iter().enumerate() {
+            job = job.with_stage_config(json!({
+                "type": "SQLTransform",
+                "sql": sql,
+                "outputView": format!("view{}", index)
+            }))?;
+        }
+        let mut ctx = new_execution_context(ExecutionConfig::new());
+        let _ = job.execute(&mut ctx).await;
+        let calls = recorder.calls.lock().unwrap().clone();
+        Ok(calls)
+    }
+
+    #[tokio::test]
+    async fn test_hooks() -> Result<()> {
+        assert_eq!(
+            run(&["SELECT 1 AS one", "SELECT * FROM view0"]).await?,
+            vec![
+                "before_stage view0",
+                "after_stage view0",
+                "before_stage view1",
+                "after_stage view1",
+                "job_end true"
+            ]
+        );
+
+        // the failing stage is not followed by after_stage and later stages do not run
+        assert_eq!(
+            run(&[
+                "SELECT 1 AS one",
+                "SELECT * FROM missing",
+                "SELECT 1 AS one"
+            ])
+            .await?,
+            vec![
+                "before_stage view0",
+                "after_stage view0",
+                "before_stage view1",
+                "on_error view1",
+                "job_end false"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_lifecycle_plugins() -> Result<()> {
+        let config = json!({
+            "stages": [],
+            "plugins": {
+                "lifecycle": [
+                    {"type": "DataFramePrinter", "numRows": 5},
+                    {"type": "StatisticsWriter", "outputURI": "${STATISTICS_PATH}"}
+                ]
+            }
+        })
+        .to_string();
+        let box_ctx = BoxContext::new(
+            None,
+            Some(
+                vec![(
+                    "STATISTICS_PATH".to_string(),
+                    "/tmp/statistics.jsonl".to_string(),
+                )]
+                .into_iter()
+                .collect(),
+            ),
+        );
+        let values = parse_lifecycle_plugins(box_ctx.clone(), &config, false, false)?
+            .iter()
+            .map(|lifecycle_plugin| lifecycle_plugin.to_value())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            vec![
+                json!({"type": "DataFramePrinter", "numRows": 5}),
+                json!({"type": "StatisticsWriter", "outputURI": "/tmp/statistics.jsonl"})
+            ]
+        );
+
+        // a job of only stages has no lifecycle plugins
+        assert!(parse_lifecycle_plugins(box_ctx, "[]", false, false)?.is_empty());
+        Ok(())
+    }
+}
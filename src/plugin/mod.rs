@@ -0,0 +1,462 @@
+//! The registry of stage types and functions available to jobs.
+//!
+//! Plugins are shared libraries which register stages and functions through the C ABI
+//! described in [`ffi`], so they need not be built with the same compiler or dependencies as box.
+
+mod data_frame_printer;
+pub mod ffi;
+mod lifecycle;
+mod stage_info;
+mod statistics_writer;
+
+pub use data_frame_printer::DataFramePrinter;
+pub use ffi::{
+    PluginDeclaration, PluginError, PluginFunction, PluginHost, PluginStage, PLUGIN_ABI_VERSION,
+};
+pub use lifecycle::LifecyclePlugin;
+pub use stage_info::{FieldInfo, StageInfo};
+pub use statistics_writer::StatisticsWriter;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use datafusion::physical_plan::udaf::AggregateUDF;
+use datafusion::physical_plan::udf::ScalarUDF;
+use datafusion::prelude::*;
+use lazy_static::lazy_static;
+use libloading::Library;
+use serde_json::value::to_value;
+use serde_json::{json, Value};
+
+use crate::api::PipelineStage;
+use crate::extract::{DelimitedExtract, ParquetExtract};
+use crate::transform::{DiffTransform, SQLTransform, SlowlyChangingDimensionTransform};
+use crate::udf;
+use crate::util::serde_helpers::json_path;
+use crate::util::*;
+use crate::validate::{EqualityValidate, ExpectationsValidate, SQLValidate};
+
+/// Create a stage from its json configuration once variables have been substituted
+pub type StageFactory = Box<dyn Fn(String) -> Result<Box<dyn PipelineStage>> + Send + Sync>;
+
+/// Create a lifecycle plugin from its json configuration once variables have been substituted
+pub type LifecyclePluginFactory = fn(String) -> Result<Arc<dyn LifecyclePlugin>>;
+
+struct StageRegistration {
+    factory: StageFactory,
+
+    info: StageInfo,
+
+    /// Leave the `sql` field alone as it is substituted by the stage with its `sqlParams`
+    sql: bool,
+}
+
+#[derive(Default)]
+pub struct Registry {
+    stages: HashMap<String, StageRegistration>,
+    lifecycle_plugins: HashMap<String, LifecyclePluginFactory>,
+    udfs: Vec<ScalarUDF>,
+    udafs: Vec<AggregateUDF>,
+
+    /// Loaded plugins are never unloaded as their code backs registered stages and functions
+    libraries: Vec<Library>,
+}
+
+/// Export a plugin declaration from a `cdylib` written in Rust given the
+/// `unsafe extern "C" fn(*mut PluginHost)` which registers its stages and functions
+#[macro_export]
+macro_rules! declare_plugin {
+    ($register:path) => {
+        #[doc(hidden)]
+        #[no_mangle]
+        #[allow(non_upper_case_globals)]
+        pub static box_plugin_declaration: $crate::plugin::PluginDeclaration =
+            $crate::plugin::PluginDeclaration {
+                abi_version: $crate::plugin::PLUGIN_ABI_VERSION,
+                register: $register,
+            };
+    };
+}
+
+lazy_static! {
+    static ref REGISTRY: RwLock<Registry> = RwLock::new(Registry::with_builtins());
+}
+
+pub fn registry() -> RwLockReadGuard<'static, Registry> {
+    REGISTRY.read().unwrap()
+}
+
+pub fn registry_mut() -> RwLockWriteGuard<'static, Registry> {
+    REGISTRY.write().unwrap()
+}
+
+fn boxed<T: PipelineStage + 'static>(stage: Result<T>) -> Result<Box<dyn PipelineStage>> {
+    stage.map(|stage| Box::new(stage) as Box<dyn PipelineStage>)
+}
+
+impl Registry {
+    fn with_builtins() -> Self {
+        let mut registry = Registry::default();
+        registry.register_stage(
+            "DelimitedExtract",
+            StageInfo::new("Read delimited text files such as csv into a view")
+                .required("inputURI")
+                .required("outputView")
+                .with_default("header", true)
+                .with_default("persist", false)
+                .with_default("profile", false)
+                .required("delimiter")
+                .optional("numPartitions"),
+            |json| boxed(DelimitedExtract::try_new(json)),
+        );
+        registry.register_stage(
+            "DiffTransform",
+            StageInfo::new(
+                "Compare two views registering the rows in both and the rows only in the left or right",
+            )
+            .required("inputLeftView")
+            .required("inputRightView")
+            .with_default("inputLeftKeys", json!([]))
+            .with_default("inputRightKeys", json!([]))
+            .optional("outputIntersectionView")
+            .optional("outputLeftView")
+            .optional("outputRightView")
+            .with_default("changeFlags", false),
+            |json| boxed(DiffTransform::try_new(json)),
+        );
+        registry.register_stage(
+            "EqualityValidate",
+            StageInfo::new("Fail unless two views contain the same rows")
+                .required("leftView")
+                .required("rightView")
+                .with_default("sampleSize", 10),
+            |json| boxed(EqualityValidate::try_new(json)),
+        );
+        registry.register_stage(
+            "ExpectationsValidate",
+            StageInfo::new(
+                "Check a view against expectations such as not null, unique values or row counts",
+            )
+            .required("inputView")
+            .required("expectations"),
+            |json| boxed(ExpectationsValidate::try_new(json)),
+        );
+        registry.register_stage(
+            "ParquetExtract",
+            StageInfo::new("Read parquet files into a view")
+                .required("inputURI")
+                .required("outputView")
+                .with_default("persist", false)
+                .with_default("profile", false)
+                .optional("numPartitions"),
+            |json| boxed(ParquetExtract::try_new(json)),
+        );
+        registry.register_stage(
+            "SlowlyChangingDimensionTransform",
+            StageInfo::new("Merge a snapshot into a type 2 slowly changing dimension")
+                .required("inputView")
+                .required("dimensionView")
+                .with_default("initialLoad", false)
+                .required("keys")
+                .optional("trackedColumns")
+                .optional("effectiveTimestamp")
+                .with_default("closeDeleted", false)
+                .required("outputView"),
+            |json| boxed(SlowlyChangingDimensionTransform::try_new(json)),
+        );
+        registry.register_sql_stage(
+            "SQLTransform",
+            // exactly one of `sql` or `inputURI` is required
+            StageInfo::new("Execute a sql query registering the result as a view")
+                .optional("sql")
+                .optional("inputURI")
+                .required("outputView")
+                .with_default("profile", false)
+                .with_default("sqlParams", json!({})),
+            |json| boxed(SQLTransform::try_new(json)),
+        );
+        registry.register_sql_stage(
+            "SQLValidate",
+            StageInfo::new(
+                "Fail unless a sql query returns a single [boolean, string] row which is true",
+            )
+            .required("sql")
+            .with_default("sqlParams", json!({})),
+            |json| boxed(SQLValidate::try_new(json)),
+        );
+        registry.register_lifecycle_plugin("DataFramePrinter", |json| {
+            Ok(Arc::new(DataFramePrinter::try_new(json)?))
+        });
+        registry.register_lifecycle_plugin("StatisticsWriter", |json| {
+            Ok(Arc::new(StatisticsWriter::try_new(json)?))
+        });
+        for udf in udf::udfs() {
+            registry.register_udf(udf);
+        }
+        registry
+    }
+
+    /// Register a stage type, replacing any existing stage with the same type
+    pub fn register_stage(
+        &mut self,
+        stage_type: &str,
+        info: StageInfo,
+        factory: impl Fn(String) -> Result<Box<dyn PipelineStage>> + Send + Sync + 'static,
+    ) {
+        self.stages.insert(
+            stage_type.to_owned(),
+            StageRegistration {
+                factory: Box::new(factory),
+                info,
+                sql: false,
+            },
+        );
+    }
+
+    /// Register a stage type whose `sql` field is excluded from job variable substitution
+    pub fn register_sql_stage(
+        &mut self,
+        stage_type: &str,
+        info: StageInfo,
+        factory: impl Fn(String) -> Result<Box<dyn PipelineStage>> + Send + Sync + 'static,
+    ) {
+        self.stages.insert(
+            stage_type.to_owned(),
+            StageRegistration {
+                factory: Box::new(factory),
+                info,
+                sql: true,
+            },
+        );
+    }
+
+    /// Register a lifecycle plugin type, replacing any existing plugin with the same type
+    pub fn register_lifecycle_plugin(
+        &mut self,
+        plugin_type: &str,
+        factory: LifecyclePluginFactory,
+    ) {
+        self.lifecycle_plugins
+            .insert(plugin_type.to_owned(), factory);
+    }
+
+    pub fn register_udf(&mut self, udf: ScalarUDF) {
+        self.udfs.push(udf);
+    }
+
+    pub fn register_udaf(&mut self, udaf: AggregateUDF) {
+        self.udafs.push(udaf);
+    }
+
+    /// The registered stage types in alphabetical order
+    pub fn stage_types(&self) -> Vec<&str> {
+        sorted_keys(&self.stages)
+    }
+
+    pub fn stage_info(&self, stage_type: &str) -> Option<&StageInfo> {
+        self.stages
+            .get(stage_type)
+            .map(|registration| &registration.info)
+    }
+
+    /// Substitute the variables of a stage configuration and create the stage
+    pub fn create_stage(
+        &self,
+        object: &serde_json::Map<String, Value>,
+        params: &HashMap<String, String>,
+        allow_missing_placeholders: bool,
+        allow_missing_parameters: bool,
+    ) -> Result<Box<dyn PipelineStage>> {
+        let parse_error = |error: BoxError, path: Option<String>| BoxError::ParseError {
+            stage: Some(Value::Object(object.clone())),
+            path,
+            error: Box::new(error),
+        };
+        let stage_type = required_type(object).map_err(|error| parse_error(error, None))?;
+        let registration = self
+            .stages
+            .get(stage_type)
+            .ok_or_else(|| parse_error(unknown_type(stage_type, &self.stage_types()), None))?;
+        let json = substitute_variables(
+            object,
+            params,
+            registration.sql,
+            allow_missing_placeholders,
+            allow_missing_parameters,
+        )
+        .map_err(|error| parse_error(error, None))?;
+        (registration.factory)(json.clone()).map_err(|error| {
+            let path = match &error {
+                BoxError::SerdeError(err) => json_path(&json, err.line(), err.column()),
+                _ => None,
+            };
+            parse_error(error, path)
+        })
+    }
+
+    /// The parameters a stage configuration refers to which have no value. The `sql` of a stage
+    /// which substitutes its own `sqlParams` is excluded.
+    pub fn missing_parameters(
+        &self,
+        object: &serde_json::Map<String, Value>,
+        params: &HashMap<String, String>,
+    ) -> Vec<String> {
+        let except_sql = required_type(object)
+            .ok()
+            .and_then(|stage_type| self.stages.get(stage_type))
+            .is_some_and(|registration| registration.sql);
+        object
+            .iter()
+            .filter(|(key, _)| !(except_sql && key.as_str() == "sql"))
+            .flat_map(|(_, value)| variables::placeholders(&value.to_string()))
+            .filter(|name| !params.contains_key(name))
+            .collect()
+    }
+
+    /// Substitute the variables of a lifecycle plugin configuration and create the plugin
+    pub fn create_lifecycle_plugin(
+        &self,
+        object: &serde_json::Map<String, Value>,
+        params: &HashMap<String, String>,
+        allow_missing_placeholders: bool,
+        allow_missing_parameters: bool,
+    ) -> Result<Arc<dyn LifecyclePlugin>> {
+        let plugin_type = required_type(object)?;
+        let factory = self
+            .lifecycle_plugins
+            .get(plugin_type)
+            .ok_or_else(|| unknown_type(plugin_type, &sorted_keys(&self.lifecycle_plugins)))?;
+        let json = substitute_variables(
+            object,
+            params,
+            false,
+            allow_missing_placeholders,
+            allow_missing_parameters,
+        )?;
+        factory(json)
+    }
+
+    /// Register the functions on a context
+    pub fn register_functions(&self, ctx: &mut ExecutionContext) {
+        for udf in &self.udfs {
+            ctx.register_udf(udf.clone());
+        }
+        for udaf in &self.udafs {
+            ctx.register_udaf(udaf.clone());
+        }
+    }
+
+    /// Load a plugin after checking it implements this version of the ABI. Nothing is registered
+    /// if any of its registrations are invalid.
+    pub fn load(&mut self, path: &Path) -> Result<()> {
+        unsafe {
+            let library = Library::new(path)?;
+            let declaration =
+                &**library.get::<*const PluginDeclaration>(b"box_plugin_declaration\0")?;
+
+            if declaration.abi_version != PLUGIN_ABI_VERSION {
+                return Err(BoxError::new(format!(
+                    "Plugin '{}' has ABI version {}. Expected {}.",
+                    path.display(),
+                    declaration.abi_version,
+                    PLUGIN_ABI_VERSION
+                )));
+            }
+
+            let registrations = ffi::register(declaration).map_err(|error| {
+                BoxError::new(format!(
+                    "Plugin '{}' registration failed: {}",
+                    path.display(),
+                    error
+                ))
+            })?;
+            for (stage_type, info, factory) in registrations.stages {
+                self.stages.insert(
+                    stage_type,
+                    StageRegistration {
+                        factory,
+                        info,
+                        sql: false,
+                    },
+                );
+            }
+            for udf in registrations.functions {
+                self.register_udf(udf);
+            }
+            self.libraries.push(library);
+        }
+        Ok(())
+    }
+
+    /// Load every shared library in a directory in file name order
+    pub fn load_dir(&mut self, dir: &Path) -> Result<()> {
+        let mut paths = fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        paths.sort();
+        for path in paths {
+            if path.extension().and_then(|extension| extension.to_str())
+                == Some(std::env::consts::DLL_EXTENSION)
+            {
+                self.load(&path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn sorted_keys<T>(map: &HashMap<String, T>) -> Vec<&str> {
+    let mut keys = map.keys().map(String::as_str).collect::<Vec<_>>();
+    keys.sort_by_key(|key| key.to_lowercase());
+    keys
+}
+
+fn required_type(object: &serde_json::Map<String, Value>) -> Result<&str> {
+    object
+        .get("type")
+        .and_then(Value::as_str)
+        .ok_or_else(|| BoxError::new("Missing required field 'type'.".to_string()))
+}
+
+fn unknown_type(got: &str, expected: &[&str]) -> BoxError {
+    BoxError::new(format!(
+        "Expected field 'type' to be one of [{}]. Got '{}'.",
+        expected
+            .iter()
+            .map(|expected| format!("'{}'", expected))
+            .collect::<Vec<_>>()
+            .join(", "),
+        got
+    ))
+}
+
+/// Substitute variables into every value of the configuration, optionally excluding `sql` which
+/// is substituted by the stage itself with its `sqlParams`
+fn substitute_variables(
+    object: &serde_json::Map<String, Value>,
+    params: &HashMap<String, String>,
+    except_sql: bool,
+    allow_missing_placeholders: bool,
+    allow_missing_parameters: bool,
+) -> Result<String> {
+    let object = object
+        .iter()
+        .map(|(key, value)| {
+            if except_sql && key == "sql" {
+                return Ok((key.clone(), value.clone()));
+            }
+            let value = variables::substitute_variables(
+                Value::to_string(value),
+                params,
+                allow_missing_placeholders,
+                allow_missing_parameters,
+            )?;
+            Ok((key.clone(), Value::from_str(&value)?))
+        })
+        .collect::<Result<serde_json::Map<_, _>>>()?;
+
+    Ok(Value::to_string(&to_value(object)?))
+}
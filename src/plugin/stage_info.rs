@@ -0,0 +1,110 @@
+use std::fmt;
+
+use serde_json::Value;
+
+/// Documentation of a stage type used by notebook completion and inspection
+pub struct StageInfo {
+    pub description: &'static str,
+    pub fields: Vec<FieldInfo>,
+}
+
+pub struct FieldInfo {
+    pub name: &'static str,
+    pub required: bool,
+    pub default: Option<Value>,
+}
+
+impl StageInfo {
+    /// Describe a stage with the `type`, `id`, `name` and `description` fields every stage has.
+    /// Its other fields are added in the order they should be listed.
+    pub fn new(description: &'static str) -> Self {
+        Self {
+            description,
+            fields: vec![],
+        }
+        .required("type")
+        .optional("id")
+        .optional("name")
+        .optional("description")
+    }
+
+    pub fn required(self, name: &'static str) -> Self {
+        self.field(name, true, None)
+    }
+
+    pub fn optional(self, name: &'static str) -> Self {
+        self.field(name, false, None)
+    }
+
+    pub fn with_default(self, name: &'static str, default: impl Into<Value>) -> Self {
+        self.field(name, false, Some(default.into()))
+    }
+
+    fn field(mut self, name: &'static str, required: bool, default: Option<Value>) -> Self {
+        self.fields.push(FieldInfo {
+            name,
+            required,
+            default,
+        });
+        self
+    }
+}
+
+impl fmt::Display for StageInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.description)?;
+        writeln!(f)?;
+        writeln!(f, "Fields:")?;
+        let width = self
+            .fields
+            .iter()
+            .map(|field| field.name.len())
+            .max()
+            .unwrap_or_default();
+        for field in &self.fields {
+            let detail = match (&field.default, field.required) {
+                (_, true) => "required".to_string(),
+                (Some(default), _) => format!("default {}", default),
+                (None, _) => String::new(),
+            };
+            let line = format!("  {:width$}  {}", field.name, detail, width = width);
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_stage_info() {
+        let info = StageInfo::new("A stage")
+            .required("inputViews")
+            .with_default("batch", 1024)
+            .with_default("params", json!({}))
+            .optional("outputView");
+        assert_eq!(
+            info.fields
+                .iter()
+                .map(|field| (field.name, field.required, field.default.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("type", true, None),
+                ("id", false, None),
+                ("name", false, None),
+                ("description", false, None),
+                ("inputViews", true, None),
+                ("batch", false, Some(Value::from(1024))),
+                ("params", false, Some(json!({}))),
+                ("outputView", false, None),
+            ]
+        );
+        assert_eq!(
+            info.to_string(),
+            "A stage\n\nFields:\n  type         required\n  id\n  name\n  description\n  inputViews   required\n  batch        default 1024\n  params       default {}\n  outputView\n"
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use datafusion::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::api::BoxContext;
+use crate::plugin::LifecyclePlugin;
+use crate::util::*;
+
+/// Appends the statistics of each stage which records them to a JSON-lines file
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct StatisticsWriter {
+    #[serde(rename(serialize = "type", deserialize = "type"))]
+    _type: String,
+
+    /// A local path resolved relative to the job file
+    #[serde(rename(serialize = "outputURI", deserialize = "outputURI"))]
+    output_uri: String,
+}
+
+impl fmt::Display for StatisticsWriter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", serde_json::to_string(self).unwrap())
+    }
+}
+
+impl StatisticsWriter {
+    pub fn try_new(json: String) -> Result<StatisticsWriter> {
+        serde_json::from_str::<StatisticsWriter>(&json).map_err(BoxError::from)
+    }
+}
+
+#[async_trait]
+impl LifecyclePlugin for StatisticsWriter {
+    fn to_value(&self) -> Value {
+        serde_json::to_value(&self).unwrap()
+    }
+
+    async fn after_stage(
+        &self,
+        box_ctx: &BoxContext,
+        stage: &Value,
+        _: Option<Arc<dyn DataFrame>>,
+    ) -> Result<()> {
+        let statistics = match stage.get("statistics") {
+            Some(statistics) => statistics,
+            None => return Ok(()),
+        };
+
+        let record = json!({
+            "timestamp": Utc::now().to_rfc3339(),
+            "type": stage["type"],
+            "id": stage["id"],
+            "name": stage["name"],
+            "outputView": stage["outputView"],
+            "statistics": statistics,
+        });
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(uri::resolve(box_ctx, &self.output_uri))?;
+        writeln!(file, "{}", record)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_after_stage() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("box_{}.jsonl", uuid::Uuid::new_v4()));
+        let writer = StatisticsWriter::try_new(
+            json!({"type": "StatisticsWriter", "outputURI": path.to_str().unwrap()}).to_string(),
+        )?;
+        let box_ctx = BoxContext::new(None, None);
+
+        // a stage without statistics is not written
+        let stage = json!({"type": "SQLTransform", "outputView": "one"});
+        writer.after_stage(&box_ctx, &stage, None).await?;
+        assert!(!path.exists());
+
+        let stage = json!({
+            "type": "SQLTransform",
+            "id": "00000000-0000-0000-0000-000000000000",
+            "name": "count",
+            "outputView": "two",
+            "statistics": {"count": 3}
+        });
+        writer.after_stage(&box_ctx, &stage, None).await?;
+        writer.after_stage(&box_ctx, &stage, None).await?;
+        let contents = std::fs::read_to_string(&path)?;
+        std::fs::remove_file(&path)?;
+
+        let lines = contents.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        let mut record = serde_json::from_str::<Value>(lines[0])?;
+        assert!(record
+            .as_object_mut()
+            .unwrap()
+            .remove("timestamp")
+            .is_some());
+        assert_eq!(
+            record,
+            json!({
+                "type": "SQLTransform",
+                "id": "00000000-0000-0000-0000-000000000000",
+                "name": "count",
+                "outputView": "two",
+                "statistics": {"count": 3}
+            })
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,400 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datafusion::arrow::array::{ArrayRef, BooleanArray};
+use datafusion::arrow::compute::filter_record_batch;
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::{datasource::MemTable, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::api::*;
+use crate::util::serde_helpers::default_false;
+use crate::util::*;
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct DiffTransform {
+    #[serde(rename(serialize = "type", deserialize = "type"))]
+    _type: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+
+    #[serde(rename = "inputLeftView")]
+    input_left_view: String,
+
+    #[serde(rename = "inputRightView")]
+    input_right_view: String,
+
+    /// Columns which pair differing left and right rows for `changeFlags`. Rows are always
+    /// classified by comparing whole rows.
+    #[serde(rename = "inputLeftKeys", default)]
+    input_left_keys: Vec<String>,
+
+    #[serde(rename = "inputRightKeys", default)]
+    input_right_keys: Vec<String>,
+
+    #[serde(
+        rename = "outputIntersectionView",
+        skip_serializing_if = "Option::is_none"
+    )]
+    output_intersection_view: Option<String>,
+
+    #[serde(rename = "outputLeftView", skip_serializing_if = "Option::is_none")]
+    output_left_view: Option<String>,
+
+    #[serde(rename = "outputRightView", skip_serializing_if = "Option::is_none")]
+    output_right_view: Option<String>,
+
+    /// Add a `<column>_changed` flag per non-key column to the left and right outputs
+    #[serde(rename = "changeFlags", default = "default_false")]
+    change_flags: bool,
+
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    statistics: Option<DiffStatistics>,
+}
+
+#[derive(Serialize, Default)]
+struct DiffStatistics {
+    intersection: usize,
+    left: usize,
+    right: usize,
+}
+
+impl fmt::Display for DiffTransform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", serde_json::to_string(self).unwrap())
+    }
+}
+
+impl DiffTransform {
+    pub fn try_new(json: String) -> Result<DiffTransform> {
+        let stage = serde_json::from_str::<DiffTransform>(&json).map_err(BoxError::from)?;
+        if stage.input_left_keys.len() != stage.input_right_keys.len() {
+            return Err(BoxError::new(
+                "Expected 'inputLeftKeys' and 'inputRightKeys' to have the same length."
+                    .to_string(),
+            ));
+        }
+        if stage.output_intersection_view.is_none()
+            && stage.output_left_view.is_none()
+            && stage.output_right_view.is_none()
+        {
+            return Err(BoxError::new("Expected at least one of 'outputIntersectionView', 'outputLeftView' or 'outputRightView'.".to_string()));
+        }
+        if stage.change_flags && stage.input_left_keys.is_empty() {
+            return Err(BoxError::new(
+                "Expected 'inputLeftKeys' and 'inputRightKeys' to be set when 'changeFlags' is true."
+                    .to_string(),
+            ));
+        }
+        Ok(stage)
+    }
+}
+
+/// One side of the diff collected into a single batch
+struct Side {
+    batch: RecordBatch,
+    rows: Vec<String>,
+    keys: Vec<String>,
+}
+
+impl Side {
+    async fn try_new(ctx: &ExecutionContext, view: &str, keys: &[String]) -> Result<Self> {
+        let df = ctx.table(view)?;
+        let schema: SchemaRef = df.schema().clone().into();
+        let batch = RecordBatch::concat(&schema, &df.collect().await?)?;
+
+        let columns = (0..batch.num_columns()).collect::<Vec<_>>();
+        let rows = rows::row_keys(&batch, &columns)?;
+        let keys = if keys.is_empty() {
+            rows.clone()
+        } else {
+            let key_columns = keys
+                .iter()
+                .map(|key| schema.index_of(key))
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            rows::row_keys(&batch, &key_columns)?
+        };
+
+        Ok(Self { batch, rows, keys })
+    }
+}
+
+#[async_trait]
+impl PipelineStage for DiffTransform {
+    fn to_value(&self) -> serde_json::Value {
+        serde_json::to_value(&self).unwrap()
+    }
+
+    async fn execute(
+        &mut self,
+        _: BoxContext,
+        ctx: &mut ExecutionContext,
+    ) -> Result<Option<Arc<dyn DataFrame>>> {
+        let left = Side::try_new(ctx, &self.input_left_view, &self.input_left_keys).await?;
+        let right = Side::try_new(ctx, &self.input_right_view, &self.input_right_keys).await?;
+
+        // the schemas must match by name and type (nullability is ignored)
+        let left_signature = signature(&left.batch.schema());
+        let right_signature = signature(&right.batch.schema());
+        if left_signature != right_signature {
+            return Err(BoxError::new(format!(
+                "DiffTransform requires views with the same schema. '{}': [{}] '{}': [{}].",
+                self.input_left_view,
+                left_signature.join(", "),
+                self.input_right_view,
+                right_signature.join(", ")
+            )));
+        }
+
+        // rows are matched as a multiset: each row only matches one identical row on the other side
+        let intersection = unmatched(&left.rows, &right.rows)
+            .into_iter()
+            .map(|unmatched| !unmatched)
+            .collect::<Vec<_>>();
+        let left_only = intersection
+            .iter()
+            .map(|matched| !matched)
+            .collect::<Vec<_>>();
+        let right_only = unmatched(&right.rows, &left.rows);
+
+        self.statistics = Some(DiffStatistics {
+            intersection: intersection.iter().filter(|matched| **matched).count(),
+            left: left_only.iter().filter(|matched| **matched).count(),
+            right: right_only.iter().filter(|matched| **matched).count(),
+        });
+
+        let mut result = None;
+
+        if let Some(view) = &self.output_intersection_view {
+            let batch = filter_record_batch(&left.batch, &BooleanArray::from(intersection))?;
+            result = Some(register(ctx, view, batch)?);
+        }
+
+        if let Some(view) = &self.output_left_view {
+            let mut batch =
+                filter_record_batch(&left.batch, &BooleanArray::from(left_only.clone()))?;
+            if self.change_flags {
+                batch = with_change_flags(batch, &left, &right, &left_only, &self.input_left_keys)?;
+            }
+            let df = register(ctx, view, batch)?;
+            result = result.or(Some(df));
+        }
+
+        if let Some(view) = &self.output_right_view {
+            let mut batch =
+                filter_record_batch(&right.batch, &BooleanArray::from(right_only.clone()))?;
+            if self.change_flags {
+                batch =
+                    with_change_flags(batch, &right, &left, &right_only, &self.input_right_keys)?;
+            }
+            let df = register(ctx, view, batch)?;
+            result = result.or(Some(df));
+        }
+
+        Ok(result)
+    }
+}
+
+/// Whether each row has no identical row left on the other side once earlier rows have taken
+/// their match
+fn unmatched(rows: &[String], other_rows: &[String]) -> Vec<bool> {
+    let mut counts: HashMap<&String, usize> = HashMap::new();
+    for row in other_rows {
+        *counts.entry(row).or_default() += 1;
+    }
+    rows.iter()
+        .map(|row| match counts.get_mut(row) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                false
+            }
+            _ => true,
+        })
+        .collect()
+}
+
+fn signature(schema: &Schema) -> Vec<String> {
+    schema
+        .fields()
+        .iter()
+        .map(|field| format!("{}: {}", field.name(), field.data_type()))
+        .collect()
+}
+
+fn register(
+    ctx: &mut ExecutionContext,
+    view: &str,
+    batch: RecordBatch,
+) -> Result<Arc<dyn DataFrame>> {
+    let table_provider = MemTable::try_new(batch.schema(), vec![vec![batch]])?;
+    ctx.register_table(view, Arc::new(table_provider))?;
+    ctx.table(view).map_err(BoxError::from)
+}
+
+/// Append a nullable boolean `<column>_changed` column for each non-key column which is present on
+/// both sides. The flag is null where the key has no counterpart on the other side.
+fn with_change_flags(
+    batch: RecordBatch,
+    side: &Side,
+    other: &Side,
+    selected: &[bool],
+    keys: &[String],
+) -> Result<RecordBatch> {
+    // first occurrence of each key on the other side
+    let mut other_keys: HashMap<&String, usize> = HashMap::new();
+    for (row, key) in other.keys.iter().enumerate() {
+        other_keys.entry(key).or_insert(row);
+    }
+    let counterparts = side
+        .keys
+        .iter()
+        .zip(selected)
+        .filter(|(_, selected)| **selected)
+        .map(|(key, _)| other_keys.get(key).copied())
+        .collect::<Vec<_>>();
+
+    let side_schema = side.batch.schema();
+    let other_schema = other.batch.schema();
+    let mut fields = batch.schema().fields().clone();
+    let mut columns: Vec<ArrayRef> = batch.columns().to_vec();
+
+    for (index, field) in side_schema.fields().iter().enumerate() {
+        if keys.contains(field.name()) {
+            continue;
+        }
+        let other_index = match other_schema.index_of(field.name()) {
+            Ok(other_index) => other_index,
+            Err(_) => continue,
+        };
+
+        let values = rows::row_keys(&side.batch, &[index])?
+            .into_iter()
+            .zip(selected)
+            .filter(|(_, selected)| **selected)
+            .map(|(value, _)| value)
+            .collect::<Vec<_>>();
+        let other_values = rows::row_keys(&other.batch, &[other_index])?;
+
+        let flags = values
+            .iter()
+            .zip(&counterparts)
+            .map(|(value, counterpart)| counterpart.map(|row| value != &other_values[row]))
+            .collect::<Vec<_>>();
+
+        fields.push(Field::new(
+            &format!("{}_changed", field.name()),
+            DataType::Boolean,
+            true,
+        ));
+        columns.push(Arc::new(BooleanArray::from(flags)));
+    }
+
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).map_err(BoxError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Int32Array, StringArray};
+    use serde_json::json;
+
+    async fn view_rows(ctx: &ExecutionContext, view: &str) -> Result<Vec<String>> {
+        let mut view_rows = vec![];
+        for batch in ctx.table(view)?.collect().await? {
+            let columns = (0..batch.num_columns()).collect::<Vec<_>>();
+            view_rows.extend(rows::row_keys(&batch, &columns)?);
+        }
+        Ok(view_rows)
+    }
+
+    #[tokio::test]
+    async fn test_diff_transform() -> Result<()> {
+        let mut ctx = ExecutionContext::new();
+        register_view(
+            &mut ctx,
+            "yesterday",
+            vec![
+                ("id", Arc::new(Int32Array::from(vec![1, 2, 3, 3]))),
+                (
+                    "name",
+                    Arc::new(StringArray::from(vec!["a", "b", "c", "c"])),
+                ),
+            ],
+        )?;
+        register_view(
+            &mut ctx,
+            "today",
+            vec![
+                ("id", Arc::new(Int32Array::from(vec![1, 2, 4, 3]))),
+                (
+                    "name",
+                    Arc::new(StringArray::from(vec!["a", "B", "d", "c"])),
+                ),
+            ],
+        )?;
+
+        let mut stage = DiffTransform::try_new(
+            json!({
+                "type": "DiffTransform",
+                "inputLeftView": "yesterday",
+                "inputRightView": "today",
+                "inputLeftKeys": ["id"],
+                "inputRightKeys": ["id"],
+                "outputIntersectionView": "unchanged",
+                "outputLeftView": "removed",
+                "outputRightView": "added",
+                "changeFlags": true
+            })
+            .to_string(),
+        )?;
+        stage.execute(BoxContext::new(None, None), &mut ctx).await?;
+
+        assert_eq!(
+            view_rows(&ctx, "unchanged").await?,
+            vec![r#"["1","a"]"#, r#"["3","c"]"#]
+        );
+        // the duplicate row only matches once
+        assert_eq!(
+            view_rows(&ctx, "removed").await?,
+            vec![r#"["2","b","true"]"#, r#"["3","c","false"]"#]
+        );
+        assert_eq!(
+            view_rows(&ctx, "added").await?,
+            vec![r#"["2","B","true"]"#, r#"["4","d",null]"#]
+        );
+        assert_eq!(
+            stage.to_value()["statistics"],
+            json!({"intersection": 2, "left": 2, "right": 2})
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_try_new() {
+        let stage = |change_flags: bool| {
+            DiffTransform::try_new(
+                json!({
+                    "type": "DiffTransform",
+                    "inputLeftView": "yesterday",
+                    "inputRightView": "today",
+                    "outputLeftView": "removed",
+                    "changeFlags": change_flags
+                })
+                .to_string(),
+            )
+        };
+        assert!(stage(false).is_ok());
+        assert!(stage(true).is_err());
+    }
+}
@@ -1,3 +1,7 @@
+mod diff_transform;
+mod slowly_changing_dimension_transform;
 mod sql_transform;
 
+pub use diff_transform::DiffTransform;
+pub use slowly_changing_dimension_transform::SlowlyChangingDimensionTransform;
 pub use sql_transform::SQLTransform;
@@ -0,0 +1,419 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use datafusion::arrow::array::{
+    Array, ArrayRef, BooleanArray, StringArray, TimestampNanosecondArray, UInt32Array,
+};
+use datafusion::arrow::compute::{cast, take};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::{datasource::MemTable, prelude::*};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::api::*;
+use crate::util::serde_helpers::default_false;
+use crate::util::*;
+
+const HASH: &str = "hash";
+const VALID_FROM: &str = "valid_from";
+const VALID_TO: &str = "valid_to";
+const IS_CURRENT: &str = "is_current";
+
+/// Merges a snapshot into a type 2 slowly changing dimension
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SlowlyChangingDimensionTransform {
+    #[serde(rename(serialize = "type", deserialize = "type"))]
+    _type: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+
+    /// The new snapshot
+    #[serde(rename = "inputView")]
+    input_view: String,
+
+    /// The existing dimension
+    #[serde(rename = "dimensionView")]
+    dimension_view: String,
+
+    /// Allow the dimension view to be missing in which case all snapshot rows are inserted
+    #[serde(rename = "initialLoad", default = "default_false")]
+    initial_load: bool,
+
+    /// The business keys identifying a dimension member
+    keys: Vec<String>,
+
+    /// The attributes whose changes create a new version. Defaults to all non-key columns.
+    #[serde(rename = "trackedColumns", skip_serializing_if = "Option::is_none")]
+    tracked_columns: Option<Vec<String>>,
+
+    /// The valid_from/valid_to timestamp of the changes. Defaults to the current time.
+    #[serde(rename = "effectiveTimestamp", skip_serializing_if = "Option::is_none")]
+    effective_timestamp: Option<String>,
+
+    /// Close current members whose key is missing from the snapshot
+    #[serde(rename = "closeDeleted", default = "default_false")]
+    close_deleted: bool,
+
+    #[serde(rename = "outputView")]
+    output_view: String,
+
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    statistics: Option<MergeStatistics>,
+}
+
+#[derive(Serialize, Default)]
+struct MergeStatistics {
+    inserted: usize,
+    updated: usize,
+    closed: usize,
+    unchanged: usize,
+}
+
+impl fmt::Display for SlowlyChangingDimensionTransform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", serde_json::to_string(self).unwrap())
+    }
+}
+
+impl SlowlyChangingDimensionTransform {
+    pub fn try_new(json: String) -> Result<SlowlyChangingDimensionTransform> {
+        let stage = serde_json::from_str::<SlowlyChangingDimensionTransform>(&json)
+            .map_err(BoxError::from)?;
+        if stage.keys.is_empty() {
+            return Err(BoxError::new(
+                "Expected 'keys' to contain at least one column.".to_string(),
+            ));
+        }
+        Ok(stage)
+    }
+}
+
+#[async_trait]
+impl PipelineStage for SlowlyChangingDimensionTransform {
+    fn to_value(&self) -> serde_json::Value {
+        serde_json::to_value(&self).unwrap()
+    }
+
+    async fn execute(
+        &mut self,
+        _: BoxContext,
+        ctx: &mut ExecutionContext,
+    ) -> Result<Option<Arc<dyn DataFrame>>> {
+        let effective = match &self.effective_timestamp {
+            Some(timestamp) => parse_timestamp(timestamp)?,
+            None => Utc::now().timestamp_nanos(),
+        };
+
+        // the snapshot with the key and hash of the tracked attributes of each row
+        let snapshot = collect(ctx, &self.input_view).await?;
+        let snapshot_schema = snapshot.schema();
+        let key_columns = self
+            .keys
+            .iter()
+            .map(|key| snapshot_schema.index_of(key))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let tracked_columns = match &self.tracked_columns {
+            Some(tracked_columns) => tracked_columns
+                .iter()
+                .map(|column| snapshot_schema.index_of(column))
+                .collect::<std::result::Result<Vec<_>, _>>()?,
+            None => (0..snapshot.num_columns())
+                .filter(|column| !key_columns.contains(column))
+                .collect(),
+        };
+        let snapshot_keys = rows::row_keys(&snapshot, &key_columns)?;
+        let snapshot_hashes = rows::row_keys(&snapshot, &tracked_columns)?
+            .iter()
+            .map(|row| hex::encode(Sha256::digest(row.as_bytes())))
+            .collect::<Vec<_>>();
+
+        let mut seen = HashSet::new();
+        for key in &snapshot_keys {
+            if !seen.insert(key) {
+                return Err(BoxError::new(format!(
+                    "SlowlyChangingDimensionTransform found duplicate key {} in '{}'.",
+                    key, self.input_view
+                )));
+            }
+        }
+
+        // the dimension is the snapshot columns followed by the history columns
+        let mut fields = snapshot_schema.fields().clone();
+        fields.push(Field::new(HASH, DataType::Utf8, false));
+        fields.push(Field::new(
+            VALID_FROM,
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        ));
+        fields.push(Field::new(
+            VALID_TO,
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            true,
+        ));
+        fields.push(Field::new(IS_CURRENT, DataType::Boolean, false));
+        let schema = Arc::new(Schema::new(fields));
+
+        let dimension = match ctx.table(self.dimension_view.as_str()) {
+            Ok(_) => project(&collect(ctx, &self.dimension_view).await?, &schema)?,
+            Err(_) if self.initial_load => RecordBatch::new_empty(schema.clone()),
+            Err(err) => {
+                return Err(BoxError::new(format!(
+                    "SlowlyChangingDimensionTransform requires dimension view '{}' unless 'initialLoad' is true: {}",
+                    self.dimension_view, err
+                )))
+            }
+        };
+        let dimension_keys = rows::row_keys(
+            &dimension,
+            &self
+                .keys
+                .iter()
+                .map(|key| schema.index_of(key))
+                .collect::<std::result::Result<Vec<_>, _>>()?,
+        )?;
+        let dimension_hashes = column::<StringArray>(&dimension, HASH)?;
+        let dimension_current = column::<BooleanArray>(&dimension, IS_CURRENT)?;
+
+        let mut current: HashMap<&String, usize> = HashMap::new();
+        for (row, key) in dimension_keys.iter().enumerate() {
+            if !dimension_current.is_null(row) && dimension_current.value(row) {
+                current.insert(key, row);
+            }
+        }
+
+        let mut statistics = MergeStatistics::default();
+        let mut closed = vec![false; dimension.num_rows()];
+        let mut inserts: Vec<u32> = vec![];
+        for (row, key) in snapshot_keys.iter().enumerate() {
+            match current.get(key) {
+                None => {
+                    statistics.inserted += 1;
+                    inserts.push(row as u32);
+                }
+                Some(&dimension_row) => {
+                    if dimension_hashes.value(dimension_row) == snapshot_hashes[row] {
+                        statistics.unchanged += 1;
+                    } else {
+                        statistics.updated += 1;
+                        closed[dimension_row] = true;
+                        inserts.push(row as u32);
+                    }
+                }
+            }
+        }
+        if self.close_deleted {
+            for (key, dimension_row) in &current {
+                if !seen.contains(key) {
+                    statistics.closed += 1;
+                    closed[*dimension_row] = true;
+                }
+            }
+        }
+
+        // existing rows with the replaced and deleted members closed
+        let valid_to = column::<TimestampNanosecondArray>(&dimension, VALID_TO)?;
+        let mut existing_columns = dimension.columns().to_vec();
+        existing_columns[schema.index_of(VALID_TO)?] = Arc::new(TimestampNanosecondArray::from(
+            (0..dimension.num_rows())
+                .map(|row| {
+                    if closed[row] {
+                        Some(effective)
+                    } else if valid_to.is_null(row) {
+                        None
+                    } else {
+                        Some(valid_to.value(row))
+                    }
+                })
+                .collect::<Vec<_>>(),
+        ));
+        existing_columns[schema.index_of(IS_CURRENT)?] = Arc::new(BooleanArray::from(
+            (0..dimension.num_rows())
+                .map(|row| !closed[row] && dimension_current.value(row))
+                .collect::<Vec<_>>(),
+        ));
+        let existing = RecordBatch::try_new(schema.clone(), existing_columns)?;
+
+        // new versions of inserted and changed members
+        let indices = UInt32Array::from(inserts.clone());
+        let mut inserted_columns = snapshot
+            .columns()
+            .iter()
+            .map(|column| take(column.as_ref(), &indices, None))
+            .collect::<std::result::Result<Vec<ArrayRef>, _>>()?;
+        inserted_columns.push(Arc::new(StringArray::from(
+            inserts
+                .iter()
+                .map(|row| snapshot_hashes[*row as usize].as_str())
+                .collect::<Vec<_>>(),
+        )));
+        inserted_columns.push(Arc::new(TimestampNanosecondArray::from(vec![
+            effective;
+            inserts.len()
+        ])));
+        inserted_columns.push(Arc::new(TimestampNanosecondArray::from(vec![
+            None::<i64>;
+            inserts.len()
+        ])));
+        inserted_columns.push(Arc::new(BooleanArray::from(vec![true; inserts.len()])));
+        let inserted = RecordBatch::try_new(schema.clone(), inserted_columns)?;
+
+        self.statistics = Some(statistics);
+
+        let batch = RecordBatch::concat(&schema, &[existing, inserted])?;
+        let table_provider = MemTable::try_new(schema, vec![vec![batch]])?;
+        ctx.register_table(self.output_view.as_str(), Arc::new(table_provider))?;
+
+        ctx.table(self.output_view.as_str())
+            .map(Some)
+            .map_err(BoxError::from)
+    }
+}
+
+async fn collect(ctx: &ExecutionContext, view: &str) -> Result<RecordBatch> {
+    let df = ctx.table(view)?;
+    let schema: SchemaRef = df.schema().clone().into();
+    RecordBatch::concat(&schema, &df.collect().await?).map_err(BoxError::from)
+}
+
+/// Select and cast the columns of the existing dimension into the output schema
+fn project(batch: &RecordBatch, schema: &SchemaRef) -> Result<RecordBatch> {
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            let index = batch.schema().index_of(field.name()).map_err(|_| {
+                BoxError::new(format!(
+                    "SlowlyChangingDimensionTransform requires dimension column '{}'.",
+                    field.name()
+                ))
+            })?;
+            cast(batch.column(index), field.data_type()).map_err(BoxError::from)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    RecordBatch::try_new(schema.clone(), columns).map_err(BoxError::from)
+}
+
+fn column<'a, T: 'static>(batch: &'a RecordBatch, name: &str) -> Result<&'a T> {
+    let index = batch.schema().index_of(name)?;
+    batch
+        .column(index)
+        .as_any()
+        .downcast_ref::<T>()
+        .ok_or_else(|| BoxError::new(format!("Unexpected type for column '{}'.", name)))
+}
+
+/// Parse an RFC3339 timestamp, a 'YYYY-MM-DD HH:MM:SS' timestamp or a date as nanoseconds
+fn parse_timestamp(timestamp: &str) -> Result<i64> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(timestamp) {
+        return Ok(datetime.timestamp_nanos());
+    }
+    if let Ok(datetime) = NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S") {
+        return Ok(datetime.timestamp_nanos());
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(timestamp, "%Y-%m-%d") {
+        return Ok(date.and_hms(0, 0, 0).timestamp_nanos());
+    }
+    Err(BoxError::new(format!(
+        "Unable to parse 'effectiveTimestamp' '{}'. Expected RFC3339, 'YYYY-MM-DD HH:MM:SS' or 'YYYY-MM-DD'.",
+        timestamp
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::Int32Array;
+    use serde_json::json;
+
+    async fn merge(
+        ctx: &mut ExecutionContext,
+        dimension_view: &str,
+        effective_timestamp: &str,
+        output_view: &str,
+    ) -> Result<serde_json::Value> {
+        let mut stage = SlowlyChangingDimensionTransform::try_new(
+            json!({
+                "type": "SlowlyChangingDimensionTransform",
+                "inputView": "snapshot",
+                "dimensionView": dimension_view,
+                "initialLoad": dimension_view == "missing",
+                "keys": ["id"],
+                "effectiveTimestamp": effective_timestamp,
+                "outputView": output_view
+            })
+            .to_string(),
+        )?;
+        stage.execute(BoxContext::new(None, None), ctx).await?;
+        Ok(stage.to_value()["statistics"].clone())
+    }
+
+    /// The id, name, valid_from, valid_to and is_current of each row of the view
+    async fn history(ctx: &ExecutionContext, view: &str) -> Result<Vec<String>> {
+        let batch = collect(ctx, view).await?;
+        rows::row_keys(&batch, &[0, 1, 3, 4, 5])
+    }
+
+    #[tokio::test]
+    async fn test_merge() -> Result<()> {
+        let mut ctx = ExecutionContext::new();
+        register_view(
+            &mut ctx,
+            "snapshot",
+            vec![
+                ("id", Arc::new(Int32Array::from(vec![1, 2]))),
+                ("name", Arc::new(StringArray::from(vec!["a", "b"]))),
+            ],
+        )?;
+        assert_eq!(
+            merge(&mut ctx, "missing", "2021-01-01", "day1").await?,
+            json!({"inserted": 2, "updated": 0, "closed": 0, "unchanged": 0})
+        );
+        assert_eq!(
+            history(&ctx, "day1").await?,
+            vec![
+                r#"["1","a","2021-01-01 00:00:00",null,"true"]"#,
+                r#"["2","b","2021-01-01 00:00:00",null,"true"]"#,
+            ]
+        );
+
+        // 1 is unchanged, 2 is updated closing its current version and 3 is inserted
+        ctx.deregister_table("snapshot")?;
+        register_view(
+            &mut ctx,
+            "snapshot",
+            vec![
+                ("id", Arc::new(Int32Array::from(vec![1, 2, 3]))),
+                ("name", Arc::new(StringArray::from(vec!["a", "B", "c"]))),
+            ],
+        )?;
+        assert_eq!(
+            merge(&mut ctx, "day1", "2021-01-02", "day2").await?,
+            json!({"inserted": 1, "updated": 1, "closed": 0, "unchanged": 1})
+        );
+        assert_eq!(
+            history(&ctx, "day2").await?,
+            vec![
+                r#"["1","a","2021-01-01 00:00:00",null,"true"]"#,
+                r#"["2","b","2021-01-01 00:00:00","2021-01-02 00:00:00","false"]"#,
+                r#"["2","B","2021-01-02 00:00:00",null,"true"]"#,
+                r#"["3","c","2021-01-02 00:00:00",null,"true"]"#,
+            ]
+        );
+
+        // a missing dimension is an error unless it is the initial load
+        assert!(merge(&mut ctx, "day0", "2021-01-03", "day3").await.is_err());
+        Ok(())
+    }
+}
@@ -3,10 +3,13 @@ use std::fmt;
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use datafusion::logical_plan::LogicalPlan;
 use datafusion::{datasource::MemTable, datasource::TableProvider, prelude::*};
 use serde::{Deserialize, Serialize};
 
 use crate::api::*;
+use crate::util::serde_helpers::default_false;
+use crate::util::sql_script::{self, ScriptStatement};
 use crate::util::*;
 
 #[derive(Deserialize, Serialize)]
@@ -24,11 +27,31 @@ pub struct SQLTransform {
     #[serde(skip_serializing_if = "Option::is_none")]
     description: Option<String>,
 
-    sql: String,
+    /// The inline sql. Exactly one of 'sql' or 'inputURI' is required.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sql: Option<String>,
+
+    /// A sql file resolved relative to the job file or via a registered object store
+    #[serde(
+        rename(serialize = "inputURI", deserialize = "inputURI"),
+        skip_serializing_if = "Option::is_none"
+    )]
+    input_uri: Option<String>,
+
+    /// The sql read from 'inputURI' with its variables substituted
+    #[serde(
+        rename = "resolvedSql",
+        skip_deserializing,
+        skip_serializing_if = "Option::is_none"
+    )]
+    resolved_sql: Option<String>,
 
     #[serde(rename = "outputView")]
     output_view: String,
 
+    #[serde(default = "default_false")]
+    profile: bool,
+
     #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
     statistics: Option<Statistics>,
 
@@ -39,6 +62,13 @@ pub struct SQLTransform {
     )]
     input_views: Option<Vec<String>>,
 
+    #[serde(
+        rename = "columnLineage",
+        skip_deserializing,
+        skip_serializing_if = "Option::is_none"
+    )]
+    column_lineage: Option<Vec<lineage_visitor::ColumnLineage>>,
+
     #[serde(rename = "sqlParams", default = "default_sql_params")]
     sql_params: HashMap<String, String>,
 }
@@ -55,7 +85,13 @@ impl fmt::Display for SQLTransform {
 
 impl SQLTransform {
     pub fn try_new(json: String) -> Result<SQLTransform> {
-        serde_json::from_str::<SQLTransform>(&json).map_err(BoxError::from)
+        let stage = serde_json::from_str::<SQLTransform>(&json).map_err(BoxError::from)?;
+        if stage.sql.is_some() == stage.input_uri.is_some() {
+            return Err(BoxError::new(
+                "Expected exactly one of 'sql' or 'inputURI'.".to_string(),
+            ));
+        }
+        Ok(stage)
     }
 }
 
@@ -67,31 +103,114 @@ impl PipelineStage for SQLTransform {
 
     async fn execute(
         &mut self,
-        _: BoxContext,
+        box_ctx: BoxContext,
         ctx: &mut ExecutionContext,
     ) -> Result<Option<Arc<dyn DataFrame>>> {
-        let execution_config = ctx.state.lock().unwrap().config.clone();
+        let sql = match (&self.sql, &self.input_uri) {
+            (Some(sql), _) => sql.to_owned(),
+            (None, Some(input_uri)) => uri::read_to_string(&box_ctx, ctx, input_uri).await?,
+            (None, None) => {
+                return Err(BoxError::new(
+                    "Expected exactly one of 'sql' or 'inputURI'.".to_string(),
+                ))
+            }
+        };
 
         // substitute any variables
-        self.sql =
-            variables::substitute_variables(self.sql.to_owned(), &self.sql_params, false, false)?;
+        let sql = variables::substitute_variables(sql, &self.sql_params, false, false)?;
+        if self.input_uri.is_some() {
+            self.resolved_sql = Some(sql.to_owned());
+        } else {
+            self.sql = Some(sql.to_owned());
+        }
+
+        let mut statements = sql_script::parse_script(&sql)?;
+        let query = match statements.pop() {
+            Some(ScriptStatement::Query(query)) => query,
+            _ => {
+                return Err(BoxError::new(
+                    "Expected the final statement to be a query.".to_string(),
+                ))
+            }
+        };
+
+        // SET options and temporary views only exist for the duration of the stage so restore
+        // the configuration and any views they replaced
+        let config = ctx.state.lock().unwrap().config.clone();
+        let mut temporary_views = vec![];
+        let result = self
+            .execute_script(ctx, statements, &query, &mut temporary_views)
+            .await;
+        ctx.state.lock().unwrap().config = config;
+        for (name, previous) in temporary_views.into_iter().rev() {
+            ctx.deregister_table(name.as_str())?;
+            if let Some(previous) = previous {
+                ctx.register_table(name.as_str(), previous)?;
+            }
+        }
+        let df = result?;
 
-        // calculate the input views by traversing the plan
-        let plan = ctx.create_logical_plan(&self.sql).map_err(BoxError::from)?;
-        let mut visitor = lineage_visitor::LineageVisitor::new(false);
-        plan.accept(&mut visitor).unwrap();
-        self.input_views = Some(visitor.table_scan);
+        if self.profile {
+            self.statistics = statistics::profile(self.statistics.take(), df.clone()).await?;
+        }
 
-        // run the sql
-        let df = ctx.sql(&self.sql).await.map_err(BoxError::from)?;
+        Ok(Some(df))
+    }
+}
+
+impl SQLTransform {
+    /// Run the statements preceding the final query then register the query as the output view
+    async fn execute_script(
+        &mut self,
+        ctx: &mut ExecutionContext,
+        statements: Vec<ScriptStatement>,
+        query: &str,
+        temporary_views: &mut Vec<(String, Option<Arc<dyn TableProvider>>)>,
+    ) -> Result<Arc<dyn DataFrame>> {
+        let mut defined_views = vec![];
+        let mut input_views = vec![];
 
-        let table_provider =
-            MemTable::try_new(df.schema().clone().into(), df.collect_partitioned().await?)?;
+        for statement in statements {
+            match statement {
+                ScriptStatement::Set { key, value } => sql_script::set_option(ctx, &key, &value)?,
+                ScriptStatement::CreateView {
+                    name,
+                    temporary,
+                    query,
+                } => {
+                    let (table_provider, _) = materialize(ctx, &query, &mut input_views).await?;
+                    let previous = ctx.register_table(name.as_str(), Arc::new(table_provider))?;
+                    if temporary {
+                        temporary_views.push((name.to_owned(), previous));
+                    }
+                    defined_views.push(name);
+                }
+                ScriptStatement::With(definitions) => {
+                    for (name, query) in definitions {
+                        let (table_provider, _) =
+                            materialize(ctx, &query, &mut input_views).await?;
+                        let previous =
+                            ctx.register_table(name.as_str(), Arc::new(table_provider))?;
+                        temporary_views.push((name.to_owned(), previous));
+                        defined_views.push(name);
+                    }
+                }
+                ScriptStatement::Query(_) => {
+                    return Err(BoxError::new(
+                        "Expected only the final statement to be a query.".to_string(),
+                    ))
+                }
+            }
+        }
+
+        let (table_provider, plan) = materialize(ctx, query, &mut input_views).await?;
+        input_views.retain(|view| !defined_views.contains(view));
+        self.input_views = Some(input_views);
+        self.column_lineage = Some(lineage_visitor::column_lineage(&plan));
 
         // record statistics
-        let exec = table_provider
-            .scan(&None, execution_config.batch_size, &[], None)
-            .await?;
+        let batch_size = ctx.state.lock().unwrap().config.batch_size;
+        let exec = table_provider.scan(&None, batch_size, &[], None).await?;
         let output_partitions = Some(exec.output_partitioning().partition_count());
         self.statistics = Statistics::new(
             exec.statistics(),
@@ -100,8 +219,141 @@ impl PipelineStage for SQLTransform {
 
         ctx.register_table(self.output_view.as_str(), Arc::new(table_provider))?;
 
-        ctx.table(self.output_view.as_str())
-            .map(Some)
-            .map_err(BoxError::from)
+        ctx.table(self.output_view.as_str()).map_err(BoxError::from)
+    }
+}
+
+/// Run a query into memory, recording the views it reads
+async fn materialize(
+    ctx: &mut ExecutionContext,
+    sql: &str,
+    input_views: &mut Vec<String>,
+) -> Result<(MemTable, LogicalPlan)> {
+    // calculate the input views by traversing the plan
+    let plan = ctx.create_logical_plan(sql).map_err(BoxError::from)?;
+    let mut visitor = lineage_visitor::LineageVisitor::new(false);
+    plan.accept(&mut visitor).unwrap();
+    for view in visitor.table_scan {
+        if !input_views.contains(&view) {
+            input_views.push(view);
+        }
+    }
+
+    // run the sql
+    let df = ctx.sql(sql).await.map_err(BoxError::from)?;
+    let table_provider =
+        MemTable::try_new(df.schema().clone().into(), df.collect_partitioned().await?)?;
+
+    Ok((table_provider, plan))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::Int32Array;
+    use datafusion::arrow::util::display::array_value_to_string;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_sql_transform_script() -> Result<()> {
+        let mut ctx = ExecutionContext::new();
+        register_view(
+            &mut ctx,
+            "trips",
+            vec![("id", Arc::new(Int32Array::from(vec![1, 2, 3])))],
+        )?;
+        let batch_size = ctx.state.lock().unwrap().config.batch_size;
+
+        let sql = "-- a small batch for this stage only\nSET batch_size = 2;\n-- recent trips\nCREATE TEMP VIEW recent AS SELECT * FROM trips WHERE id > 1;\nSELECT COUNT(*) AS count FROM recent";
+        let mut stage = SQLTransform::try_new(
+            json!({"type": "SQLTransform", "sql": sql, "outputView": "out"}).to_string(),
+        )?;
+        let df = stage
+            .execute(BoxContext::new(None, None), &mut ctx)
+            .await?
+            .unwrap();
+
+        let batches = df.collect().await?;
+        assert_eq!(array_value_to_string(batches[0].column(0), 0)?, "2");
+        assert_eq!(ctx.state.lock().unwrap().config.batch_size, batch_size);
+        assert!(ctx.table("recent").is_err());
+        assert_eq!(stage.to_value()["inputViews"], json!(["trips"]));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sql_transform_profile() -> Result<()> {
+        let mut ctx = ExecutionContext::new();
+        register_view(
+            &mut ctx,
+            "trips",
+            vec![(
+                "id",
+                Arc::new(Int32Array::from(vec![Some(1), None, Some(3)])),
+            )],
+        )?;
+
+        let mut stage = SQLTransform::try_new(
+            json!({
+                "type": "SQLTransform",
+                "sql": "SELECT id FROM trips",
+                "outputView": "out",
+                "profile": true
+            })
+            .to_string(),
+        )?;
+        stage.execute(BoxContext::new(None, None), &mut ctx).await?;
+
+        let statistics = &stage.to_value()["statistics"];
+        assert_eq!(statistics["row_count"], json!(3));
+        assert_eq!(
+            statistics["columns"],
+            json!([{
+                "name": "id",
+                "data_type": "Int32",
+                "null_count": 1,
+                "distinct_count": 2,
+                "min": "1",
+                "max": "3"
+            }])
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sql_transform_input_uri() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("box_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("query.sql"), "SELECT ${value} AS answer")?;
+        let box_ctx = BoxContext::new(
+            Some(dir.join("job.json").to_string_lossy().to_string()),
+            None,
+        );
+
+        let mut ctx = ExecutionContext::new();
+        let config = json!({
+            "type": "SQLTransform",
+            "inputURI": "query.sql",
+            "outputView": "out",
+            "profile": false,
+            "sqlParams": {"value": "42"}
+        });
+        let mut stage = SQLTransform::try_new(config.to_string())?;
+        let result = stage.execute(box_ctx, &mut ctx).await;
+        std::fs::remove_dir_all(&dir)?;
+
+        let batches = result?.unwrap().collect().await?;
+        assert_eq!(array_value_to_string(batches[0].column(0), 0)?, "42");
+
+        // the sql read from the file is kept apart so the configuration still has only 'inputURI'
+        let mut value = stage.to_value();
+        assert_eq!(value["resolvedSql"], json!("SELECT 42 AS answer"));
+        assert!(value.get("sql").is_none());
+        for field in ["resolvedSql", "statistics", "inputViews", "columnLineage"] {
+            value.as_object_mut().unwrap().remove(field);
+        }
+        assert_eq!(value, config);
+        assert!(SQLTransform::try_new(value.to_string()).is_ok());
+        Ok(())
     }
 }
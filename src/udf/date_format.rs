@@ -0,0 +1,243 @@
+use std::fmt::Write;
+use std::sync::Arc;
+
+use chrono::NaiveDateTime;
+use datafusion::arrow::array::{
+    Array, ArrayRef, Date64Array, StringArray, TimestampNanosecondArray,
+};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::{DataType, TimeUnit};
+use datafusion::error::Result;
+use datafusion::physical_plan::functions::{
+    make_scalar_function, ReturnTypeFunction, Signature, Volatility,
+};
+use datafusion::physical_plan::udf::ScalarUDF;
+
+use super::*;
+
+/// `date_format(timestamp, pattern)` formats a timestamp, date or timestamp string using a Java
+/// `DateTimeFormatter` pattern such as `yyyy-MM-dd HH:mm:ss`. Timestamps are formatted as UTC so
+/// zone and offset letters such as `z` or `XXX` write the zone or offset of UTC.
+pub fn date_format() -> ScalarUDF {
+    let return_type: ReturnTypeFunction = Arc::new(|_: &[DataType]| Ok(Arc::new(DataType::Utf8)));
+    ScalarUDF::new(
+        "date_format",
+        &Signature::any(2, Volatility::Immutable),
+        &return_type,
+        &make_scalar_function(|args: &[ArrayRef]| {
+            let nanos = to_timestamp_nanos(&args[0])?;
+            let patterns = cast(&args[1], &DataType::Utf8)?;
+            let patterns = patterns.as_any().downcast_ref::<StringArray>().unwrap();
+
+            // the pattern is almost always a literal so only convert it when it changes
+            let mut converted: Option<(String, String)> = None;
+            let mut values = Vec::with_capacity(nanos.len());
+            for row in 0..nanos.len() {
+                if nanos.is_null(row) || patterns.is_null(row) {
+                    values.push(None);
+                    continue;
+                }
+                let pattern = patterns.value(row);
+                let format = match &converted {
+                    Some((java, format)) if java == pattern => format.to_owned(),
+                    _ => {
+                        let format = java_to_strftime(pattern)?;
+                        converted = Some((pattern.to_owned(), format.to_owned()));
+                        format
+                    }
+                };
+                let value = nanos.value(row);
+                let datetime = NaiveDateTime::from_timestamp(
+                    value.div_euclid(1_000_000_000),
+                    value.rem_euclid(1_000_000_000) as u32,
+                );
+                let mut formatted = String::new();
+                write!(formatted, "{}", datetime.format(&format)).map_err(|_| {
+                    execution_error(format!("Unable to format date with pattern '{}'.", pattern))
+                })?;
+                values.push(Some(formatted));
+            }
+
+            Ok(Arc::new(values.into_iter().collect::<StringArray>()) as ArrayRef)
+        }),
+    )
+}
+
+fn to_timestamp_nanos(array: &ArrayRef) -> Result<TimestampNanosecondArray> {
+    let nanos = match array.data_type() {
+        DataType::Date32 | DataType::Date64 => {
+            let dates = cast(array, &DataType::Date64)?;
+            dates
+                .as_any()
+                .downcast_ref::<Date64Array>()
+                .unwrap()
+                .iter()
+                .map(|date| date.map(|millis| millis * 1_000_000))
+                .collect::<Vec<_>>()
+        }
+        _ => {
+            let timestamps = cast(array, &DataType::Timestamp(TimeUnit::Nanosecond, None))?;
+            timestamps
+                .as_any()
+                .downcast_ref::<TimestampNanosecondArray>()
+                .unwrap()
+                .iter()
+                .collect::<Vec<_>>()
+        }
+    };
+    Ok(TimestampNanosecondArray::from(nanos))
+}
+
+/// Convert a Java date pattern to a chrono strftime format
+fn java_to_strftime(pattern: &str) -> Result<String> {
+    let chars = pattern.chars().collect::<Vec<_>>();
+    let mut format = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\'' {
+            // text within quotes is literal and a doubled quote is a quote
+            if chars.get(i + 1) == Some(&'\'') {
+                format.push('\'');
+                i += 2;
+                continue;
+            }
+            i += 1;
+            while i < chars.len() {
+                if chars[i] == '\'' {
+                    if chars.get(i + 1) == Some(&'\'') {
+                        format.push('\'');
+                        i += 2;
+                        continue;
+                    }
+                    break;
+                }
+                push_literal(&mut format, chars[i]);
+                i += 1;
+            }
+            i += 1;
+        } else if c.is_ascii_alphabetic() {
+            let mut count = 1;
+            while chars.get(i + count) == Some(&c) {
+                count += 1;
+            }
+            format.push_str(specifier(c, count)?);
+            i += count;
+        } else {
+            push_literal(&mut format, c);
+            i += 1;
+        }
+    }
+
+    Ok(format)
+}
+
+fn specifier(letter: char, count: usize) -> Result<&'static str> {
+    let specifier = match (letter, count) {
+        ('y' | 'u', 2) => "%y",
+        ('y' | 'u', _) => "%Y",
+        ('M' | 'L', 1) => "%-m",
+        ('M' | 'L', 2) => "%m",
+        ('M' | 'L', 3) => "%b",
+        ('M' | 'L', _) => "%B",
+        ('d', 1) => "%-d",
+        ('d', _) => "%d",
+        ('D', _) => "%j",
+        ('H', 1) => "%-H",
+        ('H', _) => "%H",
+        ('h', 1) => "%-I",
+        ('h', _) => "%I",
+        ('m', 1) => "%-M",
+        ('m', _) => "%M",
+        ('s', 1) => "%-S",
+        ('s', _) => "%S",
+        ('S', 3) => "%3f",
+        ('S', 6) => "%6f",
+        ('S', 9) => "%9f",
+        ('a', _) => "%p",
+        ('E', 1..=3) => "%a",
+        ('E', _) => "%A",
+        // timestamps have no time zone and are formatted as UTC
+        ('z', _) => "UTC",
+        ('Z', 4) => "GMT",
+        ('Z', 5) | ('X', _) => "Z",
+        ('Z', _) | ('x', 2 | 4) => "+0000",
+        ('x', 1) => "+00",
+        ('x', _) => "+00:00",
+        _ => {
+            return Err(execution_error(format!(
+                "Unsupported date_format pattern '{}'.",
+                letter.to_string().repeat(count)
+            )))
+        }
+    };
+    Ok(specifier)
+}
+
+fn push_literal(format: &mut String, c: char) {
+    if c == '%' {
+        format.push_str("%%");
+    } else {
+        format.push(c);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_java_to_strftime() -> Result<()> {
+        assert_eq!(
+            java_to_strftime("yyyy-MM-dd HH:mm:ss.SSS")?,
+            "%Y-%m-%d %H:%M:%S.%3f"
+        );
+        assert_eq!(
+            java_to_strftime("d MMM yy, h:mm a")?,
+            "%-d %b %y, %-I:%M %p"
+        );
+        assert_eq!(
+            java_to_strftime("EEEE 'the' D'th day' 100%")?,
+            "%A the %jth day 100%%"
+        );
+        assert_eq!(java_to_strftime("h 'o''clock'")?, "%-I o'clock");
+        assert!(java_to_strftime("Q").is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_date_format() -> crate::util::Result<()> {
+        assert_eq!(
+            execute_row(
+                "SELECT date_format('2021-03-04 05:06:07.089', 'yyyy-MM-dd HH:mm:ss.SSS'), date_format(CAST('2021-03-04' AS DATE), 'd MMM yy'), date_format(NULL, 'yyyy')"
+            )
+            .await?,
+            vec![
+                Some("2021-03-04 05:06:07.089".to_string()),
+                Some("4 Mar 21".to_string()),
+                None
+            ]
+        );
+
+        // offsets and zones are those of UTC
+        assert_eq!(
+            execute_row(
+                "SELECT date_format('2021-03-04 05:06:07', 'HH:mm:ssXXX'), date_format('2021-03-04 05:06:07', 'HH:mm z'), date_format('2021-03-04 05:06:07', 'HH:mm Z'), date_format('2021-03-04 05:06:07', 'HH:mm x'), date_format('2021-03-04 05:06:07', 'HH:mm xxx')"
+            )
+            .await?,
+            vec![
+                Some("05:06:07Z".to_string()),
+                Some("05:06 UTC".to_string()),
+                Some("05:06 +0000".to_string()),
+                Some("05:06 +00".to_string()),
+                Some("05:06 +00:00".to_string())
+            ]
+        );
+
+        assert!(execute_row("SELECT date_format('2021-03-04', 'QQ')")
+            .await
+            .is_err());
+        Ok(())
+    }
+}
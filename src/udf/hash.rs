@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+use datafusion::arrow::array::{Array, ArrayRef, BinaryArray, Int64Array, StringArray};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::DataType;
+use datafusion::physical_plan::functions::{
+    make_scalar_function, ReturnTypeFunction, Signature, Volatility,
+};
+use datafusion::physical_plan::udf::ScalarUDF;
+use sha2::{Digest, Sha224, Sha256, Sha384, Sha512};
+
+/// `sha2(expr, bitLength)` returns the hex SHA-2 digest of a string or binary value. The bit
+/// length is one of 224, 256 (or 0), 384 or 512, otherwise the result is null.
+pub fn sha2() -> ScalarUDF {
+    let return_type: ReturnTypeFunction = Arc::new(|_: &[DataType]| Ok(Arc::new(DataType::Utf8)));
+    ScalarUDF::new(
+        "sha2",
+        &Signature::any(2, Volatility::Immutable),
+        &return_type,
+        &make_scalar_function(|args: &[ArrayRef]| {
+            let strings = match args[0].data_type() {
+                DataType::Binary => None,
+                _ => Some(cast(&args[0], &DataType::Utf8)?),
+            };
+            let values: Vec<Option<&[u8]>> = match &strings {
+                Some(strings) => strings
+                    .as_any()
+                    .downcast_ref::<StringArray>()
+                    .unwrap()
+                    .iter()
+                    .map(|value| value.map(str::as_bytes))
+                    .collect(),
+                None => args[0]
+                    .as_any()
+                    .downcast_ref::<BinaryArray>()
+                    .unwrap()
+                    .iter()
+                    .collect(),
+            };
+            let bit_lengths = cast(&args[1], &DataType::Int64)?;
+            let bit_lengths = bit_lengths.as_any().downcast_ref::<Int64Array>().unwrap();
+
+            let digests = values
+                .iter()
+                .enumerate()
+                .map(|(row, value)| {
+                    let value = (*value)?;
+                    if bit_lengths.is_null(row) {
+                        return None;
+                    }
+                    match bit_lengths.value(row) {
+                        224 => Some(hex::encode(Sha224::digest(value))),
+                        0 | 256 => Some(hex::encode(Sha256::digest(value))),
+                        384 => Some(hex::encode(Sha384::digest(value))),
+                        512 => Some(hex::encode(Sha512::digest(value))),
+                        _ => None,
+                    }
+                })
+                .collect::<StringArray>();
+
+            Ok(Arc::new(digests) as ArrayRef)
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::udf::execute_row;
+
+    #[tokio::test]
+    async fn test_sha2() -> crate::util::Result<()> {
+        assert_eq!(
+            execute_row("SELECT sha2('abc', 256), sha2('abc', 0), sha2('abc', 224)").await?,
+            vec![
+                Some(
+                    "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string()
+                ),
+                Some(
+                    "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string()
+                ),
+                Some("23097d223405d8228642a477bda255b32aadbce4bda0b3f7e36c9da7".to_string()),
+            ]
+        );
+
+        // an unsupported bit length or a null input is null
+        assert_eq!(
+            execute_row("SELECT sha2('abc', 100), sha2(NULL, 256)").await?,
+            vec![None, None]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,216 @@
+use std::sync::Arc;
+
+use datafusion::arrow::array::{Array, ArrayRef, Float64Builder, ListBuilder, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::error::Result;
+use datafusion::physical_plan::functions::{make_scalar_function, Volatility};
+use datafusion::physical_plan::udf::ScalarUDF;
+use datafusion::prelude::*;
+use serde_json::Value;
+
+use super::*;
+
+/// `get_json_object(json, path)` extracts the value at a JSONPath such as `$.a.b[0]` as a
+/// string. Returns null for invalid json, invalid paths or missing values.
+pub fn get_json_object() -> ScalarUDF {
+    create_udf(
+        "get_json_object",
+        vec![DataType::Utf8, DataType::Utf8],
+        Arc::new(DataType::Utf8),
+        Volatility::Immutable,
+        make_scalar_function(|args: &[ArrayRef]| {
+            let json = downcast::<StringArray>("get_json_object", args, 0)?;
+            let path = downcast::<StringArray>("get_json_object", args, 1)?;
+
+            let array = (0..json.len())
+                .map(|row| match select_row(json, path, row)? {
+                    Value::Null => None,
+                    Value::String(value) => Some(value),
+                    value => Some(value.to_string()),
+                })
+                .collect::<StringArray>();
+            Ok(Arc::new(array) as ArrayRef)
+        }),
+    )
+}
+
+/// `get_json_double_array(json, path)` extracts the array at a JSONPath as a list of doubles.
+/// Elements which are not numbers are null.
+pub fn get_json_double_array() -> ScalarUDF {
+    create_udf(
+        "get_json_double_array",
+        vec![DataType::Utf8, DataType::Utf8],
+        Arc::new(DataType::List(Box::new(Field::new(
+            "item",
+            DataType::Float64,
+            true,
+        )))),
+        Volatility::Immutable,
+        make_scalar_function(|args: &[ArrayRef]| {
+            let json = downcast::<StringArray>("get_json_double_array", args, 0)?;
+            let path = downcast::<StringArray>("get_json_double_array", args, 1)?;
+
+            let mut builder = ListBuilder::new(Float64Builder::new(json.len()));
+            for row in 0..json.len() {
+                match select_row(json, path, row) {
+                    Some(Value::Array(values)) => {
+                        for value in values {
+                            builder.values().append_option(value.as_f64())?;
+                        }
+                        builder.append(true)?;
+                    }
+                    _ => builder.append(false)?,
+                }
+            }
+            Ok(Arc::new(builder.finish()) as ArrayRef)
+        }),
+    )
+}
+
+fn select_row(json: &StringArray, path: &StringArray, row: usize) -> Option<Value> {
+    if json.is_null(row) || path.is_null(row) {
+        return None;
+    }
+    let segments = parse_path(path.value(row))?;
+    let value = serde_json::from_str::<Value>(json.value(row)).ok()?;
+    select(&value, &segments).cloned()
+}
+
+#[derive(Debug, PartialEq)]
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// Parse the subset of JSONPath supported by Spark: `$`, `.key`, `['key']` and `[index]`
+fn parse_path(path: &str) -> Option<Vec<PathSegment>> {
+    let mut chars = path.strip_prefix('$')?.chars().peekable();
+    let mut segments = vec![];
+
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => {
+                let mut key = String::new();
+                while let Some(&next) = chars.peek() {
+                    if next == '.' || next == '[' {
+                        break;
+                    }
+                    key.push(next);
+                    chars.next();
+                }
+                if key.is_empty() {
+                    return None;
+                }
+                segments.push(PathSegment::Key(key));
+            }
+            '[' => {
+                let mut inner = String::new();
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some(next) => inner.push(next),
+                        None => return None,
+                    }
+                }
+                match inner
+                    .strip_prefix('\'')
+                    .and_then(|key| key.strip_suffix('\''))
+                {
+                    Some(key) => segments.push(PathSegment::Key(key.to_owned())),
+                    None => segments.push(PathSegment::Index(inner.trim().parse().ok()?)),
+                }
+            }
+            _ => return None,
+        }
+    }
+
+    Some(segments)
+}
+
+fn select<'a>(value: &'a Value, segments: &[PathSegment]) -> Option<&'a Value> {
+    segments
+        .iter()
+        .try_fold(value, |value, segment| match segment {
+            PathSegment::Key(key) => value.get(key),
+            PathSegment::Index(index) => value.get(index),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::udf::execute_row;
+    use datafusion::arrow::array::{Float64Array, ListArray};
+
+    #[test]
+    fn test_parse_path() {
+        assert_eq!(
+            parse_path("$.a['b c'][1]"),
+            Some(vec![
+                PathSegment::Key("a".to_string()),
+                PathSegment::Key("b c".to_string()),
+                PathSegment::Index(1)
+            ])
+        );
+        assert_eq!(parse_path("$"), Some(vec![]));
+        assert_eq!(parse_path("a.b"), None);
+        assert_eq!(parse_path("$.a[x]"), None);
+    }
+
+    #[tokio::test]
+    async fn test_get_json_object() -> crate::util::Result<()> {
+        assert_eq!(
+            execute_row(
+                r#"SELECT get_json_object('{"a": {"b": [1, "x"]}}', '$.a.b[1]'), get_json_object('{"a": {"b": [1, "x"]}}', '$.a'), get_json_object('{"a": 1}', '$.c'), get_json_object('{"a": 1}', '$.a['), get_json_object('{"a": 1', '$.a'), get_json_object(NULL, '$.a')"#
+            )
+            .await?,
+            vec![
+                Some("x".to_string()),
+                Some(r#"{"b":[1,"x"]}"#.to_string()),
+                None,
+                None,
+                None,
+                None
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_json_double_array() -> crate::util::Result<()> {
+        let batch = crate::udf::execute(
+            r#"SELECT get_json_double_array('{"a": [1, 2.5, "x"]}', '$.a'), get_json_double_array('{"a": 1}', '$.a'), get_json_double_array('{"a": [1]}', 'a'), get_json_double_array(NULL, '$.a')"#,
+        )
+        .await?;
+        let values = batch
+            .columns()
+            .iter()
+            .map(|column| {
+                let list = column.as_any().downcast_ref::<ListArray>().unwrap();
+                if list.is_null(0) {
+                    return None;
+                }
+                let values = list.value(0);
+                let values = values.as_any().downcast_ref::<Float64Array>().unwrap();
+                Some(values.iter().collect::<Vec<_>>())
+            })
+            .collect::<Vec<_>>();
+        // a value which is not an array, a malformed path or a null input is null
+        assert_eq!(
+            values,
+            vec![Some(vec![Some(1.0), Some(2.5), None]), None, None, None]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_select() {
+        let value: Value =
+            serde_json::from_str(r#"{"store": {"fruit": [{"type": "apple"}, {"type": "pear"}]}}"#)
+                .unwrap();
+        let segments = parse_path("$.store.fruit[1].type").unwrap();
+        assert_eq!(select(&value, &segments), Some(&Value::from("pear")));
+        let segments = parse_path("$.store.vegetable").unwrap();
+        assert_eq!(select(&value, &segments), None);
+    }
+}
@@ -0,0 +1,133 @@
+use std::sync::Arc;
+
+use datafusion::arrow::array::{ArrayRef, StringArray, StructArray};
+use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::physical_plan::functions::{
+    make_scalar_function, ReturnTypeFunction, Signature, TypeSignature, Volatility,
+};
+use datafusion::physical_plan::udf::ScalarUDF;
+
+use super::*;
+
+/// The largest number of fields accepted by `struct`
+const MAX_STRUCT_FIELDS: usize = 64;
+
+/// `struct(expr, ...)` combines its arguments into a struct with fields named `col1`, `col2`, ...
+pub fn struct_udf() -> ScalarUDF {
+    let return_type: ReturnTypeFunction = Arc::new(|data_types: &[DataType]| {
+        Ok(Arc::new(DataType::Struct(struct_fields(data_types))))
+    });
+    ScalarUDF::new(
+        "struct",
+        &Signature::one_of(
+            (1..=MAX_STRUCT_FIELDS).map(TypeSignature::Any).collect(),
+            Volatility::Immutable,
+        ),
+        &return_type,
+        &make_scalar_function(|args: &[ArrayRef]| {
+            let data_types = args
+                .iter()
+                .map(|arg| arg.data_type().clone())
+                .collect::<Vec<_>>();
+            let array = StructArray::from(
+                struct_fields(&data_types)
+                    .into_iter()
+                    .zip(args.iter().cloned())
+                    .collect::<Vec<_>>(),
+            );
+            Ok(Arc::new(array) as ArrayRef)
+        }),
+    )
+}
+
+fn struct_fields(data_types: &[DataType]) -> Vec<Field> {
+    data_types
+        .iter()
+        .enumerate()
+        .map(|(index, data_type)| Field::new(&format!("col{}", index + 1), data_type.clone(), true))
+        .collect()
+}
+
+/// `uuid(expr)` returns a random version 4 UUID per row. DataFusion 6 only passes the batch
+/// length to its builtin functions so a UDF without arguments cannot return a value per row and
+/// any column must be given, e.g. `uuid(id)`.
+pub fn uuid() -> ScalarUDF {
+    let return_type: ReturnTypeFunction = Arc::new(|_: &[DataType]| Ok(Arc::new(DataType::Utf8)));
+    ScalarUDF::new(
+        "uuid",
+        &Signature::any(1, Volatility::Volatile),
+        &return_type,
+        &make_scalar_function(|args: &[ArrayRef]| {
+            let array = (0..args[0].len())
+                .map(|_| Some(::uuid::Uuid::new_v4().to_string()))
+                .collect::<StringArray>();
+            Ok(Arc::new(array) as ArrayRef)
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Array, Int32Array};
+    use datafusion::prelude::*;
+    use std::collections::HashSet;
+
+    #[tokio::test]
+    async fn test_struct() -> crate::util::Result<()> {
+        let batch = crate::udf::execute("SELECT struct(1, 'a', NULL)").await?;
+        let array = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<StructArray>()
+            .unwrap();
+        assert_eq!(
+            array
+                .columns()
+                .iter()
+                .zip(array.column_names())
+                .map(|(column, name)| (name, column.data_type().clone(), column.is_null(0)))
+                .collect::<Vec<_>>(),
+            vec![
+                ("col1", DataType::Int64, false),
+                ("col2", DataType::Utf8, false),
+                ("col3", DataType::Utf8, true)
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_uuid() -> crate::util::Result<()> {
+        let mut ctx = ExecutionContext::new();
+        ctx.register_udf(uuid());
+        crate::util::register_view(
+            &mut ctx,
+            "trips",
+            vec![("id", Arc::new(Int32Array::from(vec![1, 2, 3])))],
+        )?;
+
+        let batches = ctx
+            .sql("SELECT uuid(id) FROM trips")
+            .await?
+            .collect()
+            .await?;
+        let values = batches[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        let distinct = (0..values.len())
+            .map(|index| values.value(index))
+            .collect::<HashSet<_>>();
+        assert_eq!(distinct.len(), 3);
+
+        // rejected when planned or executed depending on when the signature is checked
+        let result = match ctx.sql("SELECT uuid() FROM trips").await {
+            Ok(df) => df.collect().await.map(|_| ()),
+            Err(err) => Err(err),
+        };
+        assert!(result.is_err());
+        Ok(())
+    }
+}
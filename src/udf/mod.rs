@@ -0,0 +1,78 @@
+//! Spark SQL functions used by Arc jobs which DataFusion does not provide.
+//!
+//! Only scalar functions are provided as DataFusion covers the aggregates Arc jobs use.
+//! `random()` is not included as it is a DataFusion builtin. `explode` cannot be provided as
+//! DataFusion 6 has no table generating functions and `uuid` requires an argument as a UDF
+//! without arguments is not given the number of rows.
+
+mod date_format;
+mod hash;
+mod json;
+mod misc;
+mod regexp_extract;
+
+use datafusion::arrow::array::ArrayRef;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::physical_plan::udf::ScalarUDF;
+
+/// All the builtin functions
+pub fn udfs() -> Vec<ScalarUDF> {
+    vec![
+        json::get_json_object(),
+        json::get_json_double_array(),
+        date_format::date_format(),
+        regexp_extract::regexp_extract(),
+        hash::sha2(),
+        misc::struct_udf(),
+        misc::uuid(),
+    ]
+}
+
+/// Downcast a function argument to a concrete array type
+fn downcast<'a, T: 'static>(name: &str, args: &'a [ArrayRef], index: usize) -> Result<&'a T> {
+    args.get(index)
+        .and_then(|arg| arg.as_any().downcast_ref::<T>())
+        .ok_or_else(|| {
+            DataFusionError::Execution(format!(
+                "Unexpected type for argument {} of '{}'.",
+                index + 1,
+                name
+            ))
+        })
+}
+
+fn execution_error(message: String) -> DataFusionError {
+    DataFusionError::Execution(message)
+}
+
+/// Execute a query with the builtin functions registered returning the first row as strings
+#[cfg(test)]
+async fn execute_row(sql: &str) -> crate::util::Result<Vec<Option<String>>> {
+    use datafusion::arrow::array::Array;
+
+    let batch = execute(sql).await?;
+    batch
+        .columns()
+        .iter()
+        .map(|column| {
+            if column.is_null(0) {
+                Ok(None)
+            } else {
+                Ok(Some(
+                    datafusion::arrow::util::display::array_value_to_string(column, 0)?,
+                ))
+            }
+        })
+        .collect()
+}
+
+/// Execute a query with the builtin functions registered returning the first batch
+#[cfg(test)]
+async fn execute(sql: &str) -> crate::util::Result<datafusion::arrow::record_batch::RecordBatch> {
+    let mut ctx = datafusion::prelude::ExecutionContext::new();
+    for udf in udfs() {
+        ctx.register_udf(udf);
+    }
+    let mut batches = ctx.sql(sql).await?.collect().await?;
+    Ok(batches.remove(0))
+}
@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use datafusion::arrow::array::{Array, ArrayRef, Int64Array, StringArray};
+use datafusion::arrow::datatypes::DataType;
+use datafusion::physical_plan::functions::{
+    make_scalar_function, ReturnTypeFunction, Signature, TypeSignature, Volatility,
+};
+use datafusion::physical_plan::udf::ScalarUDF;
+use regex::Regex;
+
+use super::*;
+
+/// `regexp_extract(str, regexp[, idx])` returns the group `idx` (default 1) of the first match or
+/// an empty string if there is no match
+pub fn regexp_extract() -> ScalarUDF {
+    let return_type: ReturnTypeFunction = Arc::new(|_: &[DataType]| Ok(Arc::new(DataType::Utf8)));
+    ScalarUDF::new(
+        "regexp_extract",
+        &Signature::one_of(
+            vec![
+                TypeSignature::Exact(vec![DataType::Utf8, DataType::Utf8]),
+                TypeSignature::Exact(vec![DataType::Utf8, DataType::Utf8, DataType::Int64]),
+            ],
+            Volatility::Immutable,
+        ),
+        &return_type,
+        &make_scalar_function(|args: &[ArrayRef]| {
+            let values = downcast::<StringArray>("regexp_extract", args, 0)?;
+            let patterns = downcast::<StringArray>("regexp_extract", args, 1)?;
+            let indexes = match args.len() {
+                3 => Some(downcast::<Int64Array>("regexp_extract", args, 2)?),
+                _ => None,
+            };
+
+            // the pattern is almost always a literal so only compile it when it changes
+            let mut compiled: Option<(String, Regex)> = None;
+            let mut extracted = Vec::with_capacity(values.len());
+            for row in 0..values.len() {
+                if values.is_null(row)
+                    || patterns.is_null(row)
+                    || indexes.is_some_and(|indexes| indexes.is_null(row))
+                {
+                    extracted.push(None);
+                    continue;
+                }
+                let pattern = patterns.value(row);
+                if compiled
+                    .as_ref()
+                    .map_or(true, |(source, _)| source != pattern)
+                {
+                    let regex = Regex::new(pattern).map_err(|err| {
+                        execution_error(format!(
+                            "Invalid regexp_extract pattern '{}': {}",
+                            pattern, err
+                        ))
+                    })?;
+                    compiled = Some((pattern.to_owned(), regex));
+                }
+                let regex = &compiled.as_ref().unwrap().1;
+
+                let index = indexes.map_or(1, |indexes| indexes.value(row));
+                if index < 0 || index as usize >= regex.captures_len() {
+                    return Err(execution_error(format!(
+                        "regexp_extract group index {} is out of range for pattern '{}'.",
+                        index, pattern
+                    )));
+                }
+                let value = regex
+                    .captures(values.value(row))
+                    .and_then(|captures| captures.get(index as usize))
+                    .map_or("", |group| group.as_str());
+                extracted.push(Some(value.to_owned()));
+            }
+
+            Ok(Arc::new(extracted.into_iter().collect::<StringArray>()) as ArrayRef)
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::udf::execute_row;
+
+    #[tokio::test]
+    async fn test_regexp_extract() -> crate::util::Result<()> {
+        assert_eq!(
+            execute_row(
+                "SELECT regexp_extract('100-200', '([0-9]+)-([0-9]+)'), regexp_extract('100-200', '([0-9]+)-([0-9]+)', 2), regexp_extract('100-200', '([0-9]+)-([0-9]+)', 0), regexp_extract('abc', '([0-9]+)'), regexp_extract(NULL, '([0-9]+)')"
+            )
+            .await?,
+            vec![
+                Some("100".to_string()),
+                Some("200".to_string()),
+                Some("100-200".to_string()),
+                Some("".to_string()),
+                None
+            ]
+        );
+
+        let err = execute_row("SELECT regexp_extract('100-200', '([0-9]+)-([0-9]+)', 3)")
+            .await
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("regexp_extract group index 3 is out of range"));
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
 use crate::jupyter::JupyterMessage;
 use datafusion::arrow::error::ArrowError;
 use datafusion::error::DataFusionError;
-use serde_json::Error;
+use serde_json::{Error, Value};
 use std::error;
 use std::fmt::{Display, Formatter};
 use std::result;
@@ -42,10 +42,29 @@ pub enum BoxError {
 
     /// Mpsc errors
     MpscRecvError(std::sync::mpsc::RecvError),
-    MpscSendError(std::sync::mpsc::SendError<JupyterMessage>),
+    MpscSendError(String),
 
     /// Utf8 errors
     Utf8Error(std::str::Utf8Error),
+
+    /// Http errors
+    HttpError(reqwest::Error),
+
+    /// Plugin loading errors
+    PluginError(libloading::Error),
+
+    /// Errors creating a stage with the stage and the path of the field in error, if known
+    ParseError {
+        stage: Option<Value>,
+        path: Option<String>,
+        error: Box<BoxError>,
+    },
+
+    /// Errors executing a stage with the stage
+    ExecutionError {
+        stage: Value,
+        error: Box<BoxError>,
+    },
 }
 
 impl BoxError {
@@ -109,7 +128,7 @@ impl From<std::sync::mpsc::RecvError> for BoxError {
 
 impl From<std::sync::mpsc::SendError<JupyterMessage>> for BoxError {
     fn from(e: std::sync::mpsc::SendError<JupyterMessage>) -> Self {
-        BoxError::MpscSendError(e)
+        BoxError::MpscSendError(e.to_string())
     }
 }
 
@@ -119,6 +138,18 @@ impl From<std::str::Utf8Error> for BoxError {
     }
 }
 
+impl From<reqwest::Error> for BoxError {
+    fn from(e: reqwest::Error) -> Self {
+        BoxError::HttpError(e)
+    }
+}
+
+impl From<libloading::Error> for BoxError {
+    fn from(e: libloading::Error) -> Self {
+        BoxError::PluginError(e)
+    }
+}
+
 impl Display for BoxError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match *self {
@@ -134,8 +165,22 @@ impl Display for BoxError {
             BoxError::MpscRecvError(ref desc) => write!(f, "{}", desc),
             BoxError::MpscSendError(ref desc) => write!(f, "{}", desc),
             BoxError::Utf8Error(ref desc) => write!(f, "{}", desc),
+            BoxError::HttpError(ref desc) => write!(f, "{}", desc),
+            BoxError::PluginError(ref desc) => write!(f, "{}", desc),
+            BoxError::ParseError { ref error, .. } => write!(f, "{}", error),
+            BoxError::ExecutionError { ref error, .. } => write!(f, "{}", error),
         }
     }
 }
 
-impl error::Error for BoxError {}
+impl error::Error for BoxError {
+    /// The error a stage failed with, so the stage context can be looked through
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            BoxError::ParseError { error, .. } | BoxError::ExecutionError { error, .. } => {
+                Some(error.as_ref())
+            }
+            _ => None,
+        }
+    }
+}
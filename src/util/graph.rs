@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::util::*;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum NodeKind {
+    Stage,
+    View,
+    Dataset,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Node {
+    pub id: String,
+    pub kind: NodeKind,
+    pub label: String,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+}
+
+/// The dependency graph between the stages of a job and the views and datasets they read and write
+#[derive(Serialize, Debug, Default)]
+pub struct JobGraph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+
+    #[serde(skip)]
+    ids: HashMap<String, String>,
+}
+
+impl JobGraph {
+    /// Build the graph from the serialized stages without executing them. The sql of a
+    /// `SQLTransform` which reads it from `inputURI` must already be set as `sql`.
+    pub fn try_new(stages: &[Value]) -> Result<Self> {
+        let mut graph = JobGraph::default();
+
+        for (index, stage) in stages.iter().enumerate() {
+            let stage_id = format!("stage{}", index);
+            let label = match (stage["type"].as_str(), stage["name"].as_str()) {
+                (Some(_type), Some(name)) => format!("{}: {} ({})", index, _type, name),
+                (Some(_type), None) => format!("{}: {}", index, _type),
+                _ => index.to_string(),
+            };
+            graph.nodes.push(Node {
+                id: stage_id.to_owned(),
+                kind: NodeKind::Stage,
+                label,
+            });
+
+            if let Some(input_uri) = input_dataset(stage) {
+                let id = graph.node(NodeKind::Dataset, input_uri);
+                graph.edge(id, stage_id.to_owned());
+            }
+
+            let mut input_views: Vec<String> = vec![];
+            let mut output_views: Vec<String> = vec![];
+            if let Value::Object(object) = stage {
+                for (key, value) in object {
+                    if !key.ends_with("View") {
+                        continue;
+                    }
+                    if let Some(view) = value.as_str() {
+                        if key.starts_with("output") {
+                            output_views.push(view.to_owned());
+                        } else {
+                            input_views.push(view.to_owned());
+                        }
+                    }
+                }
+            }
+
+            if let Some(views) = stage["inputViews"].as_array() {
+                input_views.extend(
+                    views
+                        .iter()
+                        .filter_map(|view| view.as_str().map(String::from)),
+                );
+            }
+
+            if let Some(sql) = stage["sql"].as_str() {
+                input_views.extend(sql_references(sql, &stage["sqlParams"])?);
+            }
+
+            for view in input_views {
+                let id = graph.node(NodeKind::View, &view);
+                graph.edge(id, stage_id.to_owned());
+            }
+            for view in output_views {
+                let id = graph.node(NodeKind::View, &view);
+                graph.edge(stage_id.to_owned(), id);
+            }
+        }
+
+        Ok(graph)
+    }
+
+    /// Find or create the node for a view or dataset
+    fn node(&mut self, kind: NodeKind, label: &str) -> String {
+        let key = format!("{:?}:{}", kind, label);
+        if let Some(id) = self.ids.get(&key) {
+            return id.to_owned();
+        }
+
+        let prefix = match kind {
+            NodeKind::Stage => "stage",
+            NodeKind::View => "view",
+            NodeKind::Dataset => "dataset",
+        };
+        let id = format!("{}{}", prefix, self.ids.len());
+        self.ids.insert(key, id.to_owned());
+        self.nodes.push(Node {
+            id: id.to_owned(),
+            kind,
+            label: label.to_owned(),
+        });
+        id
+    }
+
+    fn edge(&mut self, from: String, to: String) {
+        let edge = Edge { from, to };
+        if !self.edges.contains(&edge) {
+            self.edges.push(edge);
+        }
+    }
+
+    /// Render as Graphviz DOT
+    pub fn to_dot(&self) -> String {
+        let mut dot = "digraph job {\n  rankdir=LR;\n".to_string();
+        for node in &self.nodes {
+            let shape = match node.kind {
+                NodeKind::Stage => "box",
+                NodeKind::View => "ellipse",
+                NodeKind::Dataset => "cylinder",
+            };
+            dot.push_str(&format!(
+                "  {} [label=\"{}\", shape={}];\n",
+                node.id,
+                node.label.replace('\\', "\\\\").replace('"', "\\\""),
+                shape
+            ));
+        }
+        for edge in &self.edges {
+            dot.push_str(&format!("  {} -> {};\n", edge.from, edge.to));
+        }
+        dot.push('}');
+        dot
+    }
+
+    /// Render as a Mermaid flowchart
+    pub fn to_mermaid(&self) -> String {
+        let mut mermaid = "flowchart LR\n".to_string();
+        for node in &self.nodes {
+            let label = node.label.replace('"', "#quot;");
+            let node = match node.kind {
+                NodeKind::Stage => format!("{}[\"{}\"]", node.id, label),
+                NodeKind::View => format!("{}([\"{}\"])", node.id, label),
+                NodeKind::Dataset => format!("{}[(\"{}\")]", node.id, label),
+            };
+            mermaid.push_str(&format!("  {}\n", node));
+        }
+        for edge in &self.edges {
+            mermaid.push_str(&format!("  {} --> {}\n", edge.from, edge.to));
+        }
+        mermaid
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(BoxError::from)
+    }
+}
+
+/// Find the views referenced by the sql after applying any sqlParams
+fn sql_references(sql: &str, sql_params: &Value) -> Result<Vec<String>> {
+    let params = match sql_params {
+        Value::Object(object) => object
+            .iter()
+            .filter_map(|(key, value)| {
+                value
+                    .as_str()
+                    .map(|value| (key.to_owned(), value.to_owned()))
+            })
+            .collect::<HashMap<_, _>>(),
+        _ => HashMap::new(),
+    };
+    let sql = variables::substitute_variables(sql.to_owned(), &params, true, true)?;
+    lineage_visitor::sql_table_references(&sql)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stages() -> Vec<Value> {
+        serde_json::from_str(
+            r#"[
+                {"type": "ParquetExtract", "inputURI": "/data/orders", "outputView": "orders"},
+                {"type": "ParquetExtract", "inputURI": "/data/lineitem", "outputView": "lineitem"},
+                {
+                    "type": "SQLTransform",
+                    "name": "join",
+                    "sql": "WITH o AS (SELECT * FROM ${table}) SELECT * FROM lineitem JOIN o ON l_orderkey = o_orderkey",
+                    "sqlParams": {"table": "orders"},
+                    "outputView": "out"
+                }
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_job_graph() -> Result<()> {
+        let graph = JobGraph::try_new(&stages())?;
+
+        let labels = graph
+            .nodes
+            .iter()
+            .map(|node| node.label.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            labels,
+            vec![
+                "0: ParquetExtract",
+                "/data/orders",
+                "orders",
+                "1: ParquetExtract",
+                "/data/lineitem",
+                "lineitem",
+                "2: SQLTransform (join)",
+                "out",
+            ]
+        );
+
+        let edges = graph
+            .edges
+            .iter()
+            .map(|edge| format!("{}->{}", edge.from, edge.to))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            edges,
+            vec![
+                "dataset0->stage0",
+                "stage0->view1",
+                "dataset2->stage1",
+                "stage1->view3",
+                "view1->stage2",
+                "view3->stage2",
+                "stage2->view4",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_render() -> Result<()> {
+        let graph = JobGraph::try_new(&stages()[0..1])?;
+
+        assert_eq!(
+            graph.to_dot(),
+            "digraph job {\n  rankdir=LR;\n  stage0 [label=\"0: ParquetExtract\", shape=box];\n  dataset0 [label=\"/data/orders\", shape=cylinder];\n  view1 [label=\"orders\", shape=ellipse];\n  dataset0 -> stage0;\n  stage0 -> view1;\n}"
+        );
+        assert_eq!(
+            graph.to_mermaid(),
+            "flowchart LR\n  stage0[\"0: ParquetExtract\"]\n  dataset0[(\"/data/orders\")]\n  view1([\"orders\"])\n  dataset0 --> stage0\n  stage0 --> view1\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sql_transform_input_uri() -> Result<()> {
+        // the inputURI of a SQLTransform is its sql file so the edges come from the sql
+        let stages: Vec<Value> = serde_json::from_str(
+            r#"[
+                {"type": "ParquetExtract", "inputURI": "/data/orders", "outputView": "orders"},
+                {"type": "SQLTransform", "inputURI": "/sql/orders.sql", "sql": "SELECT * FROM orders", "outputView": "out"}
+            ]"#,
+        )
+        .unwrap();
+        let graph = JobGraph::try_new(&stages)?;
+
+        let labels = graph
+            .nodes
+            .iter()
+            .map(|node| node.label.as_str())
+            .collect::<Vec<_>>();
+        assert!(!labels.contains(&"/sql/orders.sql"));
+
+        let edges = graph
+            .edges
+            .iter()
+            .map(|edge| format!("{}->{}", edge.from, edge.to))
+            .collect::<Vec<_>>();
+        assert!(edges.contains(&"view1->stage1".to_string()));
+
+        Ok(())
+    }
+}
@@ -1,6 +1,18 @@
-use datafusion::logical_plan::{LogicalPlan, PlanVisitor};
+use std::collections::{BTreeSet, HashSet};
 use std::fmt;
 
+use datafusion::logical_plan::{Column, Expr, LogicalPlan, PlanVisitor};
+use datafusion::optimizer::utils::expr_to_columns;
+use serde::Serialize;
+use sqlparser::ast::{
+    Expr as SqlExpr, Query, SelectItem, SetExpr, Statement, TableFactor, TableWithJoins,
+};
+use sqlparser::dialect::GenericDialect;
+use sqlparser::parser::Parser;
+
+use crate::util::sql_script::ScriptStatement;
+use crate::util::*;
+
 pub struct LineageVisitor {
     print: bool,
 
@@ -35,3 +47,372 @@ impl PlanVisitor for LineageVisitor {
         Ok(true)
     }
 }
+
+/// A column of an input view
+#[derive(Serialize, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SourceColumn {
+    pub view: String,
+    pub column: String,
+}
+
+/// The input view columns an output column derives from and the expressions applied to them
+#[derive(Serialize, Clone)]
+pub struct ColumnLineage {
+    pub name: String,
+
+    pub sources: Vec<SourceColumn>,
+
+    /// Non-trivial expressions ordered from the output column towards the sources
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub expressions: Vec<String>,
+
+    /// Set when the column passes through a plan node which is not traced so some sources may
+    /// be missing
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub partial: bool,
+}
+
+#[derive(Default)]
+struct Lineage {
+    sources: BTreeSet<SourceColumn>,
+    expressions: Vec<String>,
+    partial: bool,
+}
+
+/// Calculate the lineage of every output column of the plan
+pub fn column_lineage(plan: &LogicalPlan) -> Vec<ColumnLineage> {
+    plan.schema()
+        .fields()
+        .iter()
+        .enumerate()
+        .map(|(index, field)| {
+            let mut lineage = Lineage::default();
+            trace_column(plan, index, &mut lineage);
+            ColumnLineage {
+                name: field.name().to_owned(),
+                sources: lineage.sources.into_iter().collect(),
+                expressions: lineage.expressions,
+                partial: lineage.partial,
+            }
+        })
+        .collect()
+}
+
+/// Follow the column at `index` of the plan output schema down to the table scans
+fn trace_column(plan: &LogicalPlan, index: usize, lineage: &mut Lineage) {
+    match plan {
+        LogicalPlan::TableScan { table_name, .. } => {
+            let field = plan.schema().field(index);
+            lineage.sources.insert(SourceColumn {
+                view: table_name.to_owned(),
+                column: field.name().to_owned(),
+            });
+        }
+        LogicalPlan::Projection { expr, input, .. } => {
+            if let Some(expr) = expr.get(index) {
+                trace_expr(expr, input, lineage);
+            }
+        }
+        LogicalPlan::Aggregate {
+            group_expr,
+            aggr_expr,
+            input,
+            ..
+        } => {
+            if let Some(expr) = group_expr.iter().chain(aggr_expr.iter()).nth(index) {
+                trace_expr(expr, input, lineage);
+            }
+        }
+        LogicalPlan::Window {
+            window_expr, input, ..
+        } => {
+            let input_len = input.schema().fields().len();
+            if index < input_len {
+                trace_column(input, index, lineage);
+            } else if let Some(expr) = window_expr.get(index - input_len) {
+                trace_expr(expr, input, lineage);
+            }
+        }
+        LogicalPlan::Join { left, right, .. } | LogicalPlan::CrossJoin { left, right, .. } => {
+            let left_len = left.schema().fields().len();
+            if index < left_len {
+                trace_column(left, index, lineage);
+            } else {
+                trace_column(right, index - left_len, lineage);
+            }
+        }
+        LogicalPlan::Union { inputs, .. } => {
+            for input in inputs {
+                trace_column(input, index, lineage);
+            }
+        }
+        LogicalPlan::Filter { input, .. }
+        | LogicalPlan::Sort { input, .. }
+        | LogicalPlan::Limit { input, .. }
+        | LogicalPlan::Repartition { input, .. } => trace_column(input, index, lineage),
+        // literal rows have no sources
+        LogicalPlan::EmptyRelation { .. } | LogicalPlan::Values { .. } => {}
+        _ => lineage.partial = true,
+    }
+}
+
+/// Record the expression and follow every column it references into the input plan
+fn trace_expr(expr: &Expr, input: &LogicalPlan, lineage: &mut Lineage) {
+    let expr = match expr {
+        Expr::Alias(expr, _) => expr.as_ref(),
+        expr => expr,
+    };
+    if !matches!(expr, Expr::Column(_)) {
+        // the name datafusion gives the expression's output column e.g. `SUM(trips.fare)`
+        match expr.name(input.schema()) {
+            Ok(name) => lineage.expressions.push(name),
+            Err(_) => lineage.partial = true,
+        }
+    }
+
+    let mut columns: HashSet<Column> = HashSet::new();
+    if expr_to_columns(expr, &mut columns).is_ok() {
+        // sort so the recorded expressions are deterministic
+        let mut columns = columns.into_iter().collect::<Vec<_>>();
+        columns.sort_by_key(|column| column.flat_name());
+        for column in columns {
+            if let Ok(index) = input.schema().index_of_column(&column) {
+                trace_column(input, index, lineage);
+            }
+        }
+    }
+}
+
+/// Find the tables referenced by a SQL script without planning it, which allows the
+/// references to be found before any of the views are registered
+pub fn sql_table_references(sql: &str) -> Result<Vec<String>> {
+    let mut references = SqlReferences::default();
+    for statement in sql_script::parse_script(sql)? {
+        match statement {
+            ScriptStatement::Set { .. } => {}
+            ScriptStatement::CreateView { name, query, .. } => {
+                references.visit_sql(&query)?;
+                references.views.insert(name);
+            }
+            ScriptStatement::With(definitions) => {
+                for (name, query) in definitions {
+                    references.visit_sql(&query)?;
+                    references.views.insert(name);
+                }
+            }
+            ScriptStatement::Query(query) => references.visit_sql(&query)?,
+        }
+    }
+    Ok(references.tables)
+}
+
+#[derive(Default)]
+struct SqlReferences {
+    tables: Vec<String>,
+
+    /// Views defined by earlier statements of the script
+    views: HashSet<String>,
+
+    /// The names of the common table expressions in scope of each enclosing query
+    ctes: Vec<HashSet<String>>,
+}
+
+impl SqlReferences {
+    fn visit_sql(&mut self, sql: &str) -> Result<()> {
+        let statements = Parser::parse_sql(&GenericDialect {}, sql)
+            .map_err(|err| BoxError::new(format!("Failed to parse SQL: {}", err)))?;
+        for statement in &statements {
+            if let Statement::Query(query) = statement {
+                self.visit_query(query);
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_query(&mut self, query: &Query) {
+        // a common table expression is visible to the later ones and the body of its query
+        self.ctes.push(HashSet::new());
+        if let Some(with) = &query.with {
+            for cte in &with.cte_tables {
+                self.visit_query(&cte.query);
+                if let Some(scope) = self.ctes.last_mut() {
+                    scope.insert(cte.alias.name.value.to_owned());
+                }
+            }
+        }
+        self.visit_set_expr(&query.body);
+        self.ctes.pop();
+    }
+
+    fn visit_set_expr(&mut self, set_expr: &SetExpr) {
+        match set_expr {
+            SetExpr::Select(select) => {
+                for table_with_joins in &select.from {
+                    self.visit_table_with_joins(table_with_joins);
+                }
+                for item in &select.projection {
+                    match item {
+                        SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => {
+                            self.visit_expr(expr)
+                        }
+                        _ => {}
+                    }
+                }
+                for expr in select.selection.iter().chain(select.having.iter()) {
+                    self.visit_expr(expr);
+                }
+            }
+            SetExpr::Query(query) => self.visit_query(query),
+            SetExpr::SetOperation { left, right, .. } => {
+                self.visit_set_expr(left);
+                self.visit_set_expr(right);
+            }
+            _ => {}
+        }
+    }
+
+    /// Follow the subqueries of an expression
+    fn visit_expr(&mut self, expr: &SqlExpr) {
+        match expr {
+            SqlExpr::Subquery(query) | SqlExpr::Exists(query) => self.visit_query(query),
+            SqlExpr::InSubquery { expr, subquery, .. } => {
+                self.visit_expr(expr);
+                self.visit_query(subquery);
+            }
+            SqlExpr::BinaryOp { left, right, .. } => {
+                self.visit_expr(left);
+                self.visit_expr(right);
+            }
+            SqlExpr::UnaryOp { expr, .. }
+            | SqlExpr::Nested(expr)
+            | SqlExpr::Cast { expr, .. }
+            | SqlExpr::IsNull(expr)
+            | SqlExpr::IsNotNull(expr) => self.visit_expr(expr),
+            SqlExpr::InList { expr, list, .. } => {
+                self.visit_expr(expr);
+                for expr in list {
+                    self.visit_expr(expr);
+                }
+            }
+            SqlExpr::Between {
+                expr, low, high, ..
+            } => {
+                self.visit_expr(expr);
+                self.visit_expr(low);
+                self.visit_expr(high);
+            }
+            SqlExpr::Case {
+                operand,
+                conditions,
+                results,
+                else_result,
+            } => {
+                for expr in operand
+                    .iter()
+                    .chain(else_result.iter())
+                    .map(|expr| expr.as_ref())
+                    .chain(conditions.iter())
+                    .chain(results.iter())
+                {
+                    self.visit_expr(expr);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn visit_table_with_joins(&mut self, table_with_joins: &TableWithJoins) {
+        self.visit_table_factor(&table_with_joins.relation);
+        for join in &table_with_joins.joins {
+            self.visit_table_factor(&join.relation);
+        }
+    }
+
+    fn visit_table_factor(&mut self, table_factor: &TableFactor) {
+        match table_factor {
+            TableFactor::Table { name, .. } => {
+                let table = name
+                    .0
+                    .iter()
+                    .map(|ident| ident.value.to_owned())
+                    .collect::<Vec<_>>()
+                    .join(".");
+                let defined = self.views.contains(&table)
+                    || self.ctes.iter().any(|scope| scope.contains(&table));
+                if !defined && !self.tables.contains(&table) {
+                    self.tables.push(table);
+                }
+            }
+            TableFactor::Derived { subquery, .. } => self.visit_query(subquery),
+            TableFactor::NestedJoin(table_with_joins) => {
+                self.visit_table_with_joins(table_with_joins)
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use datafusion::arrow::array::{Float64Array, Int32Array};
+    use datafusion::prelude::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_column_lineage() -> Result<()> {
+        let mut ctx = ExecutionContext::new();
+        register_view(
+            &mut ctx,
+            "trips",
+            vec![
+                ("id", Arc::new(Int32Array::from(vec![1, 2]))),
+                ("fare", Arc::new(Float64Array::from(vec![1.5, 2.0]))),
+            ],
+        )?;
+
+        let plan = ctx.create_logical_plan(
+            "SELECT id, fare * 2 AS double_fare, 1 AS one FROM trips WHERE fare > 1",
+        )?;
+        assert_eq!(
+            serde_json::to_value(column_lineage(&plan))?,
+            json!([
+                {"name": "id", "sources": [{"view": "trips", "column": "id"}]},
+                {
+                    "name": "double_fare",
+                    "sources": [{"view": "trips", "column": "fare"}],
+                    "expressions": ["trips.fare * Int64(2)"]
+                },
+                {"name": "one", "sources": [], "expressions": ["Int64(1)"]}
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sql_table_references() -> Result<()> {
+        // a common table expression only hides a table of the same name within its query
+        assert_eq!(
+            sql_table_references(
+                "SELECT * FROM (WITH trips AS (SELECT * FROM raw) SELECT * FROM trips) t JOIN trips ON t.id = trips.id"
+            )?,
+            vec!["raw", "trips"]
+        );
+        assert_eq!(
+            sql_table_references(
+                "SELECT * FROM trips WHERE fare > (SELECT AVG(fare) FROM fares) AND id IN (SELECT id FROM valid)"
+            )?,
+            vec!["trips", "fares", "valid"]
+        );
+        // a view is only defined for the statements following it
+        assert_eq!(
+            sql_table_references(
+                "CREATE TEMPORARY VIEW trips AS SELECT * FROM trips WHERE fare > 0;\nSELECT * FROM trips JOIN zones ON trips.zone = zones.id"
+            )?,
+            vec!["trips", "zones"]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use serde_json::{json, Map, Value};
+use uuid::Uuid;
+
+use crate::util::*;
+
+/// Describe a schema in the Arc metadata format, the column definitions Arc uses to type and
+/// validate data
+pub fn arc_metadata(schema: &Schema) -> Result<Value> {
+    schema
+        .fields()
+        .iter()
+        .map(column_metadata)
+        .collect::<Result<Vec<_>>>()
+        .map(Value::Array)
+}
+
+fn column_metadata(field: &Field) -> Result<Value> {
+    let mut column = Map::new();
+    column.insert("id".to_string(), json!(Uuid::new_v4().to_string()));
+    column.insert("name".to_string(), json!(field.name()));
+    column.insert("description".to_string(), Value::Null);
+
+    let arc_type = match field.data_type() {
+        DataType::Utf8 | DataType::LargeUtf8 => "string",
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::UInt8 | DataType::UInt16 => {
+            "integer"
+        }
+        DataType::Int64 | DataType::UInt32 | DataType::UInt64 => "long",
+        DataType::Float16 | DataType::Float32 | DataType::Float64 => "double",
+        DataType::Decimal(_, _) => "decimal",
+        DataType::Boolean => "boolean",
+        DataType::Date32 | DataType::Date64 => "date",
+        DataType::Timestamp(_, _) => "timestamp",
+        DataType::Binary | DataType::LargeBinary => "binary",
+        data_type => {
+            return Err(BoxError::new(format!(
+                "Unable to describe column '{}' of type {} as Arc metadata.",
+                field.name(),
+                data_type
+            )))
+        }
+    };
+    column.insert("type".to_string(), json!(arc_type));
+    column.insert("trim".to_string(), json!(true));
+    column.insert("nullable".to_string(), json!(field.is_nullable()));
+    column.insert("nullableValues".to_string(), json!(["", "null"]));
+
+    match field.data_type() {
+        DataType::Decimal(precision, scale) => {
+            column.insert("precision".to_string(), json!(precision));
+            column.insert("scale".to_string(), json!(scale));
+        }
+        DataType::Boolean => {
+            column.insert("trueValues".to_string(), json!(["true"]));
+            column.insert("falseValues".to_string(), json!(["false"]));
+        }
+        DataType::Date32 | DataType::Date64 => {
+            column.insert("formatters".to_string(), json!(["yyyy-MM-dd"]));
+        }
+        DataType::Timestamp(_, timezone) => {
+            column.insert(
+                "formatters".to_string(),
+                json!(["yyyy-MM-dd'T'HH:mm:ss.SSSXXX"]),
+            );
+            column.insert(
+                "timezoneId".to_string(),
+                json!(timezone.clone().unwrap_or_else(|| "UTC".to_string())),
+            );
+        }
+        _ => {}
+    }
+
+    column.insert("metadata".to_string(), json!({}));
+    Ok(Value::Object(column))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::datatypes::TimeUnit;
+
+    #[test]
+    fn test_arc_metadata() -> Result<()> {
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("amount", DataType::Decimal(10, 2), true),
+            Field::new(
+                "created",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                true,
+            ),
+        ]);
+        let mut metadata = arc_metadata(&schema)?;
+        for column in metadata.as_array_mut().unwrap() {
+            column.as_object_mut().unwrap().remove("id");
+        }
+        assert_eq!(
+            metadata,
+            json!([
+                {
+                    "name": "id",
+                    "description": null,
+                    "type": "long",
+                    "trim": true,
+                    "nullable": false,
+                    "nullableValues": ["", "null"],
+                    "metadata": {}
+                },
+                {
+                    "name": "amount",
+                    "description": null,
+                    "type": "decimal",
+                    "trim": true,
+                    "nullable": true,
+                    "nullableValues": ["", "null"],
+                    "precision": 10,
+                    "scale": 2,
+                    "metadata": {}
+                },
+                {
+                    "name": "created",
+                    "description": null,
+                    "type": "timestamp",
+                    "trim": true,
+                    "nullable": true,
+                    "nullableValues": ["", "null"],
+                    "formatters": ["yyyy-MM-dd'T'HH:mm:ss.SSSXXX"],
+                    "timezoneId": "UTC",
+                    "metadata": {}
+                }
+            ])
+        );
+
+        let schema = Schema::new(vec![Field::new(
+            "tags",
+            DataType::List(Box::new(Field::new("item", DataType::Utf8, true))),
+            true,
+        )]);
+        assert!(arc_metadata(&schema).is_err());
+        Ok(())
+    }
+}
@@ -1,70 +1,141 @@
 pub mod error;
-pub mod lineage_visitor;
+pub mod graph;
+pub(crate) mod lineage_visitor;
+pub(crate) mod metadata;
+pub mod openlineage;
+pub(crate) mod rows;
 pub mod serde_helpers;
+pub(crate) mod sql_script;
 pub mod statistics;
+pub mod uri;
 pub mod variables;
 
 pub use error::{BoxError, Result};
 pub use statistics::Partitions;
 pub use statistics::Statistics;
 
-use std::sync::Arc;
-
 use datafusion::arrow::datatypes::Schema;
 use datafusion::arrow::record_batch::RecordBatch;
 use datafusion::arrow::util::display::array_value_to_string;
+use serde_json::Value;
 
-/// Convert a series of record batches into an html table
+/// Convert a series of record batches into an html table. The header is made from the schema so
+/// that it is shown even when there are no rows.
 #[allow(dead_code)]
 pub fn create_html_table(
+    schema: &Schema,
     results: Vec<RecordBatch>,
     max_rows: Option<usize>,
     table_class_names: &[&str],
 ) -> Result<String> {
-    if results.is_empty() {
-        Ok("".to_string())
+    let mut html = if table_class_names.is_empty() {
+        "<table>".to_string()
     } else {
-        let schema: Arc<Schema> = results[0].schema();
-
-        let mut html = if table_class_names.is_empty() {
-            "<table>".to_string()
-        } else {
-            format!("<table class=\"{}\">", table_class_names.join(" "))
-        };
+        format!("<table class=\"{}\">", table_class_names.join(" "))
+    };
 
-        // make the header
-        html.push_str("<thead><tr>");
-        for field in schema.fields() {
-            html.push_str(format!("<th>{}</th>", field.name()).as_str());
-        }
-        html.push_str("</tr></thead>");
+    // make the header of column names and types
+    html.push_str("<thead><tr>");
+    for field in schema.fields() {
+        html.push_str(format!("<th>{}</th>", escape_html(field.name())).as_str());
+    }
+    html.push_str("</tr><tr>");
+    for field in schema.fields() {
+        html.push_str(format!("<th>{}</th>", escape_html(&field.data_type().to_string())).as_str());
+    }
+    html.push_str("</tr></thead>");
 
-        // make the body
-        html.push_str("<tbody>");
+    // make the body
+    html.push_str("<tbody>");
 
-        let max_rows = max_rows.unwrap_or(usize::MAX);
-        let mut num_rows = 0;
-        for batch in results {
-            if num_rows < max_rows {
-                for row in 0..batch.num_rows() {
-                    if num_rows < max_rows {
-                        html.push_str("<tr>");
-                        for col in 0..batch.num_columns() {
-                            let column = batch.column(col);
-                            html.push_str(
-                                format!("<td>{}</td>", &array_value_to_string(column, row)?)
-                                    .as_str(),
-                            );
-                        }
-                        html.push_str("</tr>");
-                        num_rows += 1;
+    let max_rows = max_rows.unwrap_or(usize::MAX);
+    let mut num_rows = 0;
+    for batch in results {
+        if num_rows < max_rows {
+            for row in 0..batch.num_rows() {
+                if num_rows < max_rows {
+                    html.push_str("<tr>");
+                    for col in 0..batch.num_columns() {
+                        let column = batch.column(col);
+                        html.push_str(
+                            format!(
+                                "<td>{}</td>",
+                                escape_html(&array_value_to_string(column, row)?)
+                            )
+                            .as_str(),
+                        );
                     }
+                    html.push_str("</tr>");
+                    num_rows += 1;
                 }
             }
         }
-        html.push_str("</tbody>");
-        html.push_str("</table>");
+    }
+    html.push_str("</tbody>");
+    html.push_str("</table>");
+
+    Ok(html)
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// The uri of the external dataset a serialized stage reads. The `inputURI` of a `SQLTransform`
+/// is the file of its sql rather than a dataset.
+pub fn input_dataset(stage: &Value) -> Option<&str> {
+    match stage["type"].as_str() {
+        Some("SQLTransform") => None,
+        _ => stage["inputURI"].as_str(),
+    }
+}
+
+/// Register a view of the given columns for tests of stages
+#[cfg(test)]
+pub fn register_view(
+    ctx: &mut datafusion::prelude::ExecutionContext,
+    name: &str,
+    columns: Vec<(&str, datafusion::arrow::array::ArrayRef)>,
+) -> Result<()> {
+    let batch = RecordBatch::try_from_iter(columns)?;
+    let table = datafusion::datasource::MemTable::try_new(batch.schema(), vec![vec![batch]])?;
+    ctx.register_table(name, std::sync::Arc::new(table))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use datafusion::arrow::array::StringArray;
+    use datafusion::arrow::datatypes::{DataType, Field};
+
+    #[test]
+    fn test_create_html_table() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("<b>", DataType::Utf8, true)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(StringArray::from(vec![
+                Some("<script>\"a\" & 'b'</script>"),
+                Some("c"),
+            ]))],
+        )?;
+        assert_eq!(
+            create_html_table(&schema, vec![batch], Some(1), &["tex2jax_ignore"])?,
+            "<table class=\"tex2jax_ignore\"><thead><tr><th>&lt;b&gt;</th></tr><tr><th>Utf8</th></tr></thead><tbody><tr><td>&lt;script&gt;&quot;a&quot; &amp; &#39;b&#39;&lt;/script&gt;</td></tr></tbody></table>"
+        );
 
-        Ok(html)
+        // the header is shown without any rows
+        assert_eq!(
+            create_html_table(&schema, vec![], None, &[])?,
+            "<table><thead><tr><th>&lt;b&gt;</th></tr><tr><th>Utf8</th></tr></thead><tbody></tbody></table>"
+        );
+        Ok(())
     }
 }
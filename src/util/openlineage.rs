@@ -0,0 +1,492 @@
+use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::io::Write;
+
+use chrono::Utc;
+use datafusion::logical_plan::DFSchema;
+use datafusion::prelude::*;
+use serde::Serialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::util::*;
+
+const PRODUCER: &str = "https://github.com/tripl-ai/box";
+const RUN_EVENT_SCHEMA_URL: &str =
+    "https://openlineage.io/spec/1-0-2/OpenLineage.json#/definitions/RunEvent";
+const SCHEMA_FACET_SCHEMA_URL: &str =
+    "https://openlineage.io/spec/facets/1-0-0/SchemaDatasetFacet.json#/$defs/SchemaDatasetFacet";
+const ERROR_FACET_SCHEMA_URL: &str =
+    "https://openlineage.io/spec/facets/1-0-0/ErrorMessageRunFacet.json#/$defs/ErrorMessageRunFacet";
+
+/// Where OpenLineage events are sent
+#[derive(Clone, Debug)]
+pub enum Transport {
+    /// POST each event to an http(s) endpoint
+    Http(String),
+
+    /// Append each event as a line to a file
+    File(String),
+}
+
+impl Transport {
+    pub fn new(uri: &str) -> Self {
+        if uri.starts_with("http://") || uri.starts_with("https://") {
+            Transport::Http(uri.to_owned())
+        } else {
+            Transport::File(uri.trim_start_matches("file://").to_owned())
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct OpenLineageClient {
+    transport: Transport,
+    namespace: String,
+    job_name: String,
+}
+
+impl OpenLineageClient {
+    pub fn new(uri: &str, namespace: String, job_name: String) -> Self {
+        Self {
+            transport: Transport::new(uri),
+            namespace,
+            job_name,
+        }
+    }
+
+    async fn send(&self, http: &reqwest::Client, event: &RunEvent) -> Result<()> {
+        let body = serde_json::to_string(event)?;
+        match &self.transport {
+            Transport::Http(url) => {
+                http.post(url)
+                    .header(http::header::CONTENT_TYPE, "application/json")
+                    .body(body)
+                    .send()
+                    .await?
+                    .error_for_status()?;
+            }
+            Transport::File(path) => {
+                let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+                writeln!(file, "{}", body)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunEvent {
+    event_type: String,
+    event_time: String,
+    run: Run,
+    job: Job,
+    inputs: Vec<Dataset>,
+    outputs: Vec<Dataset>,
+    producer: String,
+    #[serde(rename = "schemaURL")]
+    schema_url: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Run {
+    run_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    facets: Option<Value>,
+}
+
+#[derive(Serialize)]
+struct Job {
+    namespace: String,
+    name: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct Dataset {
+    namespace: String,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    facets: Option<DatasetFacets>,
+}
+
+#[derive(Serialize, Clone)]
+struct DatasetFacets {
+    schema: SchemaDatasetFacet,
+}
+
+#[derive(Serialize, Clone)]
+struct SchemaDatasetFacet {
+    #[serde(rename = "_producer")]
+    producer: String,
+    #[serde(rename = "_schemaURL")]
+    schema_url: String,
+    fields: Vec<SchemaField>,
+}
+
+#[derive(Serialize, Clone)]
+struct SchemaField {
+    name: String,
+    #[serde(rename = "type")]
+    _type: String,
+}
+
+/// Accumulates the datasets read and written by a job and emits the START/COMPLETE/FAIL events
+pub struct OpenLineageRun {
+    client: OpenLineageClient,
+    http: reqwest::Client,
+    run_id: String,
+    inputs: Vec<Dataset>,
+    outputs: Vec<Dataset>,
+}
+
+impl OpenLineageRun {
+    pub fn new(client: OpenLineageClient) -> Self {
+        Self {
+            client,
+            http: reqwest::Client::new(),
+            run_id: Uuid::new_v4().to_string(),
+            inputs: vec![],
+            outputs: vec![],
+        }
+    }
+
+    /// Emit the START event with the external datasets the stages declare they read
+    pub async fn start(&mut self, stages: &[Value]) {
+        for stage in stages {
+            if let Some(input_uri) = input_dataset(stage) {
+                let (namespace, name) = uri_dataset(input_uri);
+                self.add_input(Dataset {
+                    namespace,
+                    name,
+                    facets: None,
+                });
+            }
+        }
+        self.emit("START", None).await
+    }
+
+    pub async fn complete(&self) {
+        self.emit("COMPLETE", None).await
+    }
+
+    pub async fn fail(&self, error: &str) {
+        self.emit("FAIL", Some(error)).await
+    }
+
+    /// Record the datasets of an executed stage from its serialized form
+    pub fn record_stage(&mut self, stage: &Value, ctx: &ExecutionContext) {
+        let output_view = stage["outputView"].as_str();
+        let output_schema = output_view.and_then(|view| view_schema(ctx, view));
+
+        // external datasets
+        if let Some(input_uri) = input_dataset(stage) {
+            let (namespace, name) = uri_dataset(input_uri);
+            self.add_input(Dataset {
+                namespace,
+                name,
+                facets: output_schema.clone(),
+            });
+        }
+
+        // views read which were not produced by this job
+        let produced = self
+            .outputs
+            .iter()
+            .map(|dataset| dataset.name.to_owned())
+            .collect::<HashSet<_>>();
+        if let Some(input_views) = stage["inputViews"].as_array() {
+            for view in input_views.iter().filter_map(|view| view.as_str()) {
+                if !produced.contains(view) {
+                    self.add_input(Dataset {
+                        namespace: self.client.namespace.to_owned(),
+                        name: view.to_owned(),
+                        facets: view_schema(ctx, view),
+                    });
+                }
+            }
+        }
+
+        if let Some(view) = output_view {
+            self.outputs.retain(|dataset| dataset.name != view);
+            self.outputs.push(Dataset {
+                namespace: self.client.namespace.to_owned(),
+                name: view.to_owned(),
+                facets: output_schema,
+            });
+        }
+    }
+
+    /// Add an input, or its schema if the input is already known without one
+    fn add_input(&mut self, dataset: Dataset) {
+        match self
+            .inputs
+            .iter_mut()
+            .find(|input| input.namespace == dataset.namespace && input.name == dataset.name)
+        {
+            Some(input) => {
+                if input.facets.is_none() {
+                    input.facets = dataset.facets;
+                }
+            }
+            None => self.inputs.push(dataset),
+        }
+    }
+
+    async fn emit(&self, event_type: &str, error: Option<&str>) {
+        let event = RunEvent {
+            event_type: event_type.to_owned(),
+            event_time: Utc::now().to_rfc3339(),
+            run: Run {
+                run_id: self.run_id.to_owned(),
+                facets: error.map(|message| {
+                    serde_json::json!({
+                        "errorMessage": {
+                            "_producer": PRODUCER,
+                            "_schemaURL": ERROR_FACET_SCHEMA_URL,
+                            "message": message,
+                            "programmingLanguage": "rust",
+                        }
+                    })
+                }),
+            },
+            job: Job {
+                namespace: self.client.namespace.to_owned(),
+                name: self.client.job_name.to_owned(),
+            },
+            inputs: self.inputs.clone(),
+            outputs: self.outputs.clone(),
+            producer: PRODUCER.to_owned(),
+            schema_url: RUN_EVENT_SCHEMA_URL.to_owned(),
+        };
+
+        // lineage is best effort and must not fail the job
+        if let Err(err) = self.client.send(&self.http, &event).await {
+            eprintln!("Failed to emit OpenLineage {} event: {}", event_type, err);
+        }
+    }
+}
+
+/// Split a uri into an OpenLineage dataset namespace (scheme and authority) and name (path)
+fn uri_dataset(uri: &str) -> (String, String) {
+    match uri.split_once("://") {
+        Some((scheme, rest)) if scheme != "file" => match rest.split_once('/') {
+            Some((authority, path)) => {
+                (format!("{}://{}", scheme, authority), format!("/{}", path))
+            }
+            None => (format!("{}://{}", scheme, rest), "/".to_owned()),
+        },
+        Some((_, path)) => ("file".to_owned(), path.to_owned()),
+        None => ("file".to_owned(), uri.to_owned()),
+    }
+}
+
+fn view_schema(ctx: &ExecutionContext, view: &str) -> Option<DatasetFacets> {
+    ctx.table(view).ok().map(|df| schema_facet(df.schema()))
+}
+
+fn schema_facet(schema: &DFSchema) -> DatasetFacets {
+    DatasetFacets {
+        schema: SchemaDatasetFacet {
+            producer: PRODUCER.to_owned(),
+            schema_url: SCHEMA_FACET_SCHEMA_URL.to_owned(),
+            fields: schema
+                .fields()
+                .iter()
+                .map(|field| SchemaField {
+                    name: field.name().to_owned(),
+                    _type: field.data_type().to_string(),
+                })
+                .collect(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpListener;
+    use std::sync::Arc;
+
+    use datafusion::arrow::array::Int32Array;
+    use serde_json::json;
+
+    use crate::api::{new_execution_context, BoxContext};
+
+    #[test]
+    fn test_uri_dataset() {
+        let cases = vec![
+            ("s3://bucket/path/to/data", ("s3://bucket", "/path/to/data")),
+            ("s3://bucket", ("s3://bucket", "/")),
+            ("file:///tmp/data", ("file", "/tmp/data")),
+            ("./tpch/parquet/orders", ("file", "./tpch/parquet/orders")),
+        ];
+
+        for (uri, (namespace, name)) in cases {
+            assert_eq!(uri_dataset(uri), (namespace.to_owned(), name.to_owned()));
+        }
+    }
+
+    #[test]
+    fn test_transport() {
+        assert!(matches!(
+            Transport::new("http://localhost:5000/api/v1/lineage"),
+            Transport::Http(_)
+        ));
+        assert!(matches!(
+            Transport::new("file:///tmp/lineage.jsonl"),
+            Transport::File(path) if path == "/tmp/lineage.jsonl"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_http_transport() -> Result<()> {
+        // a stub endpoint which records the body of each request
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/api/v1/lineage", listener.local_addr()?);
+        let server = std::thread::spawn(move || -> std::io::Result<Vec<String>> {
+            let mut bodies = vec![];
+            for _ in 0..2 {
+                let (mut stream, _) = listener.accept()?;
+                let mut reader = BufReader::new(stream.try_clone()?);
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line)?;
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body)?;
+                bodies.push(String::from_utf8(body).unwrap());
+                stream.write_all(
+                    b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                )?;
+            }
+            Ok(bodies)
+        });
+
+        let mut run = OpenLineageRun::new(OpenLineageClient::new(
+            &url,
+            "box".to_string(),
+            "trips".to_string(),
+        ));
+        run.start(&[
+            json!({
+                "type": "ParquetExtract",
+                "inputURI": "s3://bucket/trips",
+                "outputView": "trips"
+            }),
+            json!({
+                "type": "SQLTransform",
+                "inputURI": "s3://bucket/fares.sql",
+                "outputView": "fares"
+            }),
+        ])
+        .await;
+        run.complete().await;
+
+        let events = server
+            .join()
+            .unwrap()?
+            .iter()
+            .map(|body| serde_json::from_str::<Value>(body))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        assert_eq!(
+            events
+                .iter()
+                .map(|event| event["eventType"].clone())
+                .collect::<Vec<_>>(),
+            vec![json!("START"), json!("COMPLETE")]
+        );
+        assert_eq!(
+            events[0]["job"],
+            json!({"namespace": "box", "name": "trips"})
+        );
+        assert_eq!(
+            events[0]["inputs"],
+            json!([{"namespace": "s3://bucket", "name": "/trips"}])
+        );
+        assert_eq!(events[0]["run"]["runId"], events[1]["run"]["runId"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_file_transport_failed_job() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("box_{}.jsonl", uuid::Uuid::new_v4()));
+        let box_ctx = BoxContext::new(None, None).with_openlineage(OpenLineageClient::new(
+            &format!("file://{}", path.to_string_lossy()),
+            "box".to_string(),
+            "trips".to_string(),
+        ));
+
+        let mut ctx = new_execution_context(ExecutionConfig::new());
+        register_view(
+            &mut ctx,
+            "trips",
+            vec![("id", Arc::new(Int32Array::from(vec![1, 2])))],
+        )?;
+        // the api job rather than the OpenLineage job of this module
+        let result = crate::api::Job::new(box_ctx)
+            .with_show_entry_exit(false)
+            .with_stage_config(json!({
+                "type": "SQLTransform",
+                "sql": "SELECT id FROM trips",
+                "outputView": "fares"
+            }))?
+            .with_stage_config(json!({
+                "type": "SQLTransform",
+                "sql": "SELECT * FROM missing",
+                "outputView": "failed"
+            }))?
+            .execute(&mut ctx)
+            .await;
+        assert!(result.is_err());
+
+        let contents = std::fs::read_to_string(&path)?;
+        std::fs::remove_file(&path)?;
+        let events = contents
+            .lines()
+            .map(serde_json::from_str::<Value>)
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        assert_eq!(
+            events
+                .iter()
+                .map(|event| event["eventType"].clone())
+                .collect::<Vec<_>>(),
+            vec![json!("START"), json!("FAIL")]
+        );
+        assert_eq!(events[0]["inputs"], json!([]));
+        assert_eq!(events[0]["run"]["runId"], events[1]["run"]["runId"]);
+
+        // the datasets of the stages which completed before the failure
+        let schema = json!({
+            "schema": {
+                "_producer": PRODUCER,
+                "_schemaURL": SCHEMA_FACET_SCHEMA_URL,
+                "fields": [{"name": "id", "type": "Int32"}]
+            }
+        });
+        assert_eq!(
+            events[1]["inputs"],
+            json!([{"namespace": "box", "name": "trips", "facets": schema}])
+        );
+        assert_eq!(
+            events[1]["outputs"],
+            json!([{"namespace": "box", "name": "fares", "facets": schema}])
+        );
+        assert!(events[1]["run"]["facets"]["errorMessage"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("missing"));
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+use datafusion::arrow::array::{Array, ArrayRef};
+use datafusion::arrow::datatypes::DataType;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::arrow::util::display::array_value_to_string;
+use serde_json::{Map, Value};
+
+use crate::util::*;
+
+/// Render each row of the batch, restricted to the given columns, as a string which is equal for
+/// equal rows so that rows of any type can be hashed and compared
+pub fn row_keys(batch: &RecordBatch, columns: &[usize]) -> Result<Vec<String>> {
+    let arrays = columns
+        .iter()
+        .map(|column| batch.column(*column))
+        .collect::<Vec<_>>();
+
+    (0..batch.num_rows())
+        .map(|row| {
+            let values = arrays
+                .iter()
+                .map(|array| {
+                    if array.is_null(row) {
+                        Ok(None)
+                    } else {
+                        array_value_to_string(array, row).map(Some)
+                    }
+                })
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            Ok(serde_json::to_string(&values)?)
+        })
+        .collect()
+}
+
+/// Render a single row as a json object of column name to value typed as by `json_value`
+pub fn row_value(batch: &RecordBatch, row: usize) -> Result<Value> {
+    let schema = batch.schema();
+    let mut object = Map::new();
+    for (index, field) in schema.fields().iter().enumerate() {
+        object.insert(
+            field.name().to_owned(),
+            json_value(batch.column(index), row)?,
+        );
+    }
+    Ok(Value::Object(object))
+}
+
+/// Convert a value to json keeping numbers and booleans typed and rendering other types for
+/// display
+pub fn json_value(array: &ArrayRef, row: usize) -> Result<Value> {
+    if array.is_null(row) {
+        return Ok(Value::Null);
+    }
+    let value = array_value_to_string(array, row)?;
+    match array.data_type() {
+        DataType::Boolean
+        | DataType::Int8
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::Int64
+        | DataType::UInt8
+        | DataType::UInt16
+        | DataType::UInt32
+        | DataType::UInt64
+        | DataType::Float16
+        | DataType::Float32
+        | DataType::Float64
+        | DataType::Decimal(_, _) => {
+            // NaN and infinity are not valid json numbers
+            Ok(serde_json::from_str::<Value>(&value).unwrap_or(Value::String(value)))
+        }
+        _ => Ok(Value::String(value)),
+    }
+}
+
+/// Render the first value of an array for display
+pub fn value_as_string(array: &ArrayRef) -> Result<Option<String>> {
+    if array.is_null(0) {
+        Ok(None)
+    } else {
+        Ok(Some(array_value_to_string(array, 0)?))
+    }
+}
+
+/// Read the first value of an integer array
+pub fn value_as_usize(array: &ArrayRef) -> Result<Option<usize>> {
+    value_as_string(array)?
+        .map(|value| {
+            value
+                .parse::<usize>()
+                .map_err(|err| BoxError::new(format!("Invalid count '{}': {}", value, err)))
+        })
+        .transpose()
+}
@@ -36,3 +36,131 @@ pub fn default_true() -> bool {
 pub fn default_false() -> bool {
     false
 }
+
+/// The path, such as `columns[1].name`, of the value at the line and column of a json document
+/// reported by a serde_json error or `None` for the document itself
+pub fn json_path(json: &str, line: usize, column: usize) -> Option<String> {
+    enum Frame {
+        Object { key: Option<String>, in_key: bool },
+        Array(usize),
+    }
+
+    let offset = json
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(|line| line.chars().count())
+        .sum::<usize>()
+        + column;
+    let chars = json.chars().take(offset).collect::<Vec<_>>();
+    let mut frames: Vec<Frame> = vec![];
+    let mut index = 0;
+    while index < chars.len() {
+        match chars[index] {
+            '"' => {
+                let mut string = String::new();
+                index += 1;
+                while index < chars.len() && chars[index] != '"' {
+                    if chars[index] == '\\' {
+                        index += 1;
+                    }
+                    if let Some(c) = chars.get(index) {
+                        string.push(*c);
+                    }
+                    index += 1;
+                }
+                if let Some(Frame::Object { key, in_key: true }) = frames.last_mut() {
+                    *key = Some(string);
+                }
+            }
+            '{' => frames.push(Frame::Object {
+                key: None,
+                in_key: true,
+            }),
+            '[' => frames.push(Frame::Array(0)),
+            '}' | ']' => {
+                frames.pop();
+            }
+            ':' => {
+                if let Some(Frame::Object { in_key, .. }) = frames.last_mut() {
+                    *in_key = false;
+                }
+            }
+            ',' => match frames.last_mut() {
+                Some(Frame::Object { in_key, .. }) => *in_key = true,
+                Some(Frame::Array(array_index)) => *array_index += 1,
+                None => {}
+            },
+            _ => {}
+        }
+        index += 1;
+    }
+
+    let mut path = String::new();
+    for frame in &frames {
+        match frame {
+            Frame::Object { key: Some(key), .. } => {
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(key);
+            }
+            Frame::Object { key: None, .. } => break,
+            Frame::Array(array_index) => path.push_str(&format!("[{}]", array_index)),
+        }
+    }
+    if path.is_empty() {
+        None
+    } else {
+        Some(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Column {
+        #[allow(dead_code)]
+        name: String,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Stage {
+        #[allow(dead_code)]
+        persist: bool,
+        #[allow(dead_code)]
+        columns: Vec<Column>,
+    }
+
+    fn error_path(json: &str) -> Option<String> {
+        let err = serde_json::from_str::<Stage>(json).unwrap_err();
+        json_path(json, err.line(), err.column())
+    }
+
+    #[test]
+    fn test_json_path() {
+        assert_eq!(
+            error_path(r#"{"persist": "yes", "columns": []}"#),
+            Some("persist".to_string())
+        );
+        assert_eq!(
+            error_path(r#"{"persist": true, "columns": [{"name": "a"}, {"name": 1}]}"#),
+            Some("columns[1].name".to_string())
+        );
+        assert_eq!(
+            error_path(
+                "{\n  \"persist\": true,\n  \"columns\": [{\"name\": \"a\", \"nam\": \"b\"}]\n}"
+            ),
+            Some("columns[0].nam".to_string())
+        );
+        assert_eq!(
+            error_path(r#"{"persist": true, "columns": [{}]}"#),
+            Some("columns[0]".to_string())
+        );
+        assert_eq!(error_path(r#"{"persist": true}"#), None);
+    }
+}
@@ -0,0 +1,270 @@
+use std::str::FromStr;
+
+use datafusion::prelude::*;
+use lazy_static::lazy_static;
+use regex::Regex;
+use sqlparser::ast::Statement;
+use sqlparser::dialect::GenericDialect;
+use sqlparser::parser::Parser;
+
+use crate::util::*;
+
+lazy_static! {
+    static ref COMMENT_RE: Regex = Regex::new(r"(?s)^(?:--[^\n]*|/\*.*?\*/)").unwrap();
+}
+
+/// A single statement of a `;` separated sql script
+#[derive(Debug, PartialEq)]
+pub enum ScriptStatement {
+    /// `SET key = value` or `SET key TO value`
+    Set {
+        key: String,
+        value: String,
+    },
+
+    /// `CREATE [OR REPLACE] [TEMP|TEMPORARY] VIEW name AS query`
+    CreateView {
+        name: String,
+        temporary: bool,
+        query: String,
+    },
+
+    /// A standalone `WITH name AS (query), ...` whose definitions are visible to later statements
+    With(Vec<(String, String)>),
+
+    Query(String),
+}
+
+/// Split a script into statements on `;` outside of quotes and comments, dropping empty
+/// statements
+pub fn split_statements(sql: &str) -> Vec<String> {
+    let mut statements = vec![];
+    let mut statement = String::new();
+    let mut chars = sql.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' | '`' => {
+                statement.push(c);
+                // a doubled quote is an escaped quote and is handled by reentering the match
+                for next in chars.by_ref() {
+                    statement.push(next);
+                    if next == c {
+                        break;
+                    }
+                }
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                statement.push(c);
+                for next in chars.by_ref() {
+                    statement.push(next);
+                    if next == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                statement.push(c);
+                statement.push(chars.next().unwrap());
+                let mut previous = ' ';
+                for next in chars.by_ref() {
+                    statement.push(next);
+                    if previous == '*' && next == '/' {
+                        break;
+                    }
+                    previous = next;
+                }
+            }
+            ';' => statements.push(std::mem::take(&mut statement)),
+            _ => statement.push(c),
+        }
+    }
+    statements.push(statement);
+
+    statements
+        .into_iter()
+        .map(|statement| statement.trim().to_owned())
+        .filter(|statement| !is_blank(statement))
+        .collect()
+}
+
+/// True if the statement contains only whitespace and comments
+fn is_blank(statement: &str) -> bool {
+    strip_leading_comments(statement).is_empty()
+}
+
+/// Remove the whitespace and comments preceding the first token of a statement
+fn strip_leading_comments(statement: &str) -> &str {
+    let mut statement = statement.trim_start();
+    while let Some(comment) = COMMENT_RE.find(statement) {
+        statement = statement[comment.end()..].trim_start();
+    }
+    statement
+}
+
+/// Classify a statement produced by `split_statements`. Leading comments are ignored when
+/// classifying but a query is returned as written.
+pub fn parse_statement(statement: &str) -> Result<ScriptStatement> {
+    lazy_static! {
+        static ref SET_RE: Regex =
+            Regex::new(r"(?is)^SET\s+([\w.]+)\s*(?:=|\s+TO\s+)\s*(.+)$").unwrap();
+        static ref CREATE_VIEW_RE: Regex = Regex::new(
+            r"(?is)^CREATE\s+(?:OR\s+REPLACE\s+)?(TEMP\s+|TEMPORARY\s+)?VIEW\s+(\w+)\s+AS\s+(.+)$"
+        )
+        .unwrap();
+        static ref WITH_RE: Regex = Regex::new(r"(?is)^WITH\s").unwrap();
+    }
+
+    let query = statement;
+    let statement = strip_leading_comments(statement);
+
+    if let Some(captures) = SET_RE.captures(statement) {
+        let value = captures[2].trim();
+        let value = value
+            .strip_prefix('\'')
+            .and_then(|value| value.strip_suffix('\''))
+            .unwrap_or(value);
+        return Ok(ScriptStatement::Set {
+            key: captures[1].to_lowercase(),
+            value: value.to_owned(),
+        });
+    }
+
+    if let Some(captures) = CREATE_VIEW_RE.captures(statement) {
+        return Ok(ScriptStatement::CreateView {
+            name: captures[2].to_owned(),
+            temporary: captures.get(1).is_some(),
+            query: captures[3].trim().to_owned(),
+        });
+    }
+
+    // a with clause without a final query only parses once a query is appended
+    if WITH_RE.is_match(statement) && Parser::parse_sql(&GenericDialect {}, statement).is_err() {
+        if let Ok(statements) =
+            Parser::parse_sql(&GenericDialect {}, &format!("{} SELECT 1", statement))
+        {
+            if let [Statement::Query(query)] = statements.as_slice() {
+                if let Some(with) = &query.with {
+                    return Ok(ScriptStatement::With(
+                        with.cte_tables
+                            .iter()
+                            .map(|cte| (cte.alias.name.value.to_owned(), cte.query.to_string()))
+                            .collect(),
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(ScriptStatement::Query(query.to_owned()))
+}
+
+/// Split and classify a script
+pub fn parse_script(sql: &str) -> Result<Vec<ScriptStatement>> {
+    split_statements(sql)
+        .iter()
+        .map(|statement| parse_statement(statement))
+        .collect()
+}
+
+/// Apply a `SET` statement to the session configuration. The change persists for the life of
+/// the context so callers scoping it to a stage must restore the previous configuration.
+pub fn set_option(ctx: &mut ExecutionContext, key: &str, value: &str) -> Result<()> {
+    let mut state = ctx.state.lock().unwrap();
+    let config = &mut state.config;
+    match key.trim_start_matches("datafusion.execution.") {
+        "target_partitions" => config.target_partitions = parse_option(key, value)?,
+        "batch_size" => config.batch_size = parse_option(key, value)?,
+        "repartition_joins" => config.repartition_joins = parse_option(key, value)?,
+        "repartition_aggregations" => {
+            config.repartition_aggregations = parse_option(key, value)?
+        }
+        "repartition_windows" => config.repartition_windows = parse_option(key, value)?,
+        _ => {
+            return Err(BoxError::new(format!(
+                "Unsupported SET option '{}'. Expected one of ['batch_size', 'repartition_aggregations', 'repartition_joins', 'repartition_windows', 'target_partitions'].",
+                key
+            )))
+        }
+    }
+    Ok(())
+}
+
+fn parse_option<T: FromStr>(key: &str, value: &str) -> Result<T> {
+    value.parse::<T>().map_err(|_| {
+        BoxError::new(format!(
+            "Unable to parse value '{}' for SET option '{}'.",
+            value, key
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_statements() {
+        let sql = "SET batch_size = 1024;\n-- a comment; with a semicolon\nSELECT ';' AS a /* ; */ FROM t;\n\n;-- trailing";
+        assert_eq!(
+            split_statements(sql),
+            vec![
+                "SET batch_size = 1024",
+                "-- a comment; with a semicolon\nSELECT ';' AS a /* ; */ FROM t",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_statement() -> Result<()> {
+        assert_eq!(
+            parse_statement("set target_partitions to '4'")?,
+            ScriptStatement::Set {
+                key: "target_partitions".to_string(),
+                value: "4".to_string()
+            }
+        );
+        assert_eq!(
+            parse_statement("CREATE TEMP VIEW recent AS\nSELECT * FROM trips WHERE year > 2020")?,
+            ScriptStatement::CreateView {
+                name: "recent".to_string(),
+                temporary: true,
+                query: "SELECT * FROM trips WHERE year > 2020".to_string()
+            }
+        );
+        assert_eq!(
+            parse_statement("WITH a AS (SELECT 1 AS x), b AS (SELECT x FROM a)")?,
+            ScriptStatement::With(vec![
+                ("a".to_string(), "SELECT 1 AS x".to_string()),
+                ("b".to_string(), "SELECT x FROM a".to_string())
+            ])
+        );
+        assert_eq!(
+            parse_statement("WITH a AS (SELECT 1 AS x) SELECT * FROM a")?,
+            ScriptStatement::Query("WITH a AS (SELECT 1 AS x) SELECT * FROM a".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_script_leading_comments() -> Result<()> {
+        let sql = "-- tune the join\nSET batch_size = 1024;\n/* recent trips */ CREATE VIEW recent AS SELECT * FROM trips;\n-- totals\nWITH a AS (SELECT 1 AS x);\n-- result\nSELECT * FROM recent";
+        assert_eq!(
+            parse_script(sql)?,
+            vec![
+                ScriptStatement::Set {
+                    key: "batch_size".to_string(),
+                    value: "1024".to_string()
+                },
+                ScriptStatement::CreateView {
+                    name: "recent".to_string(),
+                    temporary: false,
+                    query: "SELECT * FROM trips".to_string()
+                },
+                ScriptStatement::With(vec![("a".to_string(), "SELECT 1 AS x".to_string())]),
+                ScriptStatement::Query("-- result\nSELECT * FROM recent".to_string()),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -1,5 +1,15 @@
+use std::sync::Arc;
+
+use datafusion::arrow::datatypes::DataType;
+use datafusion::logical_plan::Column;
+use datafusion::physical_plan::aggregates::AggregateFunction;
+use datafusion::prelude::*;
 use serde::{Deserialize, Serialize};
-#[derive(Deserialize, Serialize, Clone)]
+
+use crate::util::rows::{value_as_string, value_as_usize};
+use crate::util::*;
+
+#[derive(Deserialize, Serialize, Clone, Default)]
 pub struct Statistics {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub row_count: Option<usize>,
@@ -9,6 +19,9 @@ pub struct Statistics {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub partitions: Option<Partitions>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub columns: Option<Vec<ColumnStatistics>>,
 }
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -20,6 +33,45 @@ pub struct Partitions {
     pub output: Option<usize>,
 }
 
+#[derive(Deserialize, Serialize, Clone)]
+pub struct ColumnStatistics {
+    pub name: String,
+
+    pub data_type: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub null_count: Option<usize>,
+
+    /// Approximate (HyperLogLog) count of the distinct non-null values
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distinct_count: Option<usize>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_length: Option<usize>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<usize>,
+}
+
+/// The number of rows of a DataFrame if its plan knows it exactly without executing it, as for
+/// views held in memory
+pub async fn exact_row_count(
+    ctx: &ExecutionContext,
+    df: Arc<dyn DataFrame>,
+) -> Result<Option<usize>> {
+    let statistics = ctx
+        .create_physical_plan(&df.to_logical_plan())
+        .await?
+        .statistics();
+    Ok(statistics.num_rows.filter(|_| statistics.is_exact))
+}
+
 impl Statistics {
     pub fn new(
         stat: datafusion::physical_plan::Statistics,
@@ -29,6 +81,7 @@ impl Statistics {
             row_count: stat.num_rows,
             total_byte_size: stat.total_byte_size,
             partitions,
+            columns: None,
         })
     }
 }
@@ -38,3 +91,248 @@ impl Partitions {
         Self { input, output }
     }
 }
+
+impl ColumnStatistics {
+    fn new(name: &str, data_type: &DataType) -> Self {
+        Self {
+            name: name.to_owned(),
+            data_type: data_type.to_string(),
+            null_count: None,
+            distinct_count: None,
+            min: None,
+            max: None,
+            min_length: None,
+            max_length: None,
+        }
+    }
+}
+
+/// The individual aggregates calculated for each column
+enum Profile {
+    NullCount(usize),
+    DistinctCount(usize),
+    Min(usize),
+    Max(usize),
+    MinLength(usize),
+    MaxLength(usize),
+}
+
+/// Calculate per-column statistics for a dataframe in a single aggregation pass and merge them
+/// into any existing statistics
+pub async fn profile(
+    statistics: Option<Statistics>,
+    df: Arc<dyn DataFrame>,
+) -> Result<Option<Statistics>> {
+    let fields = df.schema().fields().clone();
+
+    // the first aggregate is always the row count
+    let mut aggr_expr = vec![count(lit(1))];
+    let mut profiles: Vec<Vec<Profile>> = Vec::with_capacity(fields.len());
+
+    for field in &fields {
+        let column = Expr::Column(Column {
+            relation: None,
+            name: field.name().to_owned(),
+        });
+        let mut column_profiles = vec![];
+
+        column_profiles.push(Profile::NullCount(aggr_expr.len()));
+        aggr_expr.push(count(column.clone()));
+
+        if is_distinct_countable(field.data_type()) {
+            column_profiles.push(Profile::DistinctCount(aggr_expr.len()));
+            aggr_expr.push(Expr::AggregateFunction {
+                fun: AggregateFunction::ApproxDistinct,
+                args: vec![column.clone()],
+                distinct: false,
+            });
+        }
+
+        if is_orderable(field.data_type()) {
+            column_profiles.push(Profile::Min(aggr_expr.len()));
+            aggr_expr.push(min(column.clone()));
+            column_profiles.push(Profile::Max(aggr_expr.len()));
+            aggr_expr.push(max(column.clone()));
+        }
+
+        if matches!(field.data_type(), DataType::Utf8 | DataType::LargeUtf8) {
+            column_profiles.push(Profile::MinLength(aggr_expr.len()));
+            aggr_expr.push(min(character_length(column.clone())));
+            column_profiles.push(Profile::MaxLength(aggr_expr.len()));
+            aggr_expr.push(max(character_length(column)));
+        }
+
+        profiles.push(column_profiles);
+    }
+
+    let batches = df.aggregate(vec![], aggr_expr)?.collect().await?;
+    let batch = batches
+        .iter()
+        .find(|batch| batch.num_rows() == 1)
+        .ok_or_else(|| BoxError::new("Expected profile to return a single row.".to_string()))?;
+
+    let row_count = value_as_usize(batch.column(0))?.unwrap_or_default();
+
+    let columns = fields
+        .iter()
+        .zip(profiles)
+        .map(|(field, column_profiles)| {
+            let mut column_statistics = ColumnStatistics::new(field.name(), field.data_type());
+            for column_profile in column_profiles {
+                match column_profile {
+                    Profile::NullCount(i) => {
+                        column_statistics.null_count =
+                            value_as_usize(batch.column(i))?.map(|non_null| row_count - non_null)
+                    }
+                    Profile::DistinctCount(i) => {
+                        column_statistics.distinct_count = value_as_usize(batch.column(i))?
+                    }
+                    Profile::Min(i) => column_statistics.min = value_as_string(batch.column(i))?,
+                    Profile::Max(i) => column_statistics.max = value_as_string(batch.column(i))?,
+                    Profile::MinLength(i) => {
+                        column_statistics.min_length = value_as_usize(batch.column(i))?
+                    }
+                    Profile::MaxLength(i) => {
+                        column_statistics.max_length = value_as_usize(batch.column(i))?
+                    }
+                }
+            }
+            Ok(column_statistics)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut statistics = statistics.unwrap_or_default();
+    statistics.row_count = statistics.row_count.or(Some(row_count));
+    statistics.columns = Some(columns);
+
+    Ok(Some(statistics))
+}
+
+/// Types supported by the DataFusion min/max aggregates
+fn is_orderable(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Int8
+            | DataType::Int16
+            | DataType::Int32
+            | DataType::Int64
+            | DataType::UInt8
+            | DataType::UInt16
+            | DataType::UInt32
+            | DataType::UInt64
+            | DataType::Float32
+            | DataType::Float64
+            | DataType::Utf8
+            | DataType::LargeUtf8
+            | DataType::Date32
+            | DataType::Date64
+            | DataType::Timestamp(_, _)
+    )
+}
+
+/// Types supported by the DataFusion approx_distinct aggregate
+fn is_distinct_countable(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Int8
+            | DataType::Int16
+            | DataType::Int32
+            | DataType::Int64
+            | DataType::UInt8
+            | DataType::UInt16
+            | DataType::UInt32
+            | DataType::UInt64
+            | DataType::Utf8
+            | DataType::LargeUtf8
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{ArrayRef, Float64Array, Int64Array, StringArray};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_profile() -> Result<()> {
+        let mut ctx = ExecutionContext::new();
+        register_view(
+            &mut ctx,
+            "trips",
+            vec![
+                (
+                    "id",
+                    Arc::new(Int64Array::from(vec![Some(1), Some(2), Some(2), None])) as ArrayRef,
+                ),
+                (
+                    "name",
+                    Arc::new(StringArray::from(vec![
+                        Some("a"),
+                        Some("bbb"),
+                        Some("bbb"),
+                        None,
+                    ])) as ArrayRef,
+                ),
+                (
+                    "fare",
+                    Arc::new(Float64Array::from(vec![
+                        Some(1.5),
+                        None,
+                        Some(-2.5),
+                        Some(1.5),
+                    ])) as ArrayRef,
+                ),
+            ],
+        )?;
+
+        let statistics = profile(None, ctx.table("trips")?).await?;
+        assert_eq!(
+            serde_json::to_value(statistics)?,
+            json!({
+                "row_count": 4,
+                "columns": [
+                    {
+                        "name": "id",
+                        "data_type": "Int64",
+                        "null_count": 1,
+                        "distinct_count": 2,
+                        "min": "1",
+                        "max": "2"
+                    },
+                    {
+                        "name": "name",
+                        "data_type": "Utf8",
+                        "null_count": 1,
+                        "distinct_count": 2,
+                        "min": "a",
+                        "max": "bbb",
+                        "min_length": 1,
+                        "max_length": 3
+                    },
+                    // floats are not supported by approx_distinct
+                    {
+                        "name": "fare",
+                        "data_type": "Float64",
+                        "null_count": 1,
+                        "min": "-2.5",
+                        "max": "1.5"
+                    }
+                ]
+            })
+        );
+
+        // an existing row count is kept
+        let statistics = profile(
+            Some(Statistics {
+                row_count: Some(10),
+                ..Default::default()
+            }),
+            ctx.sql("SELECT id FROM trips").await?,
+        )
+        .await?
+        .unwrap();
+        assert_eq!(statistics.row_count, Some(10));
+        assert_eq!(statistics.columns.unwrap()[0].null_count, Some(1));
+        Ok(())
+    }
+}
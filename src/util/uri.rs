@@ -0,0 +1,79 @@
+use std::io::Read;
+use std::path::Path;
+
+use datafusion::datasource::object_store::SizedFile;
+use datafusion::prelude::*;
+use futures::StreamExt;
+
+use crate::api::BoxContext;
+use crate::util::*;
+
+/// Resolve a relative local path against the directory containing the job file. Absolute paths
+/// and URIs with a scheme are returned unchanged.
+pub fn resolve(box_ctx: &BoxContext, uri: &str) -> String {
+    if uri.contains("://") || Path::new(uri).is_absolute() {
+        return uri.to_owned();
+    }
+
+    match box_ctx
+        .job_path
+        .as_ref()
+        .and_then(|job_path| Path::new(job_path).parent())
+    {
+        Some(parent) => parent.join(uri).to_string_lossy().to_string(),
+        None => uri.to_owned(),
+    }
+}
+
+/// Read a single file via the object store registered for its scheme
+pub async fn read_to_string(
+    box_ctx: &BoxContext,
+    ctx: &ExecutionContext,
+    uri: &str,
+) -> Result<String> {
+    let uri = resolve(box_ctx, uri);
+    let (object_store, path) = ctx.object_store(&uri)?;
+
+    let mut files = object_store.list_file(path).await?;
+    let sized_file: SizedFile = match files.next().await {
+        Some(file) => file?.sized_file,
+        None => return Err(BoxError::new(format!("File not found at '{}'.", uri))),
+    };
+    if files.next().await.is_some() {
+        return Err(BoxError::new(format!(
+            "Expected a single file at '{}' but found multiple.",
+            uri
+        )));
+    }
+
+    let mut contents = String::new();
+    object_store
+        .file_reader(sized_file)?
+        .sync_reader()?
+        .read_to_string(&mut contents)?;
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let box_ctx = BoxContext::new(Some("/jobs/nyctaxi/job.json".to_string()), None);
+
+        assert_eq!(
+            resolve(&box_ctx, "sql/trips.sql"),
+            "/jobs/nyctaxi/sql/trips.sql"
+        );
+        assert_eq!(resolve(&box_ctx, "/sql/trips.sql"), "/sql/trips.sql");
+        assert_eq!(
+            resolve(&box_ctx, "s3://bucket/trips.sql"),
+            "s3://bucket/trips.sql"
+        );
+        assert_eq!(
+            resolve(&BoxContext::new(None, None), "sql/trips.sql"),
+            "sql/trips.sql"
+        );
+    }
+}
@@ -4,17 +4,24 @@ use crate::util::*;
 
 use regex::Regex;
 
+lazy_static! {
+    // this will find any remaining parameters which have not been replaced
+    static ref RE: Regex = Regex::new("[$][{](\\w*)(?:=[^}]+)?[}]").unwrap();
+}
+
+/// The names of the parameters the input refers to
+pub fn placeholders(input: &str) -> Vec<String> {
+    RE.captures_iter(input)
+        .map(|captures| captures[1].to_string())
+        .collect()
+}
+
 pub fn substitute_variables(
     input: String,
     params: &HashMap<String, String>,
     allow_missing_placeholders: bool,
     allow_missing_parameters: bool,
 ) -> Result<String> {
-    lazy_static! {
-        // this will find any remaining parameters which have not been replaced
-        static ref RE: Regex = Regex::new("[$][{](\\w*)(?:=[^}]+)?[}]").unwrap();
-    }
-
     // iterate over the parameters, create a new regex for each and use it to replace any matches
     let output = params.iter().try_fold(input, |sql, (key, val)| {
         let re = Regex::new(
@@ -53,8 +60,13 @@ pub fn substitute_variables(
     }
 }
 
-#[allow(dead_code)]
+/// Convert hocon style concatenation of strings and placeholders, such as `"s3://"${BUCKET}`, to
+/// json strings. Valid json is returned unchanged as its placeholders are already within strings.
 pub fn replace_hocon_parameters(input: &str) -> String {
+    if serde_json::from_str::<serde::de::IgnoredAny>(input).is_ok() {
+        return input.to_owned();
+    }
+
     lazy_static! {
         // find '/abc"${VARIABLE}"/def'
         static ref BOTH_VAR_RE: Regex = Regex::new("\"\\s*\\$\\{(\\w*)(?:=[^}]+)?}\\s*\"").unwrap();
@@ -103,6 +115,10 @@ mod tests {
                 input: r#"{"inputURI": "s3://"${VARIABLE_ONE}"/"${VARIABLE_TWO}}"#,
                 output: r#"{"inputURI": "s3://${VARIABLE_ONE}/${VARIABLE_TWO}"}"#,
             },
+            TestCase {
+                input: r#"{"sql": "SELECT * FROM ${TABLE}", "outputView": "${VIEW}"}"#,
+                output: r#"{"sql": "SELECT * FROM ${TABLE}", "outputView": "${VIEW}"}"#,
+            },
         ];
 
         cases.iter().for_each(|test| {
@@ -116,4 +132,12 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_placeholders() {
+        assert_eq!(
+            placeholders(r#"{"inputURI": "${INPUT=/tmp}/${ETL_CONF_ENV}.csv"}"#),
+            vec!["INPUT".to_string(), "ETL_CONF_ENV".to_string()]
+        );
+    }
 }
@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datafusion::logical_plan::DFSchema;
+use datafusion::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::api::*;
+use crate::util::*;
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct EqualityValidate {
+    #[serde(rename(serialize = "type", deserialize = "type"))]
+    _type: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+
+    #[serde(rename = "leftView")]
+    left_view: String,
+
+    #[serde(rename = "rightView")]
+    right_view: String,
+
+    /// The maximum number of differing rows from each side to include in the failure message
+    #[serde(rename = "sampleSize", default = "default_sample_size")]
+    sample_size: usize,
+}
+
+fn default_sample_size() -> usize {
+    10
+}
+
+impl fmt::Display for EqualityValidate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", serde_json::to_string(self).unwrap())
+    }
+}
+
+impl EqualityValidate {
+    pub fn try_new(json: String) -> Result<EqualityValidate> {
+        serde_json::from_str::<EqualityValidate>(&json).map_err(BoxError::from)
+    }
+}
+
+#[async_trait]
+impl PipelineStage for EqualityValidate {
+    fn to_value(&self) -> serde_json::Value {
+        serde_json::to_value(&self).unwrap()
+    }
+
+    async fn execute(
+        &mut self,
+        _: BoxContext,
+        ctx: &mut ExecutionContext,
+    ) -> Result<Option<Arc<dyn DataFrame>>> {
+        let left = ctx.table(self.left_view.as_str())?;
+        let right = ctx.table(self.right_view.as_str())?;
+
+        // the schemas must match by name and type (nullability is ignored)
+        let left_signature = signature(left.schema());
+        let right_signature = signature(right.schema());
+        if left_signature != right_signature {
+            return Err(BoxError::new(format!(
+                "EqualityValidate failed as schemas do not match. '{}': [{}] '{}': [{}].",
+                self.left_view,
+                left_signature.join(", "),
+                self.right_view,
+                right_signature.join(", ")
+            )));
+        }
+
+        let left_batches = left.collect().await?;
+        let right_batches = right.collect().await?;
+
+        // count each distinct row (+1 for left, -1 for right) remembering where it was first seen.
+        // rows are kept in input order so the sample is the same on every run.
+        let mut indexes: HashMap<String, usize> = HashMap::new();
+        let mut counts: Vec<(i64, bool, usize, usize)> = vec![];
+        for (is_left, batches, delta) in [(true, &left_batches, 1), (false, &right_batches, -1)] {
+            for (batch_index, batch) in batches.iter().enumerate() {
+                let columns = (0..batch.num_columns()).collect::<Vec<_>>();
+                for (row, key) in rows::row_keys(batch, &columns)?.into_iter().enumerate() {
+                    let index = *indexes.entry(key).or_insert_with(|| {
+                        counts.push((0, is_left, batch_index, row));
+                        counts.len() - 1
+                    });
+                    counts[index].0 += delta;
+                }
+            }
+        }
+
+        let mut left_only = 0;
+        let mut right_only = 0;
+        let mut left_sample = vec![];
+        let mut right_sample = vec![];
+        for (count, is_left, batch_index, row) in counts {
+            if count == 0 {
+                continue;
+            }
+            let batch = if is_left {
+                &left_batches[batch_index]
+            } else {
+                &right_batches[batch_index]
+            };
+            let (total, sample) = if count > 0 {
+                (&mut left_only, &mut left_sample)
+            } else {
+                (&mut right_only, &mut right_sample)
+            };
+            *total += count.unsigned_abs();
+            if sample.len() < self.sample_size {
+                sample.push(rows::row_value(batch, row)?);
+            }
+        }
+
+        if left_only != 0 || right_only != 0 {
+            return Err(BoxError::new(format!(
+                "EqualityValidate failed. '{}' has {} rows not found in '{}' and '{}' has {} rows not found in '{}'. Sample: {}",
+                self.left_view,
+                left_only,
+                self.right_view,
+                self.right_view,
+                right_only,
+                self.left_view,
+                serde_json::json!({
+                    "left": left_sample,
+                    "right": right_sample,
+                })
+            )));
+        }
+
+        Ok(None)
+    }
+}
+
+fn signature(schema: &DFSchema) -> Vec<String> {
+    schema
+        .fields()
+        .iter()
+        .map(|field| format!("{}: {}", field.name(), field.data_type()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Int32Array, StringArray};
+    use serde_json::json;
+
+    async fn validate(left: Vec<i32>, right: Vec<i32>) -> Result<Option<Arc<dyn DataFrame>>> {
+        let mut ctx = ExecutionContext::new();
+        register_view(
+            &mut ctx,
+            "expected",
+            vec![("id", Arc::new(Int32Array::from(left)))],
+        )?;
+        register_view(
+            &mut ctx,
+            "actual",
+            vec![("id", Arc::new(Int32Array::from(right)))],
+        )?;
+        EqualityValidate::try_new(
+            json!({"type": "EqualityValidate", "leftView": "expected", "rightView": "actual"})
+                .to_string(),
+        )?
+        .execute(BoxContext::new(None, None), &mut ctx)
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_equality_validate() -> Result<()> {
+        // the order of rows does not matter
+        assert!(validate(vec![1, 2, 3], vec![3, 1, 2]).await?.is_none());
+
+        assert_eq!(
+            validate(vec![1, 2, 3, 5], vec![1, 2, 4]).await.unwrap_err().to_string(),
+            "EqualityValidate failed. 'expected' has 2 rows not found in 'actual' and 'actual' has 1 rows not found in 'expected'. Sample: {\"left\":[{\"id\":3},{\"id\":5}],\"right\":[{\"id\":4}]}"
+        );
+        assert_eq!(
+            validate(vec![], vec![1]).await.unwrap_err().to_string(),
+            "EqualityValidate failed. 'expected' has 0 rows not found in 'actual' and 'actual' has 1 rows not found in 'expected'. Sample: {\"left\":[],\"right\":[{\"id\":1}]}"
+        );
+
+        // rows are compared as a multiset so duplicates must match
+        assert_eq!(
+            validate(vec![1, 1, 2], vec![1, 2, 2]).await.unwrap_err().to_string(),
+            "EqualityValidate failed. 'expected' has 1 rows not found in 'actual' and 'actual' has 1 rows not found in 'expected'. Sample: {\"left\":[{\"id\":1}],\"right\":[{\"id\":2}]}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_schema_mismatch() -> Result<()> {
+        let mut ctx = ExecutionContext::new();
+        register_view(
+            &mut ctx,
+            "expected",
+            vec![("id", Arc::new(Int32Array::from(vec![1])))],
+        )?;
+        register_view(
+            &mut ctx,
+            "actual",
+            vec![("id", Arc::new(StringArray::from(vec!["1"])))],
+        )?;
+        let result = EqualityValidate::try_new(
+            json!({"type": "EqualityValidate", "leftView": "expected", "rightView": "actual"})
+                .to_string(),
+        )?
+        .execute(BoxContext::new(None, None), &mut ctx)
+        .await;
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "EqualityValidate failed as schemas do not match. 'expected': [id: Int32] 'actual': [id: Utf8]."
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,533 @@
+use std::fmt;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use datafusion::arrow::array::{Array, ArrayRef, Date64Array, TimestampNanosecondArray};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::{DataType, TimeUnit};
+use datafusion::prelude::*;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::api::*;
+use crate::util::rows::value_as_usize;
+use crate::util::*;
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExpectationsValidate {
+    #[serde(rename(serialize = "type", deserialize = "type"))]
+    _type: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+
+    #[serde(rename = "inputView")]
+    input_view: String,
+
+    expectations: Vec<Expectation>,
+
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    results: Option<Vec<ExpectationResult>>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Report the failure but continue the job
+    Warn,
+
+    /// Fail the job
+    #[default]
+    Fail,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(tag = "type", rename_all = "camelCase", deny_unknown_fields)]
+pub enum Expectation {
+    NotNull {
+        column: String,
+        #[serde(default)]
+        severity: Severity,
+    },
+    Unique {
+        column: String,
+        #[serde(default)]
+        severity: Severity,
+    },
+    InSet {
+        column: String,
+        values: Vec<Value>,
+        #[serde(default)]
+        severity: Severity,
+    },
+    RegexMatch {
+        column: String,
+        pattern: String,
+        #[serde(default)]
+        severity: Severity,
+    },
+    Between {
+        column: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        min: Option<Value>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        max: Option<Value>,
+        #[serde(default)]
+        severity: Severity,
+    },
+    RowCount {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        min: Option<usize>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        max: Option<usize>,
+        #[serde(default)]
+        severity: Severity,
+    },
+    Freshness {
+        column: String,
+        #[serde(rename = "maxAgeSeconds")]
+        max_age_seconds: i64,
+        #[serde(default)]
+        severity: Severity,
+    },
+}
+
+impl Expectation {
+    fn severity(&self) -> Severity {
+        match self {
+            Expectation::NotNull { severity, .. }
+            | Expectation::Unique { severity, .. }
+            | Expectation::InSet { severity, .. }
+            | Expectation::RegexMatch { severity, .. }
+            | Expectation::Between { severity, .. }
+            | Expectation::RowCount { severity, .. }
+            | Expectation::Freshness { severity, .. } => *severity,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ExpectationResult {
+    #[serde(flatten)]
+    expectation: Expectation,
+
+    success: bool,
+
+    /// The number of rows which did not meet the expectation
+    #[serde(rename = "unexpectedCount", skip_serializing_if = "Option::is_none")]
+    unexpected_count: Option<usize>,
+
+    /// The observed value for expectations over the whole view
+    #[serde(skip_serializing_if = "Option::is_none")]
+    observed: Option<Value>,
+}
+
+impl fmt::Display for ExpectationsValidate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", serde_json::to_string(self).unwrap())
+    }
+}
+
+impl ExpectationsValidate {
+    pub fn try_new(json: String) -> Result<ExpectationsValidate> {
+        serde_json::from_str::<ExpectationsValidate>(&json).map_err(BoxError::from)
+    }
+}
+
+#[async_trait]
+impl PipelineStage for ExpectationsValidate {
+    fn to_value(&self) -> serde_json::Value {
+        serde_json::to_value(&self).unwrap()
+    }
+
+    async fn execute(
+        &mut self,
+        _: BoxContext,
+        ctx: &mut ExecutionContext,
+    ) -> Result<Option<Arc<dyn DataFrame>>> {
+        let mut results = vec![];
+        for expectation in &self.expectations {
+            results.push(evaluate(ctx, &self.input_view, expectation).await?);
+        }
+
+        let failures = results
+            .iter()
+            .filter(|result| !result.success && result.expectation.severity() == Severity::Fail)
+            .map(serde_json::to_value)
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let total = results.len();
+        self.results = Some(results);
+
+        if !failures.is_empty() {
+            return Err(BoxError::new(format!(
+                "ExpectationsValidate failed with {} of {} expectations not met: {}",
+                failures.len(),
+                total,
+                Value::Array(failures)
+            )));
+        }
+
+        Ok(None)
+    }
+}
+
+/// Compile the expectation into DataFusion queries against the view and evaluate it
+async fn evaluate(
+    ctx: &mut ExecutionContext,
+    view: &str,
+    expectation: &Expectation,
+) -> Result<ExpectationResult> {
+    let view = quote_identifier(view);
+    let (unexpected_count, observed) = match expectation {
+        Expectation::NotNull { column, .. } => {
+            let sql = format!(
+                "SELECT COUNT(*) FROM {} WHERE {} IS NULL",
+                view,
+                quote_identifier(column)
+            );
+            (Some(query_count(ctx, &sql).await?), None)
+        }
+        Expectation::Unique { column, .. } => {
+            // the number of non-null rows which repeat an earlier value
+            let sql = format!(
+                "SELECT COUNT({}) - COUNT(DISTINCT {}) FROM {}",
+                quote_identifier(column),
+                quote_identifier(column),
+                view
+            );
+            (Some(query_count(ctx, &sql).await?), None)
+        }
+        Expectation::InSet { column, values, .. } => {
+            let sql = format!(
+                "SELECT COUNT(*) FROM {} WHERE {} IS NOT NULL AND {} NOT IN ({})",
+                view,
+                quote_identifier(column),
+                quote_identifier(column),
+                values
+                    .iter()
+                    .map(to_sql_literal)
+                    .collect::<Result<Vec<_>>>()?
+                    .join(", ")
+            );
+            (Some(query_count(ctx, &sql).await?), None)
+        }
+        Expectation::RegexMatch {
+            column, pattern, ..
+        } => {
+            // validate the pattern here for a clearer error than the query would give
+            Regex::new(pattern)?;
+            // regexp_match is null for values which do not match
+            let sql = format!(
+                "SELECT COUNT(*) FROM {} WHERE {} IS NOT NULL AND regexp_match(CAST({} AS VARCHAR), {}) IS NULL",
+                view,
+                quote_identifier(column),
+                quote_identifier(column),
+                to_sql_literal(&Value::String(pattern.to_owned()))?
+            );
+            (Some(query_count(ctx, &sql).await?), None)
+        }
+        Expectation::Between {
+            column, min, max, ..
+        } => {
+            let mut conditions = vec![];
+            if let Some(min) = min {
+                conditions.push(format!(
+                    "{} < {}",
+                    quote_identifier(column),
+                    to_sql_literal(min)?
+                ));
+            }
+            if let Some(max) = max {
+                conditions.push(format!(
+                    "{} > {}",
+                    quote_identifier(column),
+                    to_sql_literal(max)?
+                ));
+            }
+            if conditions.is_empty() {
+                return Err(BoxError::new(
+                    "Expectation 'between' requires at least one of 'min' or 'max'.".to_string(),
+                ));
+            }
+            let sql = format!(
+                "SELECT COUNT(*) FROM {} WHERE {}",
+                view,
+                conditions.join(" OR ")
+            );
+            (Some(query_count(ctx, &sql).await?), None)
+        }
+        Expectation::RowCount { .. } => {
+            let sql = format!("SELECT COUNT(*) FROM {}", view);
+            (None, Some(Value::from(query_count(ctx, &sql).await?)))
+        }
+        Expectation::Freshness { column, .. } => {
+            let sql = format!("SELECT MAX({}) FROM {}", quote_identifier(column), view);
+            let batches = ctx.sql(&sql).await?.collect().await?;
+            let age = match batches.iter().find(|batch| batch.num_rows() == 1) {
+                Some(batch) => age_seconds(batch.column(0))?,
+                None => None,
+            };
+            (None, Some(age.map(Value::from).unwrap_or(Value::Null)))
+        }
+    };
+
+    let success = match expectation {
+        Expectation::RowCount { min, max, .. } => {
+            let row_count = observed
+                .as_ref()
+                .and_then(Value::as_u64)
+                .unwrap_or_default() as usize;
+            min.is_none_or(|min| row_count >= min) && max.is_none_or(|max| row_count <= max)
+        }
+        Expectation::Freshness {
+            max_age_seconds, ..
+        } => observed
+            .as_ref()
+            .and_then(Value::as_i64)
+            .is_some_and(|age| age <= *max_age_seconds),
+        _ => unexpected_count == Some(0),
+    };
+
+    Ok(ExpectationResult {
+        expectation: expectation.clone(),
+        success,
+        unexpected_count,
+        observed,
+    })
+}
+
+async fn query_count(ctx: &mut ExecutionContext, sql: &str) -> Result<usize> {
+    let batches = ctx.sql(sql).await?.collect().await?;
+    match batches.iter().find(|batch| batch.num_rows() == 1) {
+        Some(batch) => Ok(value_as_usize(batch.column(0))?.unwrap_or_default()),
+        None => Ok(0),
+    }
+}
+
+/// The number of seconds between the latest timestamp or date and now
+fn age_seconds(array: &ArrayRef) -> Result<Option<i64>> {
+    if array.is_null(0) {
+        return Ok(None);
+    }
+
+    let seconds = match array.data_type() {
+        DataType::Timestamp(_, _) => {
+            let array = cast(array, &DataType::Timestamp(TimeUnit::Nanosecond, None))?;
+            let array = array
+                .as_any()
+                .downcast_ref::<TimestampNanosecondArray>()
+                .unwrap();
+            array.value(0) / 1_000_000_000
+        }
+        DataType::Date32 | DataType::Date64 => {
+            let array = cast(array, &DataType::Date64)?;
+            let array = array.as_any().downcast_ref::<Date64Array>().unwrap();
+            array.value(0) / 1_000
+        }
+        data_type => {
+            return Err(BoxError::new(format!(
+                "Expectation 'freshness' requires a timestamp or date column. Got '{}'.",
+                data_type
+            )))
+        }
+    };
+
+    Ok(Some(Utc::now().timestamp() - seconds))
+}
+
+fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+fn to_sql_literal(value: &Value) -> Result<String> {
+    match value {
+        Value::String(value) => Ok(format!("'{}'", value.replace('\'', "''"))),
+        Value::Number(value) => Ok(value.to_string()),
+        Value::Bool(value) => Ok(value.to_string().to_uppercase()),
+        Value::Null => Ok("NULL".to_string()),
+        value => Err(BoxError::new(format!(
+            "Expected expectation value to be a string, number or boolean. Got '{}'.",
+            value
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{
+        Date32Array, Float64Array, Int32Array, StringArray, TimestampSecondArray,
+    };
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_expectations_validate() -> Result<()> {
+        let mut ctx = ExecutionContext::new();
+        register_view(
+            &mut ctx,
+            "trips",
+            vec![
+                (
+                    "id",
+                    Arc::new(Int32Array::from(vec![Some(1), Some(2), Some(2), None])),
+                ),
+                (
+                    "vendor",
+                    Arc::new(StringArray::from(vec!["CMT", "VTS", "XX", "CMT"])),
+                ),
+                (
+                    "fare",
+                    Arc::new(Float64Array::from(vec![1.0, 5.0, 500.0, 2.0])),
+                ),
+            ],
+        )?;
+
+        let mut stage = ExpectationsValidate::try_new(
+            json!({
+                "type": "ExpectationsValidate",
+                "inputView": "trips",
+                "expectations": [
+                    {"type": "notNull", "column": "id"},
+                    {"type": "unique", "column": "id", "severity": "warn"},
+                    {"type": "inSet", "column": "vendor", "values": ["CMT", "VTS"], "severity": "warn"},
+                    {"type": "regexMatch", "column": "vendor", "pattern": "^[A-Z]{3}$", "severity": "warn"},
+                    {"type": "between", "column": "fare", "min": 0, "max": 100, "severity": "warn"},
+                    {"type": "rowCount", "min": 1, "max": 10}
+                ]
+            })
+            .to_string(),
+        )?;
+        let err = stage
+            .execute(BoxContext::new(None, None), &mut ctx)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "ExpectationsValidate failed with 1 of 6 expectations not met: [{\"type\":\"notNull\",\"column\":\"id\",\"severity\":\"fail\",\"success\":false,\"unexpectedCount\":1}]"
+        );
+
+        let results = stage.to_value()["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|result| (result["success"].clone(), result["unexpectedCount"].clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            results,
+            vec![
+                (json!(false), json!(1)),
+                (json!(false), json!(1)),
+                (json!(false), json!(1)),
+                (json!(false), json!(1)),
+                (json!(false), json!(1)),
+                (json!(true), Value::Null),
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_freshness_and_warn() -> Result<()> {
+        let mut ctx = ExecutionContext::new();
+        let now = Utc::now().timestamp();
+        register_view(
+            &mut ctx,
+            "events",
+            vec![
+                (
+                    "updated",
+                    Arc::new(TimestampSecondArray::from_vec(
+                        vec![now - 3600, now - 60],
+                        None,
+                    )),
+                ),
+                // 1970-01-01
+                ("created", Arc::new(Date32Array::from(vec![0, 0]))),
+                (
+                    "missing",
+                    Arc::new(TimestampSecondArray::from(vec![None, None])),
+                ),
+                ("name", Arc::new(StringArray::from(vec!["a", "b"]))),
+            ],
+        )?;
+
+        // only expectations with the default severity of fail fail the stage
+        let mut stage = ExpectationsValidate::try_new(
+            json!({
+                "type": "ExpectationsValidate",
+                "inputView": "events",
+                "expectations": [
+                    {"type": "freshness", "column": "updated", "maxAgeSeconds": 300},
+                    {"type": "freshness", "column": "created", "maxAgeSeconds": 86400, "severity": "warn"},
+                    {"type": "freshness", "column": "missing", "maxAgeSeconds": 86400, "severity": "warn"}
+                ]
+            })
+            .to_string(),
+        )?;
+        assert!(stage
+            .execute(BoxContext::new(None, None), &mut ctx)
+            .await?
+            .is_none());
+
+        let results = stage.to_value()["results"].as_array().unwrap().clone();
+        assert_eq!(results[0]["success"], json!(true));
+        let age = results[0]["observed"].as_i64().unwrap();
+        assert!((60..300).contains(&age));
+        assert_eq!(results[1]["success"], json!(false));
+        assert!(results[1]["observed"].as_i64().unwrap() >= now);
+        // a column of only nulls has no age and is not fresh
+        assert_eq!(results[2]["success"], json!(false));
+        assert_eq!(results[2]["observed"], Value::Null);
+
+        // the same expectation with the default severity fails the stage
+        let mut stage = ExpectationsValidate::try_new(
+            json!({
+                "type": "ExpectationsValidate",
+                "inputView": "events",
+                "expectations": [
+                    {"type": "freshness", "column": "created", "maxAgeSeconds": 86400}
+                ]
+            })
+            .to_string(),
+        )?;
+        assert!(stage
+            .execute(BoxContext::new(None, None), &mut ctx)
+            .await
+            .is_err());
+
+        let mut stage = ExpectationsValidate::try_new(
+            json!({
+                "type": "ExpectationsValidate",
+                "inputView": "events",
+                "expectations": [
+                    {"type": "freshness", "column": "name", "maxAgeSeconds": 86400}
+                ]
+            })
+            .to_string(),
+        )?;
+        assert_eq!(
+            stage
+                .execute(BoxContext::new(None, None), &mut ctx)
+                .await
+                .unwrap_err()
+                .to_string(),
+            "Expectation 'freshness' requires a timestamp or date column. Got 'Utf8'."
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_quote_identifier() {
+        assert_eq!(quote_identifier("fare"), "\"fare\"");
+        assert_eq!(quote_identifier("a\"b"), "\"a\"\"b\"");
+    }
+}
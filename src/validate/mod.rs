@@ -0,0 +1,7 @@
+mod equality_validate;
+mod expectations_validate;
+mod sql_validate;
+
+pub use equality_validate::EqualityValidate;
+pub use expectations_validate::ExpectationsValidate;
+pub use sql_validate::SQLValidate;
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datafusion::arrow::array::{Array, BooleanArray, StringArray};
+use datafusion::arrow::datatypes::DataType;
+use datafusion::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::api::*;
+use crate::util::*;
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SQLValidate {
+    #[serde(rename(serialize = "type", deserialize = "type"))]
+    _type: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+
+    sql: String,
+
+    #[serde(
+        rename = "inputViews",
+        skip_deserializing,
+        skip_serializing_if = "Option::is_none"
+    )]
+    input_views: Option<Vec<String>>,
+
+    #[serde(rename = "sqlParams", default)]
+    sql_params: HashMap<String, String>,
+
+    /// The message returned by a successful validation
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    message: Option<Value>,
+}
+
+impl fmt::Display for SQLValidate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", serde_json::to_string(self).unwrap())
+    }
+}
+
+impl SQLValidate {
+    pub fn try_new(json: String) -> Result<SQLValidate> {
+        serde_json::from_str::<SQLValidate>(&json).map_err(BoxError::from)
+    }
+}
+
+#[async_trait]
+impl PipelineStage for SQLValidate {
+    fn to_value(&self) -> serde_json::Value {
+        serde_json::to_value(&self).unwrap()
+    }
+
+    async fn execute(
+        &mut self,
+        _: BoxContext,
+        ctx: &mut ExecutionContext,
+    ) -> Result<Option<Arc<dyn DataFrame>>> {
+        // substitute any variables
+        self.sql =
+            variables::substitute_variables(self.sql.to_owned(), &self.sql_params, false, false)?;
+
+        // calculate the input views by traversing the plan
+        let plan = ctx.create_logical_plan(&self.sql).map_err(BoxError::from)?;
+        let mut visitor = lineage_visitor::LineageVisitor::new(false);
+        plan.accept(&mut visitor).unwrap();
+        self.input_views = Some(visitor.table_scan);
+
+        // run the sql
+        let df = ctx.sql(&self.sql).await.map_err(BoxError::from)?;
+
+        // the columns are found by name so may be in any order
+        let schema = df.schema();
+        let column = |name: &str, data_types: &[DataType]| {
+            schema
+                .fields()
+                .iter()
+                .position(|field| field.name() == name && data_types.contains(field.data_type()))
+        };
+        let (valid_index, message_index) = match (
+            column("valid", &[DataType::Boolean]),
+            column("message", &[DataType::Utf8, DataType::Null]),
+        ) {
+            (Some(valid_index), Some(message_index)) => (valid_index, message_index),
+            _ => {
+                return Err(BoxError::new(format!(
+                    "SQLValidate requires query to return 1 row with a boolean 'valid' and a string 'message' column. Query has {} columns [{}].",
+                    schema.fields().len(),
+                    schema
+                        .fields()
+                        .iter()
+                        .map(|field| format!("{}: {}", field.name(), field.data_type()))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )))
+            }
+        };
+
+        let batches = df.collect().await?;
+        let rows = batches.iter().map(|batch| batch.num_rows()).sum::<usize>();
+        if rows != 1 {
+            return Err(BoxError::new(format!(
+                "SQLValidate requires query to return 1 row with a boolean 'valid' and a string 'message' column. Query has {} rows.",
+                rows
+            )));
+        }
+        let batch = batches.iter().find(|batch| batch.num_rows() == 1).unwrap();
+
+        let valid = batch
+            .column(valid_index)
+            .as_any()
+            .downcast_ref::<BooleanArray>()
+            .filter(|valid| !valid.is_null(0))
+            .map(|valid| valid.value(0))
+            .ok_or_else(|| {
+                BoxError::new("SQLValidate requires the 'valid' value to be non-null.".to_string())
+            })?;
+
+        // the message is expected to be json but fall back to the raw string
+        let message = batch
+            .column(message_index)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .filter(|message| !message.is_null(0))
+            .map(|message| message.value(0))
+            .map(|message| {
+                serde_json::from_str::<Value>(message)
+                    .unwrap_or_else(|_| Value::String(message.to_owned()))
+            });
+
+        if !valid {
+            return Err(BoxError::new(format!(
+                "SQLValidate failed with message: '{}'.",
+                message
+                    .map(|message| message.to_string())
+                    .unwrap_or_else(|| "null".to_string())
+            )));
+        }
+
+        self.message = message;
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    async fn validate(sql: &str) -> Result<Value> {
+        let mut stage =
+            SQLValidate::try_new(json!({"type": "SQLValidate", "sql": sql}).to_string())?;
+        let result = stage
+            .execute(BoxContext::new(None, None), &mut ExecutionContext::new())
+            .await?;
+        assert!(result.is_none());
+        Ok(stage.to_value()["message"].clone())
+    }
+
+    #[tokio::test]
+    async fn test_sql_validate() -> Result<()> {
+        assert_eq!(
+            validate("SELECT '{\"count\": 1}' AS message, TRUE AS valid").await?,
+            json!({"count": 1})
+        );
+        assert_eq!(
+            validate("SELECT TRUE AS valid, 'not json' AS message").await?,
+            json!("not json")
+        );
+
+        let err = validate("SELECT FALSE AS valid, '{\"count\": 0}' AS message")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "SQLValidate failed with message: '{\"count\":0}'."
+        );
+
+        assert!(validate("SELECT TRUE AS ok, 'message' AS message")
+            .await
+            .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+//! Load the plugin in `tests/plugin_fixture`, which implements the C ABI without depending on box,
+//! and use its stage and function.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+
+use box_lib::plugin::Registry;
+use box_lib::util::Result;
+use box_lib::BoxContext;
+use datafusion::arrow::array::{ArrayRef, Int64Array, StringArray};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::arrow::util::display::array_value_to_string;
+use datafusion::datasource::MemTable;
+use datafusion::prelude::*;
+use serde_json::json;
+
+/// Build the fixture in its own target directory as the build directory of this test is locked
+fn build_fixture() -> PathBuf {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("plugin_fixture");
+    let status = Command::new(env!("CARGO"))
+        .arg("build")
+        .arg("--manifest-path")
+        .arg(manifest_dir.join("tests/plugin_fixture/Cargo.toml"))
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    target_dir.join("debug").join(format!(
+        "{}box_plugin_fixture.{}",
+        std::env::consts::DLL_PREFIX,
+        std::env::consts::DLL_EXTENSION
+    ))
+}
+
+#[tokio::test]
+async fn test_load() -> Result<()> {
+    let mut registry = Registry::default();
+    registry.load(&build_fixture())?;
+
+    let mut ctx = ExecutionContext::new();
+    registry.register_functions(&mut ctx);
+    let batch = RecordBatch::try_from_iter(vec![
+        ("id", Arc::new(Int64Array::from(vec![1, 2, 3])) as ArrayRef),
+        (
+            "name",
+            Arc::new(StringArray::from(vec!["a", "b", "c"])) as ArrayRef,
+        ),
+    ])?;
+    ctx.register_table(
+        "trips",
+        Arc::new(MemTable::try_new(batch.schema(), vec![vec![batch]])?),
+    )?;
+
+    let batches = ctx
+        .sql("SELECT fixture_answer(id) FROM trips")
+        .await?
+        .collect()
+        .await?;
+    assert_eq!(batches[0].num_rows(), 3);
+    assert_eq!(array_value_to_string(batches[0].column(0), 2)?, "42");
+
+    // the rows of the input view are returned by the plugin and registered as the output view
+    let stage = json!({
+        "type": "FixturePassthrough",
+        "inputView": "trips",
+        "outputView": "copy"
+    });
+    registry
+        .create_stage(stage.as_object().unwrap(), &HashMap::new(), false, false)?
+        .execute(BoxContext::new(None, None), &mut ctx)
+        .await?;
+    let batches = ctx
+        .sql("SELECT name FROM copy ORDER BY id")
+        .await?
+        .collect()
+        .await?;
+    assert_eq!(array_value_to_string(batches[0].column(0), 1)?, "b");
+
+    let stage = json!({
+        "type": "FixturePassthrough",
+        "inputView": "trips",
+        "outputView": "copy",
+        "fail": true
+    });
+    let result = registry
+        .create_stage(stage.as_object().unwrap(), &HashMap::new(), false, false)?
+        .execute(BoxContext::new(None, None), &mut ctx)
+        .await;
+    assert_eq!(
+        result.err().unwrap().to_string(),
+        "FixturePassthrough failed."
+    );
+
+    // a required field of the stage's info is checked when the stage is created
+    let stage = json!({"type": "FixturePassthrough", "inputView": "trips"});
+    assert!(registry
+        .create_stage(stage.as_object().unwrap(), &HashMap::new(), false, false)
+        .is_err());
+
+    // the plugin's function must be dropped before its library is unloaded
+    drop(ctx);
+    drop(registry);
+
+    assert!(Registry::default()
+        .load(&Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml"))
+        .is_err());
+    Ok(())
+}
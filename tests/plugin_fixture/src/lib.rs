@@ -0,0 +1,225 @@
+//! A plugin loaded by the plugin integration test. It declares the C ABI and the Arrow C data
+//! interface itself, as a plugin written in another language would, and registers:
+//!
+//! - `fixture_answer(expr)` which returns 42 for every row
+//! - `FixturePassthrough` which returns the rows of its `inputView`, or fails if `fail` is true
+
+// the C structs are declared in full although only some of their fields are read
+#![allow(dead_code)]
+
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+
+#[repr(C)]
+pub struct ArrowSchema {
+    format: *const c_char,
+    name: *const c_char,
+    metadata: *const c_char,
+    flags: i64,
+    n_children: i64,
+    children: *mut *mut ArrowSchema,
+    dictionary: *mut ArrowSchema,
+    release: Option<unsafe extern "C" fn(*mut ArrowSchema)>,
+    private_data: *mut c_void,
+}
+
+#[repr(C)]
+pub struct ArrowArray {
+    length: i64,
+    null_count: i64,
+    offset: i64,
+    n_buffers: i64,
+    n_children: i64,
+    buffers: *mut *const c_void,
+    children: *mut *mut ArrowArray,
+    dictionary: *mut ArrowArray,
+    release: Option<unsafe extern "C" fn(*mut ArrowArray)>,
+    private_data: *mut c_void,
+}
+
+#[repr(C)]
+pub struct PluginDeclaration {
+    abi_version: u32,
+    register: unsafe extern "C" fn(*mut PluginHost),
+}
+
+#[repr(C)]
+pub struct PluginHost {
+    register_stage: unsafe extern "C" fn(*mut PluginHost, *const PluginStage),
+    register_function: unsafe extern "C" fn(*mut PluginHost, *const PluginFunction),
+}
+
+#[repr(C)]
+pub struct PluginStage {
+    stage_type: *const c_char,
+    info: *const c_char,
+    data: *mut c_void,
+    execute: unsafe extern "C" fn(
+        *mut c_void,
+        *const c_char,
+        *mut ArrowArray,
+        *mut ArrowSchema,
+        *mut ArrowArray,
+        *mut ArrowSchema,
+        *mut PluginError,
+    ) -> i32,
+}
+
+#[repr(C)]
+pub struct PluginFunction {
+    name: *const c_char,
+    num_args: usize,
+    return_type: *const c_char,
+    volatile: bool,
+    data: *mut c_void,
+    invoke: unsafe extern "C" fn(
+        *mut c_void,
+        *const *mut ArrowArray,
+        *const *mut ArrowSchema,
+        usize,
+        *mut ArrowArray,
+        *mut ArrowSchema,
+        *mut PluginError,
+    ) -> i32,
+}
+
+#[repr(C)]
+pub struct PluginError {
+    set_message: unsafe extern "C" fn(*mut PluginError, *const c_char),
+}
+
+const PLUGIN_OK: i32 = 0;
+const PLUGIN_RESULT: i32 = 1;
+const PLUGIN_ERROR: i32 = -1;
+
+#[no_mangle]
+#[allow(non_upper_case_globals)]
+pub static box_plugin_declaration: PluginDeclaration = PluginDeclaration {
+    abi_version: 1,
+    register,
+};
+
+unsafe extern "C" fn register(host: *mut PluginHost) {
+    let stage = PluginStage {
+        stage_type: b"FixturePassthrough\0".as_ptr() as *const c_char,
+        info: b"{\"description\": \"Return the rows of the input view\", \"required\": [\"inputView\", \"outputView\"], \"optional\": [\"fail\"]}\0"
+            .as_ptr() as *const c_char,
+        data: ptr::null_mut(),
+        execute: passthrough,
+    };
+    ((*host).register_stage)(host, &stage);
+
+    let function = PluginFunction {
+        name: b"fixture_answer\0".as_ptr() as *const c_char,
+        num_args: 1,
+        return_type: b"l\0".as_ptr() as *const c_char,
+        volatile: false,
+        data: ptr::null_mut(),
+        invoke: answer,
+    };
+    ((*host).register_function)(host, &function);
+}
+
+unsafe extern "C" fn passthrough(
+    _: *mut c_void,
+    config: *const c_char,
+    input: *mut ArrowArray,
+    input_schema: *mut ArrowSchema,
+    output: *mut ArrowArray,
+    output_schema: *mut ArrowSchema,
+    error: *mut PluginError,
+) -> i32 {
+    let config = std::ffi::CStr::from_ptr(config).to_string_lossy();
+    if config.contains("\"fail\":true") {
+        release(input, input_schema);
+        ((*error).set_message)(
+            error,
+            b"FixturePassthrough failed.\0".as_ptr() as *const c_char,
+        );
+        return PLUGIN_ERROR;
+    }
+    if input.is_null() {
+        return PLUGIN_OK;
+    }
+
+    // move the input to the output marking the input released
+    ptr::copy_nonoverlapping(input, output, 1);
+    ptr::copy_nonoverlapping(input_schema, output_schema, 1);
+    (*input).release = None;
+    (*input_schema).release = None;
+    PLUGIN_RESULT
+}
+
+unsafe extern "C" fn answer(
+    _: *mut c_void,
+    args: *const *mut ArrowArray,
+    arg_schemas: *const *mut ArrowSchema,
+    num_args: usize,
+    output: *mut ArrowArray,
+    output_schema: *mut ArrowSchema,
+    _: *mut PluginError,
+) -> i32 {
+    let length = (**args).length;
+    for index in 0..num_args {
+        release(*args.add(index), *arg_schemas.add(index));
+    }
+
+    // the values and the pointers to the [validity, values] buffers are freed on release
+    let values = vec![42i64; length as usize].into_boxed_slice();
+    let buffers = vec![ptr::null(), values.as_ptr() as *const c_void].into_boxed_slice();
+    let private_data = Box::into_raw(Box::new((values, buffers)));
+    ptr::write(
+        output,
+        ArrowArray {
+            length,
+            null_count: 0,
+            offset: 0,
+            n_buffers: 2,
+            n_children: 0,
+            buffers: (*private_data).1.as_ptr() as *mut *const c_void,
+            children: ptr::null_mut(),
+            dictionary: ptr::null_mut(),
+            release: Some(release_int64_array),
+            private_data: private_data as *mut c_void,
+        },
+    );
+    ptr::write(
+        output_schema,
+        ArrowSchema {
+            format: b"l\0".as_ptr() as *const c_char,
+            name: b"\0".as_ptr() as *const c_char,
+            metadata: ptr::null(),
+            flags: 2,
+            n_children: 0,
+            children: ptr::null_mut(),
+            dictionary: ptr::null_mut(),
+            release: Some(release_static_schema),
+            private_data: ptr::null_mut(),
+        },
+    );
+    PLUGIN_OK
+}
+
+unsafe fn release(array: *mut ArrowArray, schema: *mut ArrowSchema) {
+    if !array.is_null() {
+        if let Some(release) = (*array).release {
+            release(array);
+        }
+    }
+    if !schema.is_null() {
+        if let Some(release) = (*schema).release {
+            release(schema);
+        }
+    }
+}
+
+unsafe extern "C" fn release_int64_array(array: *mut ArrowArray) {
+    drop(Box::from_raw(
+        (*array).private_data as *mut (Box<[i64]>, Box<[*const c_void]>),
+    ));
+    (*array).release = None;
+}
+
+unsafe extern "C" fn release_static_schema(schema: *mut ArrowSchema) {
+    (*schema).release = None;
+}